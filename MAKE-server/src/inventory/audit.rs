@@ -0,0 +1,413 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::inventory::inventory::{Inventory, InventoryItem, Stock};
+use crate::now;
+
+/// Stocktakes of the inventory, and the stock changes made from them
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Audits {
+    pub sessions: Vec<AuditSession>,
+    pub adjustments: Vec<StockAdjustment>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum AuditStatus {
+    #[default]
+    Counting,
+    Finished,
+    Applied,
+    Cancelled,
+}
+
+/// A count of everything in a room, or one area of a room
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AuditSession {
+    pub id: String,
+    pub room: String,
+    pub area: Option<String>,
+    pub started_by: String,
+    pub status: AuditStatus,
+    pub counts: Vec<AuditCount>,
+    // Scanned UUIDs that don't belong to anything in the room or area
+    pub unknown_scans: Vec<String>,
+    pub timestamp_started: u64,
+    pub timestamp_finished: Option<u64>,
+    // Snapshot of the report taken when counting finished
+    pub report: Option<AuditReport>,
+}
+
+/// What was found on the shelf for one item
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AuditCount {
+    pub item_id: String,
+    pub counted: Stock,
+    pub scanned_uuids: Vec<String>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct AuditReport {
+    pub session_id: String,
+    pub lines: Vec<AuditLine>,
+    pub unknown_scans: Vec<String>,
+}
+
+/// Expected against counted stock for one item.
+/// `expected` is what should be on the shelf, so it leaves out what's checked out.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct AuditLine {
+    pub item_id: String,
+    pub name: String,
+    pub area: String,
+    pub expected: Stock,
+    pub checked_out: u64,
+    pub counted: Option<Stock>,
+    // Counted minus expected, for items counted exactly
+    pub difference: Option<i64>,
+    pub has_discrepancy: bool,
+    // Labelled units that weren't scanned
+    pub missing_uuids: Vec<String>,
+}
+
+/// A change to an item's stock made from an audit
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct StockAdjustment {
+    pub id: String,
+    pub item_id: String,
+    pub name: String,
+    pub previous: Stock,
+    pub new: Stock,
+    pub audit_id: String,
+    pub adjusted_by: String,
+    pub timestamp: u64,
+}
+
+/// Body of a request to start an audit
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AuditStart {
+    pub room: String,
+    pub area: Option<String>,
+}
+
+/// Body of a request to enter a count by hand
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AuditCountEntry {
+    pub item_id: String,
+    pub counted: Stock,
+}
+
+/// Body of a request to apply an audit's discrepancies to the inventory.
+/// Leaving out `item_ids` applies every discrepancy.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AuditApply {
+    pub item_ids: Vec<String>,
+}
+
+impl Audits {
+    pub fn get_session(&self, id: &str) -> Option<AuditSession> {
+        self.sessions.iter().find(|x| x.id == id).cloned()
+    }
+
+    fn get_session_mut(&mut self, id: &str) -> Result<&mut AuditSession, String> {
+        self.sessions
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or(format!("No audit found with id {}", id))
+    }
+
+    /// Starts counting a room, or an area of it. Only one count per area can run at once.
+    /// `started_by` is whoever made the request, see `Actor::get_name`.
+    pub fn start(
+        &mut self,
+        start: AuditStart,
+        started_by: String,
+        inventory: &Inventory,
+    ) -> Result<AuditSession, String> {
+        let session = AuditSession {
+            id: Uuid::new_v4().to_string(),
+            room: start.room.trim().to_string(),
+            area: start.area.map(|x| x.trim().to_string()).filter(|x| !x.is_empty()),
+            started_by,
+            timestamp_started: now(),
+            ..Default::default()
+        };
+
+        if session.get_items(inventory).is_empty() {
+            return Err(format!("No items found in {}", session.get_location()));
+        }
+
+        let overlapping = self.sessions.iter().any(|x| {
+            x.status == AuditStatus::Counting
+                && x.room.eq_ignore_ascii_case(&session.room)
+                && (x.area.is_none() || session.area.is_none() || x.area == session.area)
+        });
+
+        if overlapping {
+            return Err(format!("{} is already being counted", session.get_location()));
+        }
+
+        info!("Starting audit {} of {}", session.id, session.get_location());
+
+        self.sessions.push(session.clone());
+
+        Ok(session)
+    }
+
+    /// Counts one labelled unit. Scanning the same UUID twice only counts it once.
+    pub fn scan(&mut self, id: &str, uuid: &str, inventory: &Inventory) -> Result<AuditSession, String> {
+        let session = self.get_session_mut(id)?;
+        session.check_counting()?;
+
+        let item = session
+            .get_items(inventory)
+            .into_iter()
+            .find(|x| x.uuids.contains(&uuid.to_string()));
+
+        if let Some(item) = item {
+            let count = session.get_count_mut(&item.id);
+
+            if !count.scanned_uuids.contains(&uuid.to_string()) {
+                count.scanned_uuids.push(uuid.to_string());
+            }
+
+            count.counted = Stock::Count(count.scanned_uuids.len() as u64);
+        } else if !session.unknown_scans.contains(&uuid.to_string()) {
+            session.unknown_scans.push(uuid.to_string());
+        }
+
+        Ok(session.clone())
+    }
+
+    /// Sets the count for an item by hand, replacing anything scanned
+    pub fn count(
+        &mut self,
+        id: &str,
+        entry: AuditCountEntry,
+        inventory: &Inventory,
+    ) -> Result<AuditSession, String> {
+        let session = self.get_session_mut(id)?;
+        session.check_counting()?;
+
+        if !session.get_items(inventory).iter().any(|x| x.id == entry.item_id) {
+            return Err(format!(
+                "Item {} isn't in {}",
+                entry.item_id,
+                session.get_location()
+            ));
+        }
+
+        let count = session.get_count_mut(&entry.item_id);
+        count.counted = entry.counted;
+        count.scanned_uuids = Vec::new();
+
+        Ok(session.clone())
+    }
+
+    /// Stops counting and saves the report
+    pub fn finish(&mut self, id: &str, inventory: &Inventory) -> Result<AuditReport, String> {
+        let session = self.get_session_mut(id)?;
+        session.check_counting()?;
+
+        let report = session.get_report(inventory);
+
+        session.status = AuditStatus::Finished;
+        session.timestamp_finished = Some(now());
+        session.report = Some(report.clone());
+
+        info!(
+            "Finished audit {} of {}, {} discrepancies",
+            session.id,
+            session.get_location(),
+            report.lines.iter().filter(|x| x.has_discrepancy).count()
+        );
+
+        Ok(report)
+    }
+
+    pub fn cancel(&mut self, id: &str) -> Result<(), String> {
+        let session = self.get_session_mut(id)?;
+        session.check_counting()?;
+
+        session.status = AuditStatus::Cancelled;
+        session.timestamp_finished = Some(now());
+
+        Ok(())
+    }
+
+    /// Sets the stock of counted items with discrepancies to what was counted,
+    /// recording an adjustment for each change
+    pub fn apply(
+        &mut self,
+        id: &str,
+        apply: AuditApply,
+        adjusted_by: String,
+        inventory: &mut Inventory,
+    ) -> Result<Vec<StockAdjustment>, String> {
+        let session = self.get_session_mut(id)?;
+
+        if session.status != AuditStatus::Finished {
+            return Err(format!("Audit is {:?}, not Finished", session.status));
+        }
+
+        let report = session.report.clone().unwrap_or_default();
+        let mut adjustments = Vec::new();
+
+        for line in report.lines.iter() {
+            if !line.has_discrepancy || line.counted.is_none() {
+                continue;
+            }
+
+            if !apply.item_ids.is_empty() && !apply.item_ids.contains(&line.item_id) {
+                continue;
+            }
+
+            let item = inventory.items.iter_mut().find(|x| x.id == line.item_id);
+
+            if item.is_none() {
+                continue;
+            }
+
+            let item = item.unwrap();
+
+            // What's checked out is still owned, so it goes back on top of the count
+            let new = match line.counted.unwrap() {
+                Stock::Count(count) => Stock::Count(count + line.checked_out),
+                level => level,
+            };
+
+            adjustments.push(StockAdjustment {
+                id: Uuid::new_v4().to_string(),
+                item_id: item.id.clone(),
+                name: item.name.clone(),
+                previous: item.quantity,
+                new,
+                audit_id: session.id.clone(),
+                adjusted_by: adjusted_by.clone(),
+                timestamp: now(),
+            });
+
+            info!("Adjusting {} from {} to {}", item.name, item.quantity, new);
+
+            item.quantity = new;
+        }
+
+        session.status = AuditStatus::Applied;
+
+        inventory.check_restock();
+
+        self.adjustments.extend(adjustments.clone());
+
+        Ok(adjustments)
+    }
+}
+
+impl AuditSession {
+    pub fn get_location(&self) -> String {
+        match &self.area {
+            Some(area) => format!("{} - {}", self.room, area),
+            None => self.room.clone(),
+        }
+    }
+
+    /// Items in the room or area being counted
+    pub fn get_items<'a>(&self, inventory: &'a Inventory) -> Vec<&'a InventoryItem> {
+        inventory
+            .items
+            .iter()
+            .filter(|item| {
+                item.location_room.eq_ignore_ascii_case(&self.room)
+                    && self
+                        .area
+                        .as_ref()
+                        .map(|area| item.location_area.eq_ignore_ascii_case(area))
+                        .unwrap_or(true)
+            })
+            .collect()
+    }
+
+    fn check_counting(&self) -> Result<(), String> {
+        if self.status == AuditStatus::Counting {
+            Ok(())
+        } else {
+            Err(format!("Audit is {:?}", self.status))
+        }
+    }
+
+    fn get_count_mut(&mut self, item_id: &str) -> &mut AuditCount {
+        let pos = self.counts.iter().position(|x| x.item_id == item_id);
+
+        if let Some(pos) = pos {
+            &mut self.counts[pos]
+        } else {
+            self.counts.push(AuditCount {
+                item_id: item_id.to_string(),
+                ..Default::default()
+            });
+            self.counts.last_mut().unwrap()
+        }
+    }
+
+    /// Compares what was counted to what should be on the shelf.
+    /// Items that haven't been counted yet are listed without a count.
+    pub fn get_report(&self, inventory: &Inventory) -> AuditReport {
+        let lines = self
+            .get_items(inventory)
+            .into_iter()
+            .map(|item| {
+                let count = self.counts.iter().find(|x| x.item_id == item.id);
+                let counted = count.map(|x| x.counted);
+
+                let expected = match item.quantity {
+                    Stock::Count(count) => Stock::Count(count.saturating_sub(item.checked_quantity)),
+                    level => level,
+                };
+
+                let difference = match (expected, counted) {
+                    (Stock::Count(expected), Some(Stock::Count(counted))) => {
+                        Some(counted as i64 - expected as i64)
+                    }
+                    _ => None,
+                };
+
+                let has_discrepancy = counted.is_some() && counted != Some(expected);
+
+                // Only units that were scanned can be missed
+                let missing_uuids = match count {
+                    Some(count) if !count.scanned_uuids.is_empty() => item
+                        .uuids
+                        .iter()
+                        .filter(|x| !count.scanned_uuids.contains(x))
+                        .cloned()
+                        .collect(),
+                    _ => Vec::new(),
+                };
+
+                AuditLine {
+                    item_id: item.id.clone(),
+                    name: item.name.clone(),
+                    area: item.location_area.clone(),
+                    expected,
+                    checked_out: item.checked_quantity,
+                    counted,
+                    difference,
+                    has_discrepancy,
+                    missing_uuids,
+                }
+            })
+            .collect();
+
+        AuditReport {
+            session_id: self.id.clone(),
+            lines,
+            unknown_scans: self.unknown_scans.clone(),
+        }
+    }
+}
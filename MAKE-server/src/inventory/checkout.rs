@@ -1,7 +1,6 @@
 use uuid::Uuid;
 
 use crate::*;
-use std::time::SystemTime;
 
 /// Struct that handles the checkout process.
 /// 
@@ -54,6 +53,24 @@ impl CheckoutLog {
         self.currently_checked_out.clone()
     }
 
+    /// Moves every checkout and reservation from one college ID to another, for fixing identities.
+    /// Returns how many entries were moved.
+    pub fn rekey_user(&mut self, from: &u64, to: &u64) -> usize {
+        let mut moved = 0;
+
+        for entry in self
+            .currently_checked_out
+            .iter_mut()
+            .chain(self.checkout_history.iter_mut())
+            .filter(|x| &x.college_id == from)
+        {
+            entry.college_id = *to;
+            moved += 1;
+        }
+
+        moved
+    }
+
     pub fn add_reservation(&mut self, entry: CheckoutLogEntry) {
         info!("Adding reservation entry: {:?}", entry);
         self.currently_checked_out.push(entry);
@@ -63,6 +80,15 @@ impl CheckoutLog {
         self.currently_checked_out.len() + self.checkout_history.len()
     }
 
+    /// Gets a checkout by its UUID, whether it's been checked in or not.
+    pub fn get_entry(&self, checkout_uuid: &str) -> Option<CheckoutLogEntry> {
+        self.currently_checked_out
+            .iter()
+            .chain(self.checkout_history.iter())
+            .find(|x| x.checkout_uuid == checkout_uuid)
+            .cloned()
+    }
+
     pub fn extend_checkout(&mut self, checkout_uuid: String, length: u64) -> Result<(), String> {
         info!("Extending checkout entry: {:?}", checkout_uuid);
         for entry in self.currently_checked_out.iter_mut() {
@@ -102,7 +128,7 @@ pub struct CheckoutLogEntry {
 
 impl CheckoutLogEntry {
     pub fn new(college_id: u64, length: u64, items: Vec<String>) -> Self {
-        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        let now = now();
         let expires = now + length;
         let checkout_uuid = Uuid::new_v4().to_string();
         CheckoutLogEntry {
@@ -133,7 +159,7 @@ impl CheckoutLogEntry {
     }
 
     pub fn is_expired(&self) -> bool {
-        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        let now = now();
         now > self.timestamp_expires
     }
 
@@ -158,17 +184,17 @@ impl CheckoutLogEntry {
     }
 
     pub fn num_24_hours_passed(&self) -> u64 {
-        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        let now = now();
         (now - self.timestamp_expires) / (60 * 60 * 24)
     }
 
     pub fn check_in(&mut self) {
         self.checked_in = true;
-        self.timestamp_checked_in = Some(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs());
+        self.timestamp_checked_in = Some(now());
     }
 
     pub fn extend_checkout(&mut self, length: u64) {
-        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        let now = now();
         self.timestamp_expires = now + length;
     }
 }
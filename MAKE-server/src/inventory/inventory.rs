@@ -1,16 +1,45 @@
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
 
 use crate::{
-    inventory::checkout::CheckoutLogEntry, management::emails::send_individual_email,
+    inventory::checkout::{CheckoutLog, CheckoutLogEntry},
+    inventory::restock::{RestockNotice, RestockStatus},
+    management::emails::send_individual_email,
+    now,
+    people::quiz_registry::QuizRegistry,
+    people::quizzes::QuizName,
+    people::users::{AuthLevel, User},
     EMAIL_TEMPLATES, MAKERSPACE_MANAGER_EMAIL, MEMORY_DATABASE,
 };
 
 const INVENTORY_URL: &str = "https://docs.google.com/spreadsheets/d/e/2PACX-1vTzvLVGN2H5mFpQLpstQyT5kgEu1CI8qlhY60j78mO0LQgDnTHs_ZKx39xiIO1h-w09ZXyOZ5GqOf5q/pub?gid=0&single=true&output=csv";
 
+// Column headers written by CSV export, which CSV import also understands
+const CSV_HEADERS: [&str; 17] = [
+    "ID",
+    "Name",
+    "Type",
+    "Quantity",
+    "Reorder Threshold",
+    "Target Level",
+    "Room",
+    "Area",
+    "Reorder URL",
+    "Specific Name",
+    "Serial Number",
+    "Brand",
+    "Model Number",
+    "UUIDs",
+    "Kit",
+    "Required Quizzes",
+    "Min Auth Level",
+];
+
 /// The state of the inventory.
 /// Contains the timestamp of the last update and the inventory.
+///
+/// The server owns the inventory. The Google Sheet is only used as a
+/// one-way import source, and only when `sheet_import` is enabled.
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Inventory {
@@ -18,6 +47,15 @@ pub struct Inventory {
     pub items: Vec<InventoryItem>,
     pub needs_restock: Vec<RestockNotice>,
     pub sent_restock_notice: bool,
+    pub sheet_import: bool,
+}
+
+/// Results of a CSV import
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct ImportReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub skipped: Vec<String>,
 }
 
 impl Inventory {
@@ -27,93 +65,198 @@ impl Inventory {
             items: Vec::new(),
             needs_restock: Vec::new(),
             sent_restock_notice: false,
+            sheet_import: false,
         }
     }
 
-    pub async fn update(&mut self) -> Result<(), reqwest::Error> {
-        // Get time as unix timestamp
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
-
-        let response = reqwest::get(INVENTORY_URL).await;
+    fn touch(&mut self) {
+        self.last_updated = now();
+    }
 
-        if let Ok(response) = response {
-            let data = response.text().await.expect("Failed to read inventory");
+    /// Fetches the inventory Google Sheet as CSV text
+    pub async fn fetch_sheet() -> Result<String, reqwest::Error> {
+        let response = reqwest::get(INVENTORY_URL).await?;
 
-            // Fetch csv file
-            let rdr = csv::Reader::from_reader(data.as_bytes());
+        response.text().await
+    }
 
-            // Parse csv file
-            let mut items = Vec::new();
+    /// Imports items from CSV text, matching columns by header name.
+    ///
+    /// Rows are matched to existing items by ID, then by name. Only the columns
+    /// present in the CSV are changed, and items missing from the CSV are kept.
+    pub fn import_csv(&mut self, csv_text: &str) -> Result<ImportReport, String> {
+        let mut rdr = csv::Reader::from_reader(csv_text.as_bytes());
 
-            let mut kits: Vec<String> = Vec::new();
+        let columns: Vec<Option<InventoryColumn>> = rdr
+            .headers()
+            .map_err(|e| e.to_string())?
+            .iter()
+            .map(InventoryColumn::from_header)
+            .collect();
 
-            for result in rdr.into_records() {
-                if let Ok(result) = result {
-                    // Create new item
-                    let item = InventoryItem::new_from_line(
-                        result.iter().map(|x| x.to_string()).collect(),
-                    );
+        if !columns.contains(&Some(InventoryColumn::Name)) {
+            return Err("CSV has no name column".to_string());
+        }
 
-                    if item.kit.is_some() {
-                        kits.push(item.clone().kit.unwrap());
-                    }
+        let mut report = ImportReport::default();
 
-                    items.push(item);
+        for (i, record) in rdr.records().enumerate() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    report.skipped.push(format!("Row {}: {}", i + 2, e));
+                    continue;
                 }
-            }
+            };
 
-            // Add is_kit to kits
-            for kit_name in kits {
-                let pos = items.iter().position(|x| x.name == kit_name);
+            let mut item = InventoryItem::default();
+            item.apply_record(&columns, &record);
 
-                let kit_items = items
-                    .iter()
-                    .filter(|x| x.kit == Some(kit_name.clone()) && x.name != kit_name)
-                    .map(|x| x.name.clone())
-                    .collect::<Vec<String>>();
+            if item.name.is_empty() {
+                report.skipped.push(format!("Row {}: no name", i + 2));
+                continue;
+            }
 
-                if let Some(pos) = pos {
-                    items[pos].is_kit = true;
-                    items[pos].kit_items = kit_items;
-                } else {
-                    warn!("Kit \"{}\" not found in inventory", kit_name);
+            let pos = self
+                .items
+                .iter()
+                .position(|x| !item.id.is_empty() && x.id == item.id)
+                .or(self.items.iter().position(|x| x.name == item.name));
+
+            if let Some(pos) = pos {
+                // Don't let a rename collide with another item
+                if self.items[pos].name != item.name && self.items.iter().any(|x| x.name == item.name) {
+                    report
+                        .skipped
+                        .push(format!("Row {}: name {} already exists", i + 2, item.name));
+                    continue;
                 }
+
+                let existing = &mut self.items[pos];
+                existing.apply_record(&columns, &record);
+                report.updated.push(existing.name.clone());
+            } else {
+                item.id = uuid::Uuid::new_v4().to_string();
+                report.created.push(item.name.clone());
+                self.items.push(item);
             }
+        }
 
-            // Iterate through checkouts and increment num_times_checked
-            let checkouts = MEMORY_DATABASE.lock().await.checkout_log.clone();
+        self.refresh_kits();
+        self.check_restock();
+        self.touch();
 
-            for item in items.iter_mut() {
-                item.num_times_checked = checkouts
-                    .checkout_history
-                    .iter()
-                    .filter(|x| x.items.contains(&item.name))
-                    .count() as u64;
+        info!(
+            "Imported inventory CSV: {} created, {} updated, {} skipped",
+            report.created.len(),
+            report.updated.len(),
+            report.skipped.len()
+        );
 
-                item.num_times_checked += checkouts
-                    .currently_checked_out
-                    .iter()
-                    .filter(|x| x.items.contains(&item.name))
-                    .count() as u64;
+        Ok(report)
+    }
+
+    /// Exports every item as CSV, using the same headers import expects
+    pub fn export_csv(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let mut wtr = csv::Writer::from_writer(Vec::new());
+
+        wtr.write_record(&CSV_HEADERS)?;
+
+        for item in self.items.iter() {
+            wtr.write_record(&item.to_record())?;
+        }
+
+        Ok(String::from_utf8(wtr.into_inner()?)?)
+    }
+
+    /// Gives every item without an ID a new one.
+    /// Items saved before IDs existed deserialize without one.
+    pub fn assign_ids(&mut self) {
+        for item in self.items.iter_mut() {
+            if item.id.is_empty() {
+                item.id = uuid::Uuid::new_v4().to_string();
             }
+        }
+    }
 
-            // If everything is ok, update timestamp and items
-            self.last_updated = now;
-            self.items = items;
+    /// Sets is_kit and kit_items for every item that other items list as their kit
+    pub fn refresh_kits(&mut self) {
+        let kits: Vec<String> = self
+            .items
+            .iter()
+            .filter_map(|x| x.kit.clone())
+            .collect();
 
-            Ok(())
-        } else {
-            Err(response.unwrap_err())
+        for item in self.items.iter_mut() {
+            item.is_kit = false;
+            item.kit_items = Vec::new();
+        }
+
+        for kit_name in kits {
+            let pos = self.items.iter().position(|x| x.name == kit_name);
+
+            let kit_items = self
+                .items
+                .iter()
+                .filter(|x| x.kit == Some(kit_name.clone()) && x.name != kit_name)
+                .map(|x| x.name.clone())
+                .collect::<Vec<String>>();
+
+            if let Some(pos) = pos {
+                self.items[pos].is_kit = true;
+                self.items[pos].kit_items = kit_items;
+            } else {
+                warn!("Kit \"{}\" not found in inventory", kit_name);
+            }
         }
     }
 
+    /// Counts how many times each item has ever been checked out
+    pub fn update_checkout_counts(&mut self, checkouts: &CheckoutLog) {
+        for item in self.items.iter_mut() {
+            item.num_times_checked = checkouts
+                .checkout_history
+                .iter()
+                .chain(checkouts.currently_checked_out.iter())
+                .filter(|x| x.items.contains(&item.name))
+                .count() as u64;
+        }
+    }
+
+    pub fn get_item_by_id(&self, id: &str) -> Option<InventoryItem> {
+        self.items.iter().find(|item| item.id == id).cloned()
+    }
+
     pub fn get_item_by_name(&self, name: &str) -> Option<InventoryItem> {
         self.items.iter().find(|item| item.name == name).cloned()
     }
 
+    /// Checks the user has every certification the named items need, and none have expired.
+    /// Gives an error listing what's missing for each item.
+    pub fn check_requirements(&self, names: &Vec<String>, user: &User, registry: &QuizRegistry) -> Result<(), String> {
+        let valid_quizzes = registry.get_valid_quizzes(user);
+
+        let missing: Vec<String> = names
+            .iter()
+            .filter_map(|name| self.get_item_by_name(name))
+            .filter_map(|item| {
+                let missing = item.get_missing_requirements(user, &valid_quizzes);
+
+                if missing.is_empty() {
+                    None
+                } else {
+                    Some(format!("{} needs {}", item.name, missing.join(", ")))
+                }
+            })
+            .collect();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Missing certifications: {}", missing.join("; ")))
+        }
+    }
+
     pub fn get_item_by_uuid(&self, uuid: &str) -> Option<InventoryItem> {
         self.items
             .iter()
@@ -130,6 +273,108 @@ impl Inventory {
         }
     }
 
+    /// Adds a new item, giving it a new ID. Names must be unique.
+    pub fn create_item(&mut self, mut item: InventoryItem) -> Result<InventoryItem, String> {
+        if item.name.trim().is_empty() {
+            return Err("Item must have a name".to_string());
+        }
+
+        if self.items.iter().any(|x| x.name == item.name) {
+            return Err(format!("Item {} already exists", item.name));
+        }
+
+        item.id = uuid::Uuid::new_v4().to_string();
+        item.clear_derived();
+
+        info!("Creating inventory item: {:?}", item.name);
+
+        self.items.push(item.clone());
+        self.refresh_kits();
+        self.check_restock();
+        self.touch();
+
+        Ok(item)
+    }
+
+    /// Replaces the item with the given ID, keeping the fields the server tracks
+    pub fn edit_item(&mut self, id: &str, mut item: InventoryItem) -> Result<InventoryItem, String> {
+        let pos = self.items.iter().position(|x| x.id == id);
+
+        if pos.is_none() {
+            return Err(format!("No item found with id {}", id));
+        }
+
+        let pos = pos.unwrap();
+
+        if item.name.trim().is_empty() {
+            return Err("Item must have a name".to_string());
+        }
+
+        if self.items.iter().any(|x| x.name == item.name && x.id != id) {
+            return Err(format!("Item {} already exists", item.name));
+        }
+
+        let existing = &self.items[pos];
+
+        item.id = existing.id.clone();
+        item.checked_quantity = existing.checked_quantity;
+        item.num_times_checked = existing.num_times_checked;
+        item.restock_pending = existing.restock_pending;
+
+        info!("Updating inventory item: {:?}", item.name);
+
+        self.items[pos] = item.clone();
+        self.refresh_kits();
+        self.check_restock();
+        self.touch();
+
+        Ok(item)
+    }
+
+    pub fn delete_item(&mut self, id: &str) -> Result<InventoryItem, String> {
+        let pos = self.items.iter().position(|x| x.id == id);
+
+        if let Some(pos) = pos {
+            let item = self.items.remove(pos);
+
+            info!("Deleting inventory item: {:?}", item.name);
+
+            self.refresh_kits();
+            self.touch();
+
+            Ok(item)
+        } else {
+            Err(format!("No item found with id {}", id))
+        }
+    }
+
+    /// Applies a batch of changes. Items with an existing ID are updated,
+    /// the rest are created. Errors are collected instead of stopping the batch.
+    pub fn bulk_edit(&mut self, bulk: InventoryBulkEdit) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        for item in bulk.upsert {
+            let result = if !item.id.is_empty() && self.items.iter().any(|x| x.id == item.id) {
+                let id = item.id.clone();
+                self.edit_item(&id, item)
+            } else {
+                self.create_item(item)
+            };
+
+            if let Err(e) = result {
+                errors.push(e);
+            }
+        }
+
+        for id in bulk.delete {
+            if let Err(e) = self.delete_item(&id) {
+                errors.push(e);
+            }
+        }
+
+        errors
+    }
+
     pub fn update_from_checkouts(&mut self, checkouts: &Vec<CheckoutLogEntry>) {
         self.items = self
             .items
@@ -145,8 +390,92 @@ impl Inventory {
             .collect();
     }
 
-    pub fn add_restock_notice(&mut self, notice: RestockNotice) {
-        self.needs_restock.push(notice);
+    /// Takes used material out of stock.
+    /// Only items counted exactly can be decremented, levels have to be set by hand.
+    pub fn use_material(&mut self, usage: &MaterialUsage) -> Result<InventoryItem, String> {
+        let pos = self
+            .items
+            .iter()
+            .position(|x| x.id == usage.item_id)
+            .ok_or(format!("No item found with id {}", usage.item_id))?;
+
+        let item = &mut self.items[pos];
+
+        match item.quantity {
+            Stock::Count(count) => {
+                if usage.amount > count {
+                    warn!(
+                        "Used {} of {} but only {} were in stock",
+                        usage.amount, item.name, count
+                    );
+                }
+
+                item.quantity = Stock::Count(count.saturating_sub(usage.amount));
+            }
+            Stock::Level(_) => {
+                return Err(format!(
+                    "{} is tracked by level, not by count. Set its level instead",
+                    item.name
+                ));
+            }
+        }
+
+        info!("Used {} of {}, now {}", usage.amount, item.name, item.quantity);
+
+        let item = item.clone();
+
+        self.check_restock();
+        self.touch();
+
+        Ok(item)
+    }
+
+    /// Files an authorized restock notice for every item that has fallen to its
+    /// reorder threshold, once per crossing. Items that already have an open
+    /// notice aren't given another one.
+    pub fn check_restock(&mut self) {
+        let mut notices = Vec::new();
+
+        for item in self.items.iter_mut() {
+            if !item.is_below_threshold() {
+                item.restock_pending = false;
+                continue;
+            }
+
+            if item.restock_pending {
+                continue;
+            }
+
+            item.restock_pending = true;
+
+            let has_open_notice = self
+                .needs_restock
+                .iter()
+                .any(|x| x.name == item.name && x.is_open());
+
+            if has_open_notice {
+                continue;
+            }
+
+            info!("{} is at or below its reorder threshold, adding restock notice", item.name);
+
+            notices.push(RestockNotice {
+                name: item.name.clone(),
+                current_quantity: item.quantity.to_string(),
+                requested_quantity: item.get_reorder_amount(),
+                notes: format!(
+                    "Automatic notice: at or below reorder threshold of {}",
+                    item.reorder_threshold.map(|x| x.to_string()).unwrap_or_default()
+                ),
+                authorized: true,
+                vendor_url: item.reorder_url.clone(),
+                ..Default::default()
+            });
+        }
+
+        for notice in notices {
+            self.add_restock_notice(notice);
+        }
     }
 
     pub async fn send_restock_notice(&mut self) {
@@ -156,7 +485,7 @@ impl Inventory {
 
         let steward_items: Vec<String> = self.needs_restock
             .iter_mut()
-            .filter(|x| x.notified == false && x.authorized == true)
+            .filter(|x| x.notified == false && x.authorized == true && x.status == RestockStatus::Requested)
             .map(|x| {
                 x.notified = true;
 
@@ -175,7 +504,7 @@ impl Inventory {
 
         let user_items: Vec<String> = self.needs_restock
             .iter_mut()
-            .filter(|x| x.notified == false && x.authorized == false)
+            .filter(|x| x.notified == false && x.authorized == false && x.status == RestockStatus::Requested)
             .map(|x| {
                 x.notified = true;
 
@@ -233,11 +562,15 @@ impl Inventory {
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct InventoryItem {
+    pub id: String,
     pub name: String,
     pub is_material: bool,
     pub is_tool: bool,
     pub checked_quantity: u64,
-    pub quantity: i64, // -1 is low, -2 is medium, -3 is high
+    pub quantity: Stock,
+    pub reorder_threshold: Option<Stock>,
+    pub target_level: Option<Stock>,
+    pub restock_pending: bool,
     pub location_room: String,
     pub location_area: String,
     pub reorder_url: String,
@@ -250,60 +583,356 @@ pub struct InventoryItem {
     pub kit: Option<String>,
     pub kit_items: Vec<String>,
     pub num_times_checked: u64,
+    // Certifications a user needs before they can check the item out
+    pub required_quizzes: Vec<QuizName>,
+    pub min_auth_level: Option<AuthLevel>,
 }
 
 impl InventoryItem {
-    pub fn new_from_line(line: Vec<String>) -> Self {
-        InventoryItem {
-            name: line[0].clone(),
-            is_material: line[1] == "M",
-            is_tool: line[1] == "T",
-            checked_quantity: 0,
-            quantity: {
-                if line[2] == "Low" {
-                    -1
-                } else if line[2] == "Medium" {
-                    -2
-                } else if line[2] == "High" {
-                    -3
-                } else {
-                    line[2].parse::<i64>().unwrap_or(0)
+    /// Sets the fields for each known column in a CSV record.
+    /// An item's ID is never changed once it has one, since checkouts and audits point to it.
+    pub fn apply_record(&mut self, columns: &Vec<Option<InventoryColumn>>, record: &csv::StringRecord) {
+        for (column, value) in columns.iter().zip(record.iter()) {
+            let value = value.trim();
+
+            match column {
+                Some(InventoryColumn::Id) => {
+                    if self.id.is_empty() {
+                        self.id = value.to_string()
+                    }
+                }
+                Some(InventoryColumn::Name) => self.name = value.to_string(),
+                Some(InventoryColumn::Type) => {
+                    self.is_material = value == "M";
+                    self.is_tool = value == "T";
+                }
+                Some(InventoryColumn::Quantity) => self.quantity = Stock::parse(value).unwrap_or_default(),
+                Some(InventoryColumn::ReorderThreshold) => self.reorder_threshold = Stock::parse(value),
+                Some(InventoryColumn::TargetLevel) => self.target_level = Stock::parse(value),
+                Some(InventoryColumn::Room) => self.location_room = value.to_string(),
+                Some(InventoryColumn::Area) => self.location_area = value.to_string(),
+                Some(InventoryColumn::ReorderUrl) => self.reorder_url = value.to_string(),
+                Some(InventoryColumn::SpecificName) => self.specific_name = value.to_string(),
+                Some(InventoryColumn::SerialNumber) => self.serial_number = value.to_string(),
+                Some(InventoryColumn::Brand) => self.brand = value.to_string(),
+                Some(InventoryColumn::ModelNumber) => self.model_number = value.to_string(),
+                Some(InventoryColumn::Uuids) => {
+                    self.uuids = value
+                        .split(&[',', '\n'][..])
+                        .map(|x| x.trim().to_string())
+                        .filter(|x| !x.is_empty())
+                        .collect::<Vec<String>>()
                 }
+                Some(InventoryColumn::Kit) => {
+                    self.kit = if value.len() > 0 {
+                        Some(value.to_string())
+                    } else {
+                        None
+                    }
+                }
+                Some(InventoryColumn::RequiredQuizzes) => {
+                    self.required_quizzes = value
+                        .split(&[',', '\n'][..])
+                        .map(|x| x.trim())
+                        .filter(|x| !x.is_empty())
+                        .map(QuizName::new)
+                        .collect()
+                }
+                Some(InventoryColumn::MinAuthLevel) => self.min_auth_level = AuthLevel::parse(value),
+                None => {}
+            }
+        }
+    }
+
+    /// Gets the item as a CSV row, in the order of `CSV_HEADERS`
+    pub fn to_record(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.name.clone(),
+            if self.is_material {
+                "M".to_string()
+            } else if self.is_tool {
+                "T".to_string()
+            } else {
+                String::new()
             },
-            location_room: line[3].clone(),
-            location_area: line[4].clone(),
-            reorder_url: line[5].clone(),
-            specific_name: line[6].clone(),
-            serial_number: line[7].clone(),
-            brand: line[8].clone(),
-            model_number: line[9].clone(),
-            uuids: line[10]
-                .split(&[',', '\n'][..])
+            self.quantity.to_string(),
+            self.reorder_threshold.map(|x| x.to_string()).unwrap_or_default(),
+            self.target_level.map(|x| x.to_string()).unwrap_or_default(),
+            self.location_room.clone(),
+            self.location_area.clone(),
+            self.reorder_url.clone(),
+            self.specific_name.clone(),
+            self.serial_number.clone(),
+            self.brand.clone(),
+            self.model_number.clone(),
+            self.uuids.join(","),
+            self.kit.clone().unwrap_or_default(),
+            self.required_quizzes
+                .iter()
                 .map(|x| x.to_string())
-                .collect::<Vec<String>>(),
-            kit: {
-                let trimmed = line[11].trim();
-                if trimmed.len() > 0 {
-                    Some(trimmed.to_string())
+                .collect::<Vec<String>>()
+                .join(","),
+            self.min_auth_level
+                .as_ref()
+                .map(|x| format!("{:?}", x))
+                .unwrap_or_default(),
+        ]
+    }
+
+    /// What the user is missing to check the item out, eg "Welding quiz".
+    /// `valid_quizzes` are the user's passed quizzes that haven't expired.
+    pub fn get_missing_requirements(&self, user: &User, valid_quizzes: &Vec<QuizName>) -> Vec<String> {
+        let passed = user.get_passed_quizzes();
+
+        let mut missing: Vec<String> = self
+            .required_quizzes
+            .iter()
+            .filter(|x| !valid_quizzes.contains(x))
+            .map(|x| {
+                if passed.contains(x) {
+                    format!("{} quiz (expired)", x)
                 } else {
-                    None
+                    format!("{} quiz", x)
                 }
-            },
-            is_kit: false,
-            kit_items: Vec::new(),
-            num_times_checked: 0,
+            })
+            .collect();
+
+        if let Some(min_auth_level) = &self.min_auth_level {
+            if &user.get_auth_level() < min_auth_level {
+                missing.push(format!("{:?} access", min_auth_level));
+            }
+        }
+
+        missing
+    }
+
+    /// Resets the fields the server computes from the checkout log and kits
+    pub fn clear_derived(&mut self) {
+        self.checked_quantity = 0;
+        self.num_times_checked = 0;
+        self.restock_pending = false;
+        self.is_kit = false;
+        self.kit_items = Vec::new();
+    }
+
+    /// Whether the stock is at or below the reorder threshold.
+    /// Counts can't be compared to levels, so a mismatched threshold never triggers.
+    pub fn is_below_threshold(&self) -> bool {
+        match (self.quantity, self.reorder_threshold) {
+            (Stock::Count(count), Some(Stock::Count(threshold))) => count <= threshold,
+            (Stock::Level(level), Some(Stock::Level(threshold))) => level <= threshold,
+            _ => false,
+        }
+    }
+
+    /// How much to order to get back to the target level.
+    /// Without a target, the notice leaves the amount to whoever orders it.
+    pub fn get_reorder_amount(&self) -> String {
+        match (self.quantity, self.target_level) {
+            (Stock::Count(count), Some(Stock::Count(target))) => {
+                target.saturating_sub(count).to_string()
+            }
+            (_, Some(target)) => format!("Up to {}", target),
+            (_, None) => "Unknown".to_string(),
+        }
+    }
+}
+
+/// Inventory CSV columns, matched by header name
+#[derive(Debug, Clone, PartialEq)]
+pub enum InventoryColumn {
+    Id,
+    Name,
+    Type,
+    Quantity,
+    ReorderThreshold,
+    TargetLevel,
+    Room,
+    Area,
+    ReorderUrl,
+    SpecificName,
+    SerialNumber,
+    Brand,
+    ModelNumber,
+    Uuids,
+    Kit,
+    RequiredQuizzes,
+    MinAuthLevel,
+}
+
+impl InventoryColumn {
+    /// Matches a header case-insensitively, ignoring spaces and punctuation
+    pub fn from_header(header: &str) -> Option<Self> {
+        let header: String = header
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        match header.as_str() {
+            "id" => Some(InventoryColumn::Id),
+            "name" | "item" | "itemname" => Some(InventoryColumn::Name),
+            "type" | "tm" | "toolmaterial" => Some(InventoryColumn::Type),
+            "quantity" | "qty" => Some(InventoryColumn::Quantity),
+            "reorderthreshold" | "reorderat" | "threshold" => Some(InventoryColumn::ReorderThreshold),
+            "targetlevel" | "target" | "restockto" => Some(InventoryColumn::TargetLevel),
+            "room" | "locationroom" => Some(InventoryColumn::Room),
+            "area" | "locationarea" | "location" => Some(InventoryColumn::Area),
+            "reorderurl" | "reorderlink" | "url" => Some(InventoryColumn::ReorderUrl),
+            "specificname" => Some(InventoryColumn::SpecificName),
+            "serialnumber" | "serial" => Some(InventoryColumn::SerialNumber),
+            "brand" | "manufacturer" => Some(InventoryColumn::Brand),
+            "modelnumber" | "model" => Some(InventoryColumn::ModelNumber),
+            "uuids" | "uuid" => Some(InventoryColumn::Uuids),
+            "kit" => Some(InventoryColumn::Kit),
+            "requiredquizzes" | "quizzes" | "certifications" => Some(InventoryColumn::RequiredQuizzes),
+            "minauthlevel" | "authlevel" => Some(InventoryColumn::MinAuthLevel),
+            _ => None,
         }
     }
 }
 
+/// Rough amount of stock for things that aren't worth counting exactly
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StockLevel {
+    Low,
+    Medium,
+    High,
+}
+
+/// How much of an item is in stock, either an exact count or a rough level.
+///
+/// Counts serialize as numbers and levels as "Low", "Medium" or "High".
+/// The old -1/-2/-3 level encoding is still accepted when reading.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(untagged, from = "StockValue")]
+pub enum Stock {
+    Count(u64),
+    Level(StockLevel),
+}
+
+impl Default for Stock {
+    fn default() -> Self {
+        Stock::Count(0)
+    }
+}
+
+impl Stock {
+    /// Parses a count or a level name, as written in the inventory sheet
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+
+        match value.to_lowercase().as_str() {
+            "" => None,
+            "low" => Some(Stock::Level(StockLevel::Low)),
+            "medium" => Some(Stock::Level(StockLevel::Medium)),
+            "high" => Some(Stock::Level(StockLevel::High)),
+            _ => value.parse::<i64>().ok().map(Stock::from_legacy),
+        }
+    }
+
+    /// Converts the old quantity encoding, where -1 is low, -2 is medium and -3 is high
+    pub fn from_legacy(quantity: i64) -> Self {
+        match quantity {
+            -1 => Stock::Level(StockLevel::Low),
+            -2 => Stock::Level(StockLevel::Medium),
+            -3 => Stock::Level(StockLevel::High),
+            _ => Stock::Count(quantity.max(0) as u64),
+        }
+    }
+}
+
+impl std::fmt::Display for Stock {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Stock::Count(count) => write!(f, "{}", count),
+            Stock::Level(level) => write!(f, "{:?}", level),
+        }
+    }
+}
+
+/// Anything `Stock` has ever been saved as
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StockValue {
+    Number(i64),
+    Text(String),
+}
+
+impl From<StockValue> for Stock {
+    fn from(value: StockValue) -> Self {
+        match value {
+            StockValue::Number(quantity) => Stock::from_legacy(quantity),
+            StockValue::Text(text) => Stock::parse(&text).unwrap_or_default(),
+        }
+    }
+}
+
+/// Body of a request to log material as used
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
-pub struct RestockNotice {
-    pub name: String,
-    pub current_quantity: String,
-    pub requested_quantity: String,
-    pub notes: String,
-    pub notified: bool,
-    pub email: String,
-    pub authorized: bool,
+pub struct MaterialUsage {
+    pub item_id: String,
+    pub amount: u64,
+}
+
+/// Body of a bulk edit request
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct InventoryBulkEdit {
+    pub upsert: Vec<InventoryItem>,
+    pub delete: Vec<String>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Inventory, InventoryItem, Stock};
+
+    fn inventory() -> Inventory {
+        Inventory {
+            items: vec![InventoryItem {
+                id: "drill-id".to_string(),
+                name: "Drill".to_string(),
+                quantity: Stock::Count(2),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn import_matches_by_name_without_changing_ids() {
+        let mut inventory = inventory();
+
+        let report = inventory
+            .import_csv("ID,Name,Quantity\nother-id,Drill,5\n,Saw,1\n")
+            .unwrap();
+
+        assert_eq!(report.updated, vec!["Drill".to_string()]);
+        assert_eq!(report.created, vec!["Saw".to_string()]);
+
+        let drill = inventory.get_item_by_name("Drill").unwrap();
+        assert_eq!(drill.id, "drill-id");
+        assert_eq!(drill.quantity, Stock::Count(5));
+        assert!(inventory.get_item_by_id("other-id").is_none());
+        assert!(!inventory.get_item_by_name("Saw").unwrap().id.is_empty());
+    }
+
+    #[test]
+    fn import_skips_renames_onto_existing_names() {
+        let mut inventory = inventory();
+        inventory.items.push(InventoryItem {
+            id: "saw-id".to_string(),
+            name: "Saw".to_string(),
+            ..Default::default()
+        });
+
+        let report = inventory.import_csv("ID,Name\nsaw-id,Drill\n,\n").unwrap();
+
+        assert_eq!(report.skipped.len(), 2);
+        assert!(report.updated.is_empty());
+        assert_eq!(inventory.get_item_by_id("saw-id").unwrap().name, "Saw");
+    }
 }
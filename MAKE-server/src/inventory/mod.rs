@@ -1,2 +1,5 @@
+pub mod audit;
 pub mod checkout;
-pub mod inventory;
\ No newline at end of file
+pub mod inventory;
+pub mod restock;
+pub mod search;
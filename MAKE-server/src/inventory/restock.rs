@@ -0,0 +1,260 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::inventory::inventory::{Inventory, Stock};
+use crate::now;
+
+// Keep received and rejected requests around for 90 days before removing them
+const CLOSED_RESTOCK_RETENTION: u64 = 90 * 24 * 60 * 60;
+
+/// Where a restock request is in the ordering process.
+/// Requests only move forward, and can be rejected at any point until received.
+#[derive(Default, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd)]
+pub enum RestockStatus {
+    #[default]
+    Requested,
+    Approved,
+    Ordered,
+    Received,
+    Rejected,
+}
+
+impl RestockStatus {
+    pub fn is_open(&self) -> bool {
+        !matches!(self, RestockStatus::Received | RestockStatus::Rejected)
+    }
+
+    pub fn can_transition_to(&self, next: &RestockStatus) -> bool {
+        self.is_open() && (*next == RestockStatus::Rejected || next > self)
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RestockNotice {
+    pub id: String,
+    pub name: String,
+    pub current_quantity: String,
+    pub requested_quantity: String,
+    pub notes: String,
+    pub notified: bool,
+    pub email: String,
+    pub authorized: bool,
+    pub status: RestockStatus,
+    pub assignee: Option<String>,
+    pub vendor_url: String,
+    pub cost_cents: Option<i64>,
+    pub timestamp_requested: u64,
+    pub timestamp_updated: u64,
+    pub timestamp_expected_arrival: Option<u64>,
+    // Emails of everyone else who asked for the same item while this was open
+    pub also_requested_by: Vec<String>,
+}
+
+impl RestockNotice {
+    pub fn is_open(&self) -> bool {
+        self.status.is_open()
+    }
+
+    /// A request from a user, keeping only what they're allowed to set
+    pub fn from_user(&self) -> RestockNotice {
+        RestockNotice {
+            name: self.name.clone(),
+            current_quantity: self.current_quantity.clone(),
+            requested_quantity: self.requested_quantity.clone(),
+            notes: self.notes.clone(),
+            email: self.email.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// The request without who asked for it or who's handling it, for anyone to see
+    pub fn redacted(&self) -> RestockNotice {
+        RestockNotice {
+            email: String::new(),
+            also_requested_by: Vec::new(),
+            assignee: None,
+            cost_cents: None,
+            ..self.clone()
+        }
+    }
+
+    /// Everyone who asked for this item and should hear when it arrives
+    pub fn get_requester_emails(&self) -> Vec<String> {
+        let mut emails = vec![self.email.clone()];
+        emails.extend(self.also_requested_by.iter().cloned());
+        emails.retain(|x| !x.trim().is_empty());
+        emails.dedup();
+
+        emails
+    }
+}
+
+/// Body of a request to move a restock request along.
+/// Fields left out are kept as they are.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RestockUpdate {
+    pub status: Option<RestockStatus>,
+    pub assignee: Option<String>,
+    pub vendor_url: Option<String>,
+    pub cost_cents: Option<i64>,
+    pub timestamp_expected_arrival: Option<u64>,
+    pub notes: Option<String>,
+    // Added to the item's stock when marked received, if it's counted exactly
+    pub received_quantity: Option<u64>,
+}
+
+impl Inventory {
+    /// Adds a restock request, merging it into the open request for the same
+    /// item if there is one. Returns the request it ended up in.
+    pub fn add_restock_notice(&mut self, mut notice: RestockNotice) -> RestockNotice {
+        let now = now();
+
+        let existing = self
+            .needs_restock
+            .iter_mut()
+            .find(|x| x.is_open() && x.name.eq_ignore_ascii_case(&notice.name));
+
+        if let Some(existing) = existing {
+            info!("Merging restock request for {} into {}", notice.name, existing.id);
+
+            // A steward's request vouches for a user's one
+            existing.authorized |= notice.authorized;
+
+            if !notice.notes.trim().is_empty() && !existing.notes.contains(&notice.notes) {
+                if existing.notes.is_empty() {
+                    existing.notes = notice.notes;
+                } else {
+                    existing.notes = format!("{}; {}", existing.notes, notice.notes);
+                }
+            }
+
+            if !notice.email.trim().is_empty()
+                && !existing.get_requester_emails().contains(&notice.email)
+            {
+                existing.also_requested_by.push(notice.email);
+            }
+
+            existing.timestamp_updated = now;
+
+            return existing.clone();
+        }
+
+        notice.id = Uuid::new_v4().to_string();
+        notice.status = RestockStatus::Requested;
+        notice.notified = false;
+        notice.timestamp_requested = now;
+        notice.timestamp_updated = now;
+
+        if notice.vendor_url.is_empty() {
+            if let Some(item) = self.get_item_by_name(&notice.name) {
+                notice.vendor_url = item.reorder_url;
+            }
+        }
+
+        info!("Adding restock request {} for {}", notice.id, notice.name);
+
+        self.needs_restock.push(notice.clone());
+
+        notice
+    }
+
+    pub fn get_restock_notice(&self, id: &str) -> Option<RestockNotice> {
+        self.needs_restock.iter().find(|x| x.id == id).cloned()
+    }
+
+    /// Applies an update to a restock request, checking the status change is allowed.
+    /// Receiving a request adds `received_quantity` to the item's stock.
+    pub fn update_restock_notice(
+        &mut self,
+        id: &str,
+        update: RestockUpdate,
+    ) -> Result<RestockNotice, String> {
+        let notice = self
+            .needs_restock
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or(format!("No restock request found with id {}", id))?;
+
+        if let Some(status) = update.status {
+            if status != notice.status && !notice.status.can_transition_to(&status) {
+                return Err(format!(
+                    "Can't move restock request from {:?} to {:?}",
+                    notice.status, status
+                ));
+            }
+        }
+
+        if let Some(status) = update.status {
+            notice.status = status;
+        }
+        if let Some(assignee) = update.assignee {
+            notice.assignee = Some(assignee).filter(|x| !x.trim().is_empty());
+        }
+        if let Some(vendor_url) = update.vendor_url {
+            notice.vendor_url = vendor_url;
+        }
+        if let Some(cost) = update.cost_cents {
+            notice.cost_cents = Some(cost);
+        }
+        if let Some(timestamp) = update.timestamp_expected_arrival {
+            notice.timestamp_expected_arrival = Some(timestamp);
+        }
+        if let Some(notes) = update.notes {
+            notice.notes = notes;
+        }
+
+        notice.timestamp_updated = now();
+
+        info!("Restock request {} for {} is now {:?}", notice.id, notice.name, notice.status);
+
+        let notice = notice.clone();
+
+        if notice.status == RestockStatus::Received {
+            if let Some(received) = update.received_quantity {
+                let item = self.items.iter_mut().find(|x| x.name == notice.name);
+
+                if let Some(item) = item {
+                    if let Stock::Count(count) = item.quantity {
+                        item.quantity = Stock::Count(count + received);
+                    }
+                }
+
+                self.check_restock();
+            }
+        }
+
+        Ok(notice)
+    }
+
+    /// Gives every restock request saved before IDs existed an ID
+    pub fn assign_restock_ids(&mut self) {
+        let now = now();
+
+        for notice in self.needs_restock.iter_mut() {
+            if notice.id.is_empty() {
+                notice.id = Uuid::new_v4().to_string();
+            }
+
+            if notice.timestamp_requested == 0 {
+                notice.timestamp_requested = now;
+                notice.timestamp_updated = now;
+            }
+        }
+    }
+
+    /// Removes requests that were received or rejected long enough ago
+    pub fn prune_restock_notices(&mut self) {
+        let cutoff = now().saturating_sub(CLOSED_RESTOCK_RETENTION);
+        let before = self.needs_restock.len();
+
+        self.needs_restock
+            .retain(|x| x.is_open() || x.timestamp_updated > cutoff);
+
+        if self.needs_restock.len() < before {
+            info!("Removed {} old restock requests", before - self.needs_restock.len());
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::inventory::{Inventory, InventoryItem, Stock};
+
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+/// Query parameters for an inventory search.
+/// Every filter is optional, and an empty `q` returns everything that passes the filters.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SearchQuery {
+    pub q: String,
+    pub room: Option<String>,
+    pub area: Option<String>,
+    pub is_tool: Option<bool>,
+    pub is_material: Option<bool>,
+    pub kit: Option<String>,
+    pub available: Option<bool>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct SearchResult {
+    pub item: InventoryItem,
+    pub score: f64,
+}
+
+impl Inventory {
+    /// Searches the inventory, ranking the best matches first
+    pub fn search(&self, query: &SearchQuery) -> Vec<SearchResult> {
+        let terms = tokenize(&query.q);
+
+        let mut results: Vec<SearchResult> = self
+            .items
+            .iter()
+            .filter(|item| query.matches_filters(item))
+            .filter_map(|item| {
+                let score = if terms.is_empty() {
+                    1.0
+                } else {
+                    score_item(item, &terms)
+                };
+
+                if score > 0.0 {
+                    Some(SearchResult {
+                        item: item.clone(),
+                        score: score * popularity_boost(item),
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        results.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.item.name.cmp(&b.item.name))
+        });
+
+        results.truncate(query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT));
+
+        results
+    }
+}
+
+impl SearchQuery {
+    pub fn matches_filters(&self, item: &InventoryItem) -> bool {
+        if let Some(room) = &self.room {
+            if !item.location_room.eq_ignore_ascii_case(room) {
+                return false;
+            }
+        }
+
+        if let Some(area) = &self.area {
+            if !item.location_area.eq_ignore_ascii_case(area) {
+                return false;
+            }
+        }
+
+        if let Some(is_tool) = self.is_tool {
+            if item.is_tool != is_tool {
+                return false;
+            }
+        }
+
+        if let Some(is_material) = self.is_material {
+            if item.is_material != is_material {
+                return false;
+            }
+        }
+
+        // Matches the kit itself and everything in it
+        if let Some(kit) = &self.kit {
+            let in_kit = item.kit.as_ref().map(|x| x.eq_ignore_ascii_case(kit)) == Some(true);
+
+            if !in_kit && !(item.is_kit && item.name.eq_ignore_ascii_case(kit)) {
+                return false;
+            }
+        }
+
+        if let Some(available) = self.available {
+            if is_available(item) != available {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Whether at least one of the item is on the shelf
+pub fn is_available(item: &InventoryItem) -> bool {
+    match item.quantity {
+        Stock::Count(count) => count > item.checked_quantity,
+        // Low/Medium/High levels are never fully checked out
+        Stock::Level(_) => true,
+    }
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect()
+}
+
+/// Scores how well every search term matches the item's fields.
+/// Items that don't match every term get 0.
+fn score_item(item: &InventoryItem, terms: &Vec<String>) -> f64 {
+    let fields = [
+        (tokenize(&item.name), 3.0),
+        (tokenize(&item.specific_name), 2.0),
+        (tokenize(&item.brand), 1.5),
+        (tokenize(&item.model_number), 1.5),
+        (tokenize(&item.serial_number), 1.0),
+    ];
+
+    let mut total = 0.0;
+
+    for term in terms {
+        let best = fields
+            .iter()
+            .map(|(words, weight)| {
+                words
+                    .iter()
+                    .map(|word| match_term(term, word))
+                    .fold(0.0, f64::max)
+                    * weight
+            })
+            .fold(0.0, f64::max);
+
+        if best == 0.0 {
+            return 0.0;
+        }
+
+        total += best;
+    }
+
+    total
+}
+
+/// How well a single search term matches a single word, from 0 to 1
+fn match_term(term: &str, word: &str) -> f64 {
+    if term == word {
+        return 1.0;
+    }
+
+    if word.starts_with(term) {
+        return 0.8;
+    }
+
+    if word.contains(term) {
+        return 0.6;
+    }
+
+    // Allow more typos the longer the term is
+    let allowed = match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    };
+
+    let distance = levenshtein(term, word);
+
+    if distance <= allowed {
+        0.5 - 0.1 * distance as f64
+    } else {
+        0.0
+    }
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Items that get checked out more often rank slightly higher
+fn popularity_boost(item: &InventoryItem) -> f64 {
+    1.0 + (1.0 + item.num_times_checked as f64).ln() / 10.0
+}
@@ -45,21 +45,39 @@ mod routes_post;
 pub use routes_get::*;
 pub use routes_post::*;
 
+pub use crate::inventory::audit::*;
 pub use crate::inventory::checkout::*;
 pub use crate::inventory::inventory::*;
+pub use crate::inventory::restock::*;
+pub use crate::inventory::search::*;
 
+pub use crate::machines::interlock::*;
 pub use crate::machines::laser_cutter::*;
 pub use crate::machines::loom::*;
+pub use crate::machines::maintenance::*;
 pub use crate::machines::printers::*;
 
+pub use crate::management::audit_log::*;
+pub use crate::management::billing::*;
+pub use crate::management::budget::*;
 pub use crate::management::emails::*;
+pub use crate::management::incidents::*;
+pub use crate::management::labels::*;
+pub use crate::management::lost_and_found::*;
 pub use crate::management::student_storage::*;
 pub use crate::management::workshops::*;
 pub use crate::management::spotify::*;
 
+pub use crate::people::identities::*;
 pub use crate::people::permissions::*;
+pub use crate::people::privacy::*;
+pub use crate::people::quiz_bank::*;
+pub use crate::people::quiz_registry::*;
 pub use crate::people::quizzes::*;
+pub use crate::people::roster::*;
 pub use crate::people::schedule::*;
+pub use crate::people::sign_offs::*;
+pub use crate::people::suspensions::*;
 pub use crate::people::usage::*;
 pub use crate::people::users::*;
 
@@ -88,6 +106,10 @@ const INITIAL_CHECKOUT_PERIOD: u64 = 30 * 24 * 60 * 60;
 const RENEW_LENGTH: u64 = 2 * 7 * 24 * 60 * 60;
 // Number of renewals allowed
 const RENEWALS_ALLOWED: u64 = 2;
+// Number of student storage slots a single user can hold
+const MAX_SLOTS_PER_USER: usize = 1;
+// Time a waitlisted user has to claim an offered slot, 2 days
+const WAITLIST_CLAIM_PERIOD: u64 = 2 * 24 * 60 * 60;
 
 const LOGGER_STR: &str = "\nMAKE Log @ %t\nIP: %a (%{r}a)\nRequest: \"%r\"\nAgent: \"%{Referer}i\" \"%{User-Agent}i\"\nResponse: STATUS %s for %b bytes in %D ms";
 const VERSION_STRING: &str = env!("CARGO_PKG_VERSION");
@@ -107,11 +129,25 @@ include!(concat!(env!("OUT_DIR"), "/generated.rs"));
 #[serde(default)]
 pub struct Data {
     pub inventory: Inventory,
+    pub audits: Audits,
     pub users: Users,
+    pub identities: Identities,
     pub printers: Printers,
+    pub maintenance: Maintenance,
     pub quizzes: Vec<Quiz>,
+    pub quiz_banks: QuizBanks,
+    pub quiz_registry: QuizRegistry,
+    pub sign_offs: SignOffs,
     pub checkout_log: CheckoutLog,
     pub student_storage: StudentStorage,
+    pub lost_and_found: LostAndFound,
+    pub incidents: Incidents,
+    pub budget: Budget,
+    pub billing: Billing,
+    pub privacy: Privacy,
+    pub audit_log: AuditLog,
+    pub roles: Roles,
+    pub suspensions: Suspensions,
     pub button_log: ButtonRecordLog,
     pub schedule: Schedule,
     pub workshops: Workshops,
@@ -166,6 +202,23 @@ impl ApiKeys {
         self.printers == key || self.admin == key
     }
 
+    /// Which key this is, for the audit log
+    pub fn get_key_name(&self, key: &str) -> String {
+        let name = if key == self.admin {
+            "admin"
+        } else if key == self.checkout {
+            "checkout"
+        } else if key == self.student_storage {
+            "student_storage"
+        } else if key == self.printers {
+            "printers"
+        } else {
+            "none"
+        };
+
+        name.to_string()
+    }
+
     pub fn get_gmail_tuple(&self) -> (String, String) {
         (self.gmail_email.clone(), self.gmail_password.clone())
     }
@@ -185,6 +238,14 @@ pub struct EmailTemplates {
     pub expired_student_storage: String,
     pub expired_checkout: String,
     pub restock_notice: String,
+    pub restock_fulfilled: String,
+    pub student_storage_offer: String,
+    pub lost_and_found: String,
+    pub material_statement: String,
+    pub maintenance_due: String,
+    pub certification_expiring: String,
+    pub suspension: String,
+    pub suspension_ended: String,
 }
 
 impl EmailTemplates {
@@ -194,6 +255,18 @@ impl EmailTemplates {
             self.html_file_to_string("email_templates/expired_student_storage.html");
         self.expired_checkout = self.html_file_to_string("email_templates/expired_checkout.html");
         self.restock_notice = self.html_file_to_string("email_templates/restock_notice.html");
+        self.restock_fulfilled =
+            self.html_file_to_string("email_templates/restock_fulfilled.html");
+        self.student_storage_offer =
+            self.html_file_to_string("email_templates/student_storage_offer.html");
+        self.lost_and_found = self.html_file_to_string("email_templates/lost_and_found.html");
+        self.material_statement =
+            self.html_file_to_string("email_templates/material_statement.html");
+        self.maintenance_due = self.html_file_to_string("email_templates/maintenance_due.html");
+        self.certification_expiring =
+            self.html_file_to_string("email_templates/certification_expiring.html");
+        self.suspension = self.html_file_to_string("email_templates/suspension.html");
+        self.suspension_ended = self.html_file_to_string("email_templates/suspension_ended.html");
     }
 
     pub fn html_file_to_string(&self, filename: &str) -> String {
@@ -222,6 +295,70 @@ impl EmailTemplates {
         let html = self.restock_notice.clone();
         html.replace("{list}", list)
     }
+
+    pub fn get_restock_fulfilled(&self, name: &str) -> String {
+        let html = self.restock_fulfilled.clone();
+        html.replace("{name}", name)
+    }
+
+    pub fn get_student_storage_offer(&self, slot_id: &str, deadline: &str) -> String {
+        let html = self.student_storage_offer.clone();
+        html.replace("{slot_id}", slot_id)
+            .replace("{deadline}", deadline)
+    }
+
+    pub fn get_lost_and_found(&self, description: &str, location: &str, hold_until: &str) -> String {
+        let html = self.lost_and_found.clone();
+        html.replace("{description}", description)
+            .replace("{location}", location)
+            .replace("{hold_until}", hold_until)
+    }
+
+    pub fn get_material_statement(
+        &self,
+        month: &str,
+        list: &str,
+        opening_balance: &str,
+        closing_balance: &str,
+    ) -> String {
+        let html = self.material_statement.clone();
+        html.replace("{month}", month)
+            .replace("{list}", list)
+            .replace("{opening_balance}", opening_balance)
+            .replace("{closing_balance}", closing_balance)
+    }
+
+    pub fn get_maintenance_due(&self, list: &str) -> String {
+        let html = self.maintenance_due.clone();
+        html.replace("{list}", list)
+    }
+
+    pub fn get_certification_expiring(
+        &self,
+        name: &str,
+        quiz: &str,
+        expiry_date: &str,
+        quiz_link: &str,
+    ) -> String {
+        let html = self.certification_expiring.clone();
+        html.replace("{name}", name)
+            .replace("{quiz}", quiz)
+            .replace("{expiry_date}", expiry_date)
+            .replace("{quiz_link}", quiz_link)
+    }
+
+    pub fn get_suspension(&self, name: &str, scope: &str, reason: &str, until: &str) -> String {
+        let html = self.suspension.clone();
+        html.replace("{name}", name)
+            .replace("{scope}", scope)
+            .replace("{reason}", reason)
+            .replace("{until}", until)
+    }
+
+    pub fn get_suspension_ended(&self, name: &str, scope: &str) -> String {
+        let html = self.suspension_ended.clone();
+        html.replace("{name}", name).replace("{scope}", scope)
+    }
 }
 
 lazy_static! {
@@ -296,6 +433,21 @@ pub async fn load_api_keys() -> Result<(), Error> {
     Ok(())
 }
 
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+/// Formats an amount of money in cents as dollars, eg -1050 as "-10.50"
+pub fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+
+    format!("{}{}.{:02}", sign, cents.unsigned_abs() / 100, cents.unsigned_abs() % 100)
+}
+
 pub fn between(source: &str, start: &str, end: &str) -> String {
     let start_offset = source.find(start);
 
@@ -335,6 +487,10 @@ async fn async_main(args: Vec<String>) -> std::io::Result<()> {
 
     info!("Database(s) loaded!");
 
+    info!("Loading quizzes...");
+    MEMORY_DATABASE.lock().await.quiz_registry.load_config();
+    info!("Quizzes loaded!");
+
     info!("Loading 3D printers...");
     MEMORY_DATABASE.lock().await.printers.load_printers();
     info!("3D printers loaded!");
@@ -347,13 +503,18 @@ async fn async_main(args: Vec<String>) -> std::io::Result<()> {
     let needs_update = MEMORY_DATABASE.lock().await.student_storage.needs_update();
 
     if needs_update {
-        info!("Student storage validity check failed. Updating...");
-        MEMORY_DATABASE.lock().await.student_storage = StudentStorage::default();
-        info!("Student storage updated!");
+        info!("Student storage validity check failed. Migrating...");
+        let report = MEMORY_DATABASE.lock().await.student_storage.migrate_layout();
+        report.log();
+        info!("Student storage migrated!");
     } else {
         info!("Student storage validity check passed.");
     }
 
+    MEMORY_DATABASE.lock().await.student_storage.update_sizes();
+    MEMORY_DATABASE.lock().await.inventory.assign_ids();
+    MEMORY_DATABASE.lock().await.inventory.assign_restock_ids();
+
     let _ = spawn(async move {
         let mut interval = time::interval(Duration::from_secs(UPDATE_INTERVAL));
         loop {
@@ -423,8 +584,112 @@ async fn async_main(args: Vec<String>) -> std::io::Result<()> {
             .wrap(actix_web::middleware::Compress::default())
             .wrap(cors)
             .app_data(json_cfg)
+            // raw text bodies, eg inventory CSV imports
+            .app_data(web::PayloadConfig::new(10_000_000))
             .service(status)
             .service(get_inventory)
+            .service(search_inventory)
+            .service(export_inventory_csv)
+            .service(create_inventory_item)
+            .service(update_inventory_item)
+            .service(delete_inventory_item)
+            .service(bulk_edit_inventory)
+            .service(import_inventory_csv)
+            .service(import_inventory_sheet)
+            .service(set_inventory_sheet_import)
+            .service(use_inventory_material)
+            .service(update_restock_notice)
+            .service(get_budget)
+            .service(get_budget_summary)
+            .service(export_budget_ledger)
+            .service(set_budget_allocation)
+            .service(add_purchase_order)
+            .service(delete_purchase_order)
+            .service(get_material_prices)
+            .service(get_billing_balance)
+            .service(get_billing_balances)
+            .service(get_billing_statement)
+            .service(export_billing_statements)
+            .service(log_material_usage)
+            .service(set_material_price)
+            .service(add_billing_credit)
+            .service(send_billing_statements)
+            .service(get_spools)
+            .service(add_spool)
+            .service(set_spool_remaining)
+            .service(delete_spool)
+            .service(load_spool)
+            .service(unload_spool)
+            .service(get_audits)
+            .service(get_audit_report)
+            .service(get_stock_adjustments)
+            .service(start_audit)
+            .service(scan_audit_uuid)
+            .service(enter_audit_count)
+            .service(finish_audit)
+            .service(cancel_audit)
+            .service(apply_audit)
+            .service(get_maintenance_plans)
+            .service(get_maintenance_due)
+            .service(get_maintenance_log)
+            .service(get_out_of_service)
+            .service(get_interlock_approval)
+            .service(add_maintenance_plan)
+            .service(delete_maintenance_plan)
+            .service(log_maintenance)
+            .service(set_out_of_service)
+            .service(set_in_service)
+            .service(get_incidents)
+            .service(get_incidents_for_user)
+            .service(report_incident)
+            .service(update_incident)
+            .service(set_strike_limit)
+            .service(get_question_banks)
+            .service(get_quiz_attempts)
+            .service(set_question_bank)
+            .service(start_quiz_attempt)
+            .service(start_quiz_attempt_new_user)
+            .service(submit_quiz_attempt)
+            .service(get_quiz_registry)
+            .service(get_user_swipe_groups)
+            .service(set_quiz_definition)
+            .service(delete_quiz_definition)
+            .service(get_sign_offs)
+            .service(get_skills)
+            .service(get_sign_offs_for_user)
+            .service(add_sign_off)
+            .service(revoke_sign_off)
+            .service(set_skill)
+            .service(delete_skill)
+            .service(get_identities)
+            .service(get_suspicious_identities)
+            .service(merge_identities)
+            .service(rekey_identity)
+            .service(split_identity)
+            .service(add_email_alias)
+            .service(import_roster_csv_route)
+            .service(import_roster_json_route)
+            .service(export_user_data_route)
+            .service(export_user_data_csv)
+            .service(get_deletion_requests)
+            .service(request_data_deletion)
+            .service(approve_data_deletion)
+            .service(reject_data_deletion)
+            .service(set_retention_years)
+            .service(export_audit_log)
+            .service(get_audit_log)
+            .service(get_roles)
+            .service(get_user_roles)
+            .service(set_role)
+            .service(delete_role)
+            .service(assign_role)
+            .service(unassign_role)
+            .service(issue_user_token)
+            .service(revoke_user_tokens)
+            .service(get_suspensions)
+            .service(get_suspensions_for_user)
+            .service(suspend_user)
+            .service(lift_suspension)
             .service(get_quizzes)
             .service(get_users)
             .service(checkout_items)
@@ -439,6 +704,19 @@ async fn async_main(args: Vec<String>) -> std::io::Result<()> {
             .service(renew_student_storage_slot)
             .service(release_student_storage_slot)
             .service(get_student_storage_for_all)
+            .service(join_student_storage_waitlist)
+            .service(leave_student_storage_waitlist)
+            .service(transfer_student_storage_slot)
+            .service(override_student_storage_slot)
+            .service(get_student_storage_migrations)
+            .service(reassign_student_storage_orphan)
+            .service(dismiss_student_storage_orphan)
+            .service(get_lost_and_found)
+            .service(get_lost_and_found_for_user)
+            .service(get_lost_item_photo)
+            .service(add_lost_item)
+            .service(claim_lost_item)
+            .service(dispose_lost_item)
             .service(get_printers)
             .service(join_printer_queue)
             .service(leave_printer_queue)
@@ -453,6 +731,7 @@ async fn async_main(args: Vec<String>) -> std::io::Result<()> {
             .service(get_workshops)
             .service(add_user_restock_notice)
             .service(render_loom)
+            .service(render_labels)
             .service(get_now_playing)
             .service(ResourceFiles::new("/", generate()))
     });
@@ -492,48 +771,113 @@ pub fn main() {
 }
 
 async fn update_loop() {
-    // Update inventory
-    let mut inventory = MEMORY_DATABASE.lock().await.inventory.clone();
+    // Import inventory from the Google Sheet, if enabled
+    let sheet_import = MEMORY_DATABASE.lock().await.inventory.sheet_import;
 
-    let update_result = inventory.update().await;
+    if sheet_import {
+        match Inventory::fetch_sheet().await {
+            Ok(csv_text) => {
+                let import_result = MEMORY_DATABASE.lock().await.inventory.import_csv(&csv_text);
 
-    if update_result.is_err() {
-        info!(
-            "Failed to update inventory: {}",
-            update_result.err().unwrap()
-        );
-    } else {
-        let current_checkouts = MEMORY_DATABASE
-            .lock()
-            .await
-            .checkout_log
-            .get_current_checkouts();
+                if import_result.is_err() {
+                    warn!("Failed to import inventory: {}", import_result.unwrap_err());
+                }
+            }
+            Err(e) => info!("Failed to fetch inventory sheet: {}", e),
+        }
+    }
+
+    // Update inventory counts from checkouts
+    let mut data = MEMORY_DATABASE.lock().await;
+    let checkout_log = data.checkout_log.clone();
+
+    data.inventory.update_checkout_counts(&checkout_log);
+    data.inventory.update_from_checkouts(&checkout_log.get_current_checkouts());
+    drop(data);
+
+    info!("Inventory updated!");
 
-        inventory.update_from_checkouts(&current_checkouts);
+    // Get current time of day
+    let now = Utc::now();
+    let now_time = now.time();
 
-        // Get current time of day
-        let now = Utc::now();
-        let now_time = now.time();
+    if now_time.hour() < TIME_SEND_EMAIL_HOUR {
+        MEMORY_DATABASE.lock().await.inventory.sent_restock_notice = false;
+    } else {
+        let mut inventory = MEMORY_DATABASE.lock().await.inventory.clone();
 
-        if now_time.hour() < TIME_SEND_EMAIL_HOUR {
-            inventory.sent_restock_notice = false;
-        } else if (inventory.sent_restock_notice == false
-            && now_time.hour() >= TIME_SEND_EMAIL_HOUR)
-            || cfg!(debug_assertions)
-        {
+        if inventory.sent_restock_notice == false || cfg!(debug_assertions) {
             inventory.send_restock_notice().await;
+
+            // Only take back what sending the notice changed, so item edits and
+            // requests added or updated while the email was sending aren't lost
+            let notified: Vec<String> = inventory
+                .needs_restock
+                .iter()
+                .filter(|x| x.notified)
+                .map(|x| x.id.clone())
+                .collect();
+
+            let mut data = MEMORY_DATABASE.lock().await;
+
+            data.inventory.sent_restock_notice = inventory.sent_restock_notice;
+
+            for notice in data.inventory.needs_restock.iter_mut() {
+                if notified.contains(&notice.id) {
+                    notice.notified = true;
+                }
+            }
         }
+    }
+
+    MEMORY_DATABASE.lock().await.inventory.prune_restock_notices();
+    MEMORY_DATABASE.lock().await.quiz_banks.prune_expired_attempts();
 
-        MEMORY_DATABASE.lock().await.inventory = inventory;
-        info!("Inventory updated!");
+    // Let the manager and stewards know what's due for maintenance, once a day
+    if now_time.hour() < TIME_SEND_EMAIL_HOUR {
+        MEMORY_DATABASE.lock().await.maintenance.sent_digest = false;
+    } else if !MEMORY_DATABASE.lock().await.maintenance.sent_digest {
+        send_maintenance_digest().await;
+    }
+
+    // Email last month's material statements once the month is over
+    let statement_month = get_previous_month();
+    let last_statement_month = MEMORY_DATABASE.lock().await.billing.last_statement_month.clone();
+
+    if now_time.hour() >= TIME_SEND_EMAIL_HOUR && last_statement_month != statement_month {
+        send_statements(&statement_month).await;
+    }
+
+    // Anonymize history past the retention period, once a day
+    if now_time.hour() < TIME_SEND_EMAIL_HOUR {
+        MEMORY_DATABASE.lock().await.privacy.applied_retention = false;
+    } else {
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        if !data.privacy.applied_retention {
+            apply_retention(&mut data);
+        }
     }
 
     // Update quizzes
-    let mut quizzes = get_all_quizzes();
+    let data = MEMORY_DATABASE.lock().await;
+    let mut quizzes = data.quiz_registry.get_sheet_quizzes();
+    let native_quizzes = data.quiz_banks.get_native_quizzes();
+    let previous_quizzes = data.quizzes.clone();
+    drop(data);
 
     info!("Updating quizzes...");
 
     for quiz in quizzes.iter_mut() {
+        // Quizzes hosted by the server keep the sheet responses from before they moved
+        if native_quizzes.contains(quiz.get_name()) {
+            if let Some(previous) = previous_quizzes.iter().find(|x| x.get_name() == quiz.get_name()) {
+                *quiz = previous.clone();
+            }
+
+            continue;
+        }
+
         let update_result = quiz.update().await;
 
         if update_result.is_err() {
@@ -543,25 +887,67 @@ async fn update_loop() {
 
     info!("Quizzes updated!");
 
-    MEMORY_DATABASE.lock().await.quizzes = quizzes.clone();
+    let mut data = MEMORY_DATABASE.lock().await;
+    data.privacy.scrub_quizzes(&mut quizzes);
+    data.quizzes = quizzes.clone();
+    data.quiz_banks.import_responses(&quizzes);
+    drop(data);
 
     // Update users
-    let users = create_users_from_quizzes(&quizzes);
+    let identities = MEMORY_DATABASE.lock().await.identities.clone();
+    let users = create_users_from_quizzes(&quizzes, &identities);
 
     info!("Updated {} users!", users.len());
 
-    MEMORY_DATABASE.lock().await.users.update_from(&users);
+    let mut data = MEMORY_DATABASE.lock().await;
+    data.users.update_from(&users);
+    data.users.fill_missing_pass_dates();
+    drop(data);
+
+    // Remind people to retake quizzes that are about to expire
+    if now_time.hour() >= TIME_SEND_EMAIL_HOUR {
+        send_certification_reminders().await;
+    }
+
+    // Let people know their suspensions are over, whether they ran out or were lifted
+    send_suspension_endings().await;
 
     // Update and check print queue
     // First, get num of available printers
     let mut printers = MEMORY_DATABASE.lock().await.printers.clone();
 
-    let printers_avail = printers.get_available_printers();
+    let maintenance = MEMORY_DATABASE.lock().await.maintenance.clone();
+
+    // Printers that are out of service don't take anyone from the queue
+    let printers_avail: Vec<Printer> = printers
+        .get_available_printers()
+        .into_iter()
+        .filter(|x| {
+            maintenance
+                .is_out_of_service(&MaintenanceTarget::Machine(x.get_id()))
+                .is_none()
+        })
+        .collect();
 
     info!("{} printers currently available", printers_avail.len());
 
     printers.cleanup_print_queue();
 
+    // Anyone who's lost machine access since joining, eg suspended or graduated, loses their place
+    let data = MEMORY_DATABASE.lock().await;
+
+    let removed = printers.retain_queue(|entry| {
+        data.users
+            .get_user_by_id(&entry.college_id)
+            .map_or(true, |user| data.suspensions.check_access(&user, &Access::Machines).is_ok())
+    });
+
+    drop(data);
+
+    for entry in removed {
+        info!("Removed {} from the print queue, they no longer have machine access", entry.college_id);
+    }
+
     MEMORY_DATABASE.lock().await.printers = printers.clone();
 
     // Then, get first x people in queue, where x is the number of available printers
@@ -685,8 +1071,31 @@ async fn update_loop() {
         }
     }
 
+    // Offer freed slots to the waitlist
+    let offers = student_storage.process_waitlist();
+
     MEMORY_DATABASE.lock().await.student_storage = student_storage;
 
+    for offer in offers.iter() {
+        info!(
+            "Offering slot {:?} to {} from the waitlist",
+            offer.offered_slot, offer.college_id
+        );
+        offer.notify().await;
+    }
+
+    // Check for lost and found items past their hold date
+    let ready_for_disposal = MEMORY_DATABASE
+        .lock()
+        .await
+        .lost_and_found
+        .get_ready_for_disposal()
+        .len();
+
+    if ready_for_disposal > 0 {
+        info!("{} lost and found item(s) past their hold date", ready_for_disposal);
+    }
+
     // Update workshops
     let mut workshops = MEMORY_DATABASE.lock().await.workshops.clone();
 
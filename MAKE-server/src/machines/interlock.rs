@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+
+use crate::machines::maintenance::MaintenanceTarget;
+use crate::people::quizzes::QuizName;
+use crate::people::suspensions::Access;
+use crate::Data;
+
+/// Whether a machine's interlock should let a user turn it on, and why not if it shouldn't
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct InterlockApproval {
+    pub approved: bool,
+    pub reason: Option<String>,
+}
+
+impl InterlockApproval {
+    fn approve() -> Self {
+        InterlockApproval {
+            approved: true,
+            reason: None,
+        }
+    }
+
+    fn deny(reason: String) -> Self {
+        InterlockApproval {
+            approved: false,
+            reason: Some(reason),
+        }
+    }
+}
+
+/// Checks if a user is allowed to turn on a machine right now
+pub fn check_interlock(data: &Data, machine: &str, college_id: u64) -> InterlockApproval {
+    let user = data.users.get_user_by_id(&college_id);
+
+    if user.is_none() {
+        return InterlockApproval::deny("User not found".to_string());
+    }
+
+    let user = user.unwrap();
+
+    let access = data.suspensions.check_access(&user, &Access::Machines);
+
+    if access.is_err() {
+        return InterlockApproval::deny(access.unwrap_err());
+    }
+
+    // Expired certifications count as missing
+    let passed = data.quiz_registry.get_valid_quizzes(&user);
+    let missing: Vec<QuizName> = data
+        .quiz_registry
+        .get_quizzes_for_machine(machine)
+        .into_iter()
+        .filter(|x| !passed.contains(x))
+        .collect();
+
+    if !missing.is_empty() {
+        return InterlockApproval::deny(format!(
+            "Missing certifications: {}",
+            missing.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")
+        ));
+    }
+
+    let missing_sign_offs = data.sign_offs.get_missing_for_machine(machine, &user);
+
+    if !missing_sign_offs.is_empty() {
+        return InterlockApproval::deny(format!("Missing sign-offs: {}", missing_sign_offs.join(", ")));
+    }
+
+    let out_of_service = data
+        .maintenance
+        .is_out_of_service(&MaintenanceTarget::Machine(machine.to_string()));
+
+    if let Some(flag) = out_of_service {
+        return InterlockApproval::deny(format!("{} is out of service: {}", machine, flag.reason));
+    }
+
+    InterlockApproval::approve()
+}
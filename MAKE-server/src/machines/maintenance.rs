@@ -0,0 +1,361 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::inventory::inventory::Inventory;
+use crate::machines::printers::Printers;
+use crate::management::emails::send_individual_email;
+use crate::people::users::AuthLevel;
+use crate::{now, EMAIL_TEMPLATES, MAKERSPACE_MANAGER_EMAIL, MEMORY_DATABASE};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// A machine by name (eg a printer ID or "Glowforge"), or an inventory item by ID
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum MaintenanceTarget {
+    Machine(String),
+    Item(String),
+}
+
+impl std::fmt::Display for MaintenanceTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MaintenanceTarget::Machine(id) => write!(f, "machine:{}", id),
+            MaintenanceTarget::Item(id) => write!(f, "item:{}", id),
+        }
+    }
+}
+
+impl Default for MaintenanceTarget {
+    fn default() -> Self {
+        MaintenanceTarget::Machine(String::new())
+    }
+}
+
+/// How often a task needs doing
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum MaintenanceInterval {
+    Days(u64),
+    // Only printers report print time
+    PrintHours(f64),
+    // Only inventory items are checked out
+    Checkouts(u64),
+}
+
+impl Default for MaintenanceInterval {
+    fn default() -> Self {
+        MaintenanceInterval::Days(30)
+    }
+}
+
+/// Maintenance plans and history, and what's currently out of service
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Maintenance {
+    pub plans: Vec<MaintenancePlan>,
+    pub log: Vec<MaintenanceLogEntry>,
+    pub out_of_service: Vec<OutOfService>,
+    pub sent_digest: bool,
+}
+
+/// A recurring task, eg oiling the bandsaw every 30 days.
+/// The baselines are the usage counters when the task was last done.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MaintenancePlan {
+    pub id: String,
+    pub target: MaintenanceTarget,
+    pub task: String,
+    pub interval: MaintenanceInterval,
+    pub timestamp_last_done: u64,
+    pub baseline_print_hours: f64,
+    pub baseline_checkouts: u64,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MaintenanceLogEntry {
+    pub id: String,
+    pub plan_id: Option<String>,
+    pub target: MaintenanceTarget,
+    pub task: String,
+    pub performed_by: String,
+    pub notes: String,
+    pub timestamp: u64,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct OutOfService {
+    pub target: MaintenanceTarget,
+    pub reason: String,
+    pub set_by: String,
+    pub timestamp: u64,
+}
+
+/// A plan that's due, and how far past due it is
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct DueMaintenance {
+    pub plan: MaintenancePlan,
+    pub target_name: String,
+    pub usage_since: String,
+}
+
+/// Body of a request to log maintenance.
+/// Setting `back_in_service` clears the target's out of service flag.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MaintenanceLogRequest {
+    pub plan_id: Option<String>,
+    pub target: MaintenanceTarget,
+    pub task: String,
+    pub notes: String,
+    pub back_in_service: bool,
+}
+
+impl Maintenance {
+    pub fn add_plan(&mut self, mut plan: MaintenancePlan, inventory: &Inventory, printers: &Printers) -> Result<MaintenancePlan, String> {
+        if plan.task.trim().is_empty() {
+            return Err("Plan must have a task".to_string());
+        }
+
+        match (&plan.target, &plan.interval) {
+            (MaintenanceTarget::Item(_), MaintenanceInterval::PrintHours(_)) => {
+                return Err("Only printers can be maintained by print hours".to_string());
+            }
+            (MaintenanceTarget::Machine(_), MaintenanceInterval::Checkouts(_)) => {
+                return Err("Only inventory items can be maintained by checkouts".to_string());
+            }
+            (MaintenanceTarget::Item(id), _) if inventory.get_item_by_id(id).is_none() => {
+                return Err(format!("No item found with id {}", id));
+            }
+            _ => {}
+        }
+
+        plan.id = Uuid::new_v4().to_string();
+        plan.timestamp_last_done = now();
+        plan.baseline_print_hours = get_print_hours(&plan.target, printers);
+        plan.baseline_checkouts = get_checkouts(&plan.target, inventory);
+
+        info!("Adding maintenance plan {:?} for {:?}", plan.task, plan.target);
+
+        self.plans.push(plan.clone());
+
+        Ok(plan)
+    }
+
+    pub fn delete_plan(&mut self, id: &str) -> Result<MaintenancePlan, String> {
+        let pos = self
+            .plans
+            .iter()
+            .position(|x| x.id == id)
+            .ok_or(format!("No maintenance plan found with id {}", id))?;
+
+        Ok(self.plans.remove(pos))
+    }
+
+    /// Records maintenance, restarting the interval of the plan it was for.
+    /// `performed_by` is whoever made the request, see `Actor::get_name`.
+    pub fn log_maintenance(
+        &mut self,
+        request: MaintenanceLogRequest,
+        performed_by: String,
+        inventory: &Inventory,
+        printers: &Printers,
+    ) -> Result<MaintenanceLogEntry, String> {
+        let mut entry = MaintenanceLogEntry {
+            id: Uuid::new_v4().to_string(),
+            plan_id: request.plan_id.clone(),
+            target: request.target,
+            task: request.task,
+            performed_by,
+            notes: request.notes,
+            timestamp: now(),
+        };
+
+        if let Some(plan_id) = &request.plan_id {
+            let plan = self
+                .plans
+                .iter_mut()
+                .find(|x| &x.id == plan_id)
+                .ok_or(format!("No maintenance plan found with id {}", plan_id))?;
+
+            plan.timestamp_last_done = entry.timestamp;
+            plan.baseline_print_hours = get_print_hours(&plan.target, printers);
+            plan.baseline_checkouts = get_checkouts(&plan.target, inventory);
+
+            entry.target = plan.target.clone();
+
+            if entry.task.is_empty() {
+                entry.task = plan.task.clone();
+            }
+        }
+
+        if entry.task.trim().is_empty() {
+            return Err("Maintenance must have a task".to_string());
+        }
+
+        if request.back_in_service {
+            self.set_in_service(&entry.target);
+        }
+
+        info!("Logging maintenance {:?} on {:?}", entry.task, entry.target);
+
+        self.log.push(entry.clone());
+
+        Ok(entry)
+    }
+
+    pub fn set_out_of_service(&mut self, mut flag: OutOfService) {
+        info!("{:?} is out of service: {}", flag.target, flag.reason);
+
+        flag.timestamp = now();
+
+        self.out_of_service.retain(|x| x.target != flag.target);
+        self.out_of_service.push(flag);
+    }
+
+    pub fn set_in_service(&mut self, target: &MaintenanceTarget) {
+        if self.is_out_of_service(target).is_some() {
+            info!("{:?} is back in service", target);
+        }
+
+        self.out_of_service.retain(|x| &x.target != target);
+    }
+
+    pub fn is_out_of_service(&self, target: &MaintenanceTarget) -> Option<OutOfService> {
+        self.out_of_service.iter().find(|x| &x.target == target).cloned()
+    }
+
+    /// Checks none of the named items are out of service, for checkouts.
+    /// Gives an error naming the first one that is.
+    pub fn check_items_in_service(&self, names: &Vec<String>, inventory: &Inventory) -> Result<(), String> {
+        for name in names {
+            let item = inventory.get_item_by_name(name);
+
+            if let Some(item) = item {
+                if let Some(flag) = self.is_out_of_service(&MaintenanceTarget::Item(item.id)) {
+                    return Err(format!("{} is out of service: {}", name, flag.reason));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Plans whose interval has passed since they were last done
+    pub fn get_due(&self, inventory: &Inventory, printers: &Printers) -> Vec<DueMaintenance> {
+        let now = now();
+
+        self.plans
+            .iter()
+            .filter_map(|plan| {
+                let (is_due, usage_since) = match &plan.interval {
+                    MaintenanceInterval::Days(days) => {
+                        let since = now.saturating_sub(plan.timestamp_last_done) / DAY;
+                        (since >= *days, format!("{} of {} days", since, days))
+                    }
+                    MaintenanceInterval::PrintHours(hours) => {
+                        let since = get_print_hours(&plan.target, printers) - plan.baseline_print_hours;
+                        (since >= *hours, format!("{:.1} of {} print hours", since, hours))
+                    }
+                    MaintenanceInterval::Checkouts(checkouts) => {
+                        let since = get_checkouts(&plan.target, inventory)
+                            .saturating_sub(plan.baseline_checkouts);
+                        (since >= *checkouts, format!("{} of {} checkouts", since, checkouts))
+                    }
+                };
+
+                if is_due {
+                    Some(DueMaintenance {
+                        plan: plan.clone(),
+                        target_name: get_target_name(&plan.target, inventory),
+                        usage_since,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Emails the manager and stewards a list of everything due for maintenance
+pub async fn send_maintenance_digest() {
+    let mut data = MEMORY_DATABASE.lock().await;
+    data.maintenance.sent_digest = true;
+
+    let due = data.maintenance.get_due(&data.inventory, &data.printers);
+    let stewards: Vec<String> = data
+        .users
+        .get_users_with_auth_level(&AuthLevel::Steward)
+        .iter()
+        .map(|x| x.get_email())
+        .filter(|x| !x.is_empty())
+        .collect();
+    drop(data);
+
+    if due.is_empty() {
+        return;
+    }
+
+    info!("Sending maintenance digest for {} tasks", due.len());
+
+    let _ = send_individual_email(
+        MAKERSPACE_MANAGER_EMAIL.to_string(),
+        Some(stewards),
+        "Maintenance Due".to_string(),
+        EMAIL_TEMPLATES
+            .lock()
+            .await
+            .get_maintenance_due(&due_maintenance_to_html(&due)),
+    )
+    .await;
+}
+
+/// Table rows for the due maintenance digest email
+pub fn due_maintenance_to_html(due: &Vec<DueMaintenance>) -> String {
+    due.iter()
+        .map(|x| {
+            format!(
+                "<tr style=\"border: 1px solid black; border-collapse: collapse;\">
+                    <td style=\"border: 1px solid black; border-collapse: collapse; padding: 5px;\">{}</td>
+                    <td style=\"border: 1px solid black; border-collapse: collapse; padding: 5px;\">{}</td>
+                    <td style=\"border: 1px solid black; border-collapse: collapse; padding: 5px;\">{}</td>
+                </tr>",
+                x.target_name, x.plan.task, x.usage_since
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn get_target_name(target: &MaintenanceTarget, inventory: &Inventory) -> String {
+    match target {
+        MaintenanceTarget::Machine(name) => name.clone(),
+        MaintenanceTarget::Item(id) => inventory
+            .get_item_by_id(id)
+            .map(|x| x.name)
+            .unwrap_or(id.clone()),
+    }
+}
+
+fn get_print_hours(target: &MaintenanceTarget, printers: &Printers) -> f64 {
+    match target {
+        MaintenanceTarget::Machine(id) => printers
+            .get_printer_by_id(id)
+            .map(|x| x.get_total_print_hours())
+            .unwrap_or(0.0),
+        MaintenanceTarget::Item(_) => 0.0,
+    }
+}
+
+fn get_checkouts(target: &MaintenanceTarget, inventory: &Inventory) -> u64 {
+    match target {
+        MaintenanceTarget::Item(id) => inventory
+            .get_item_by_id(id)
+            .map(|x| x.num_times_checked)
+            .unwrap_or(0),
+        MaintenanceTarget::Machine(_) => 0,
+    }
+}
@@ -1,3 +1,5 @@
+pub mod interlock;
 pub mod laser_cutter;
 pub mod loom;
-pub mod printers;
\ No newline at end of file
+pub mod maintenance;
+pub mod printers;
@@ -1,14 +1,17 @@
 use log::*;
 use serde::{Deserialize, Serialize};
 use std::io::Read;
-use std::{collections::HashMap, time::SystemTime};
+use std::collections::HashMap;
 use toml::Value;
 
 use crate::management::emails::send_individual_email;
 use crate::{people::users::User, API_KEYS};
-use crate::{EMAIL_TEMPLATES};
+use crate::{now, EMAIL_TEMPLATES};
 
 const PRINT_QUEUE_ENTRY_EXPIRATION_TIME: u64 = 60 * 15; // 15 minutes
+const FILAMENT_DIAMETER_MM: f64 = 1.75;
+// Spools with less than this left get a restock notice
+const LOW_SPOOL_GRAMS: f64 = 100.0;
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct PrinterWebhookUpdate {
@@ -45,6 +48,27 @@ pub struct PrinterWebhookStateFlags {
 #[derive(Deserialize, Serialize, Clone)]
 pub struct PrinterWebhookJob {
     estimatedPrintTime: Option<f64>,
+    // Estimated filament use per extruder, eg tool0
+    filament: Option<HashMap<String, PrinterWebhookFilament>>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct PrinterWebhookFilament {
+    length: Option<f64>, // mm
+    volume: Option<f64>, // cm^3
+}
+
+impl PrinterWebhookFilament {
+    /// Volume of filament in cm^3, worked out from the length if needed
+    pub fn get_volume(&self) -> f64 {
+        if let Some(volume) = self.volume.filter(|x| *x > 0.0) {
+            volume
+        } else {
+            let radius = FILAMENT_DIAMETER_MM / 2.0;
+
+            self.length.unwrap_or(0.0) * std::f64::consts::PI * radius * radius / 1000.0
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -93,6 +117,8 @@ pub struct Printers {
     print_log: Vec<PrintLogEntry>,
     queue: Vec<PrintQueueEntry>,
     queue_log: Vec<PrintQueueEntry>,
+    #[serde(default)]
+    spools: Vec<Spool>,
 }
 
 impl Printers {
@@ -102,6 +128,7 @@ impl Printers {
             print_log: Vec::new(),
             queue: Vec::new(),
             queue_log: Vec::new(),
+            spools: Vec::new(),
         }
     }
 
@@ -155,10 +182,7 @@ impl Printers {
         let entry = PrintLogEntry {
             printer_id,
             college_id,
-            timestamp: SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_secs(),
+            timestamp: now(),
         };
 
         self.add_log(entry);
@@ -181,18 +205,202 @@ impl Printers {
         );
 
         printer.status = PrinterStatus::from_webhook(&printer_webhook_update.state);
-        printer.last_updated = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
+        printer.last_updated = now();
 
         printer.current_time_left = printer_webhook_update.progress.printTimeLeft.unwrap_or(0);
 
+        if printer_webhook_update.topic == "Print Done" {
+            printer.total_print_seconds += printer_webhook_update.progress.printTime.unwrap_or(0);
+        }
+
+        let printer_id = printer.id.clone();
+
         self.add_set_printer(printer);
 
+        // Take the job's estimated filament off the loaded spool
+        if printer_webhook_update.topic == "Print Done" {
+            let volume: f64 = printer_webhook_update
+                .job
+                .filament
+                .as_ref()
+                .map(|tools| tools.values().map(|x| x.get_volume()).sum())
+                .unwrap_or(0.0);
+
+            self.use_filament(&printer_id, volume);
+        }
+
         Ok(())
     }
 
+    pub fn get_spools(&self) -> Vec<Spool> {
+        self.spools.clone()
+    }
+
+    pub fn get_spool(&self, uuid: &str) -> Option<Spool> {
+        self.spools.iter().find(|x| x.uuid == uuid).cloned()
+    }
+
+    /// Adds a new spool, giving it a UUID for its label if it doesn't have one
+    pub fn add_spool(&mut self, mut spool: Spool) -> Result<Spool, String> {
+        if spool.material.trim().is_empty() {
+            return Err("Spool must have a material".to_string());
+        }
+
+        if spool.uuid.is_empty() {
+            spool.uuid = uuid::Uuid::new_v4().to_string();
+        } else if self.get_spool(&spool.uuid).is_some() {
+            return Err(format!("Spool {} already exists", spool.uuid));
+        }
+
+        if spool.remaining_grams <= 0.0 {
+            spool.remaining_grams = spool.initial_grams;
+        }
+
+        spool.printer_id = None;
+        spool.low_warning_sent = false;
+        spool.timestamp_added = now();
+
+        info!("Adding {} {} spool {}", spool.color, spool.material, spool.uuid);
+
+        self.spools.push(spool.clone());
+
+        Ok(spool)
+    }
+
+    /// Sets how much is left on a spool, eg after weighing it
+    pub fn set_spool_remaining(&mut self, uuid: &str, grams: f64) -> Result<Spool, String> {
+        let spool = self
+            .spools
+            .iter_mut()
+            .find(|x| x.uuid == uuid)
+            .ok_or(format!("No spool found with uuid {}", uuid))?;
+
+        spool.remaining_grams = grams.max(0.0);
+
+        if !spool.is_low() {
+            spool.low_warning_sent = false;
+        }
+
+        Ok(spool.clone())
+    }
+
+    pub fn delete_spool(&mut self, uuid: &str) -> Result<Spool, String> {
+        let pos = self
+            .spools
+            .iter()
+            .position(|x| x.uuid == uuid)
+            .ok_or(format!("No spool found with uuid {}", uuid))?;
+
+        if let Some(printer_id) = self.spools[pos].printer_id.clone() {
+            self.unload_spool(&printer_id)?;
+        }
+
+        info!("Removing spool {}", uuid);
+
+        Ok(self.spools.remove(pos))
+    }
+
+    /// Loads a spool into a printer, unloading whatever was in it.
+    /// A spool can only be in one printer at a time.
+    pub fn load_spool(&mut self, printer_id: &str, uuid: &str) -> Result<Spool, String> {
+        if !self.exists(printer_id) {
+            return Err(format!("No printer found with id {}", printer_id));
+        }
+
+        let spool = self
+            .get_spool(uuid)
+            .ok_or(format!("No spool found with uuid {}", uuid))?;
+
+        if let Some(previous_printer) = spool.printer_id {
+            self.unload_spool(&previous_printer)?;
+        }
+
+        self.unload_spool(printer_id)?;
+
+        let printer = self.printers.get_mut(printer_id).unwrap();
+        printer.loaded_spool = Some(uuid.to_string());
+
+        let spool = self.spools.iter_mut().find(|x| x.uuid == uuid).unwrap();
+        spool.printer_id = Some(printer_id.to_string());
+
+        info!("Loaded spool {} into {}", uuid, printer_id);
+
+        Ok(spool.clone())
+    }
+
+    pub fn get_loaded_spool(&self, printer_id: &str) -> Option<Spool> {
+        self.spools
+            .iter()
+            .find(|x| x.printer_id.as_deref() == Some(printer_id))
+            .cloned()
+    }
+
+    /// Unloads the spool in a printer, returning it if there was one
+    pub fn unload_spool(&mut self, printer_id: &str) -> Result<Option<Spool>, String> {
+        let printer = self
+            .printers
+            .get_mut(printer_id)
+            .ok_or(format!("No printer found with id {}", printer_id))?;
+
+        let uuid = printer.loaded_spool.take();
+
+        if let Some(uuid) = uuid {
+            info!("Unloaded spool {} from {}", uuid, printer_id);
+
+            let spool = self.spools.iter_mut().find(|x| x.uuid == uuid);
+
+            if let Some(spool) = spool {
+                spool.printer_id = None;
+
+                return Ok(Some(spool.clone()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Takes filament off the spool loaded in a printer
+    pub fn use_filament(&mut self, printer_id: &str, volume: f64) {
+        let uuid = self
+            .printers
+            .get(printer_id)
+            .and_then(|x| x.loaded_spool.clone());
+
+        if uuid.is_none() {
+            if volume > 0.0 {
+                warn!("{} finished a print with no spool loaded", printer_id);
+            }
+
+            return;
+        }
+
+        let uuid = uuid.unwrap();
+        let spool = self.spools.iter_mut().find(|x| x.uuid == uuid);
+
+        if let Some(spool) = spool {
+            let grams = volume * spool.get_density();
+
+            spool.remaining_grams = (spool.remaining_grams - grams).max(0.0);
+
+            info!(
+                "{} used {:.1} g from spool {}, {:.1} g left",
+                printer_id, grams, uuid, spool.remaining_grams
+            );
+        }
+    }
+
+    /// Spools that have just run low, marking them as warned so they're only returned once
+    pub fn take_low_spools(&mut self) -> Vec<Spool> {
+        self.spools
+            .iter_mut()
+            .filter(|x| x.is_low() && !x.low_warning_sent)
+            .map(|x| {
+                x.low_warning_sent = true;
+                x.clone()
+            })
+            .collect()
+    }
+
     pub fn add_user_to_queue(&mut self, user: &User) -> Result<(), String> {
         // Check if user is already in queue
         if self
@@ -210,6 +418,101 @@ impl Printers {
         Ok(())
     }
 
+    pub fn get_print_log_for_id(&self, college_id: &u64) -> Vec<PrintLogEntry> {
+        self.print_log
+            .iter()
+            .filter(|x| &x.college_id == college_id)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_queue_entries_for_id(&self, college_id: &u64) -> Vec<PrintQueueEntry> {
+        self.queue
+            .iter()
+            .chain(self.queue_log.iter())
+            .filter(|x| &x.college_id == college_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Takes a user out of the queue and strips their ID and email from the logs, keeping print counts.
+    /// Returns how many entries were changed.
+    pub fn anonymize_user(&mut self, college_id: &u64, anonymous_id: u64) -> usize {
+        let before = self.queue.len();
+        self.queue.retain(|x| &x.college_id != college_id);
+        let mut changed = before - self.queue.len();
+
+        changed += self.anonymize_logs(|x| x == *college_id, |_| true, anonymous_id);
+
+        changed
+    }
+
+    /// Strips IDs and emails from log entries older than `cutoff`
+    pub fn anonymize_before(&mut self, cutoff: u64, anonymous_id: u64) -> usize {
+        self.anonymize_logs(|x| x != anonymous_id, |x| x < cutoff, anonymous_id)
+    }
+
+    fn anonymize_logs(
+        &mut self,
+        id_matches: impl Fn(u64) -> bool,
+        time_matches: impl Fn(u64) -> bool,
+        anonymous_id: u64,
+    ) -> usize {
+        let mut changed = 0;
+
+        for entry in self
+            .print_log
+            .iter_mut()
+            .filter(|x| id_matches(x.college_id) && time_matches(x.timestamp))
+        {
+            entry.college_id = anonymous_id;
+            changed += 1;
+        }
+
+        for entry in self
+            .queue_log
+            .iter_mut()
+            .filter(|x| id_matches(x.college_id) && time_matches(x.timestamp_submitted))
+        {
+            entry.college_id = anonymous_id;
+            entry.email = String::new();
+            changed += 1;
+        }
+
+        changed
+    }
+
+    /// Moves every queue entry from one college ID to another, for fixing identities.
+    /// Returns how many were moved.
+    pub fn rekey_user(&mut self, from: &u64, to: &u64) -> usize {
+        let mut moved = 0;
+
+        for entry in self
+            .queue
+            .iter_mut()
+            .chain(self.queue_log.iter_mut())
+            .filter(|x| &x.college_id == from)
+        {
+            entry.college_id = *to;
+            moved += 1;
+        }
+
+        moved
+    }
+
+    /// Moves every print log entry from one college ID to another, for fixing identities.
+    /// Returns how many were moved.
+    pub fn rekey_print_log(&mut self, from: &u64, to: &u64) -> usize {
+        let mut moved = 0;
+
+        for entry in self.print_log.iter_mut().filter(|x| &x.college_id == from) {
+            entry.college_id = *to;
+            moved += 1;
+        }
+
+        moved
+    }
+
     pub fn remove_user_from_queue(&mut self, user: &User) -> Result<(), String> {
         let queue_entry = self
             .queue
@@ -278,6 +581,14 @@ impl Printers {
             .collect();
     }
 
+    /// Takes everyone `keep` returns false for out of the queue, eg people who've been suspended since joining
+    pub fn retain_queue(&mut self, keep: impl Fn(&PrintQueueEntry) -> bool) -> Vec<PrintQueueEntry> {
+        let (kept, removed) = self.queue.drain(..).partition(|x| keep(x));
+        self.queue = kept;
+
+        removed
+    }
+
     pub fn get_print_queue_length(&self) -> usize {
         self.queue.len()
     }
@@ -301,7 +612,7 @@ impl Printers {
     }
 }
 
-#[derive(Default, Deserialize, Serialize, Clone)]
+#[derive(Default, Debug, Deserialize, Serialize, Clone)]
 pub struct PrintQueueEntry {
     pub uuid: String,
     pub college_id: u64,
@@ -328,19 +639,13 @@ impl PrintQueueEntry {
         .await;
 
         self.timestamp_notified = Some(
-            SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_secs(),
+            now(),
         );
     }
 
     pub fn accept(&mut self) {
         self.timestamp_accepted = Some(
-            SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_secs(),
+            now(),
         );
     }
 
@@ -355,22 +660,16 @@ impl PrintQueueEntry {
     pub fn has_expired(&self) -> bool {
         if self.timestamp_accepted.is_some() {
             self.timestamp_accepted.unwrap() + PRINT_QUEUE_ENTRY_EXPIRATION_TIME
-                < SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .expect("Time went backwards")
-                    .as_secs()
+                < now()
         } else {
             self.timestamp_notified.is_some()
                 && self.timestamp_notified.unwrap() + PRINT_QUEUE_ENTRY_EXPIRATION_TIME
-                    < SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .expect("Time went backwards")
-                        .as_secs()
+                    < now()
         }
     }
 }
 
-#[derive(Default, Deserialize, Serialize, Clone)]
+#[derive(Default, Debug, Deserialize, Serialize, Clone)]
 pub struct PrintLogEntry {
     pub timestamp: u64,
     pub printer_id: String,
@@ -383,6 +682,10 @@ pub struct Printer {
     status: PrinterStatus,
     last_updated: u64,
     current_time_left: u64,
+    #[serde(default)]
+    loaded_spool: Option<String>,
+    #[serde(default)]
+    total_print_seconds: u64,
 }
 
 impl Printer {
@@ -392,15 +695,14 @@ impl Printer {
             status: PrinterStatus::default(),
             last_updated: 0,
             current_time_left: 0,
+            loaded_spool: None,
+            total_print_seconds: 0,
         }
     }
 
     pub fn set_status(&mut self, status: PrinterStatus) {
         self.status = status;
-        self.last_updated = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
+        self.last_updated = now();
     }
 
     pub fn get_status(&self) -> PrinterStatus {
@@ -410,4 +712,45 @@ impl Printer {
     pub fn set_time_left(&mut self, time_left: u64) {
         self.current_time_left = time_left;
     }
+
+    pub fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    /// Hours spent printing over the printer's lifetime, for maintenance
+    pub fn get_total_print_hours(&self) -> f64 {
+        self.total_print_seconds as f64 / 3600.0
+    }
+}
+
+/// A spool of filament, labelled with its UUID
+#[derive(Default, Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct Spool {
+    pub uuid: String,
+    pub material: String,
+    pub color: String,
+    pub initial_grams: f64,
+    pub remaining_grams: f64,
+    pub printer_id: Option<String>,
+    pub low_warning_sent: bool,
+    pub timestamp_added: u64,
+}
+
+impl Spool {
+    /// Density in g/cm^3, used to turn filament estimates into grams
+    pub fn get_density(&self) -> f64 {
+        match self.material.to_uppercase().as_str() {
+            "ABS" => 1.04,
+            "ASA" => 1.07,
+            "PETG" => 1.27,
+            "TPU" => 1.21,
+            "NYLON" => 1.14,
+            _ => 1.24, // PLA
+        }
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.remaining_grams < LOW_SPOOL_GRAMS
+    }
 }
@@ -0,0 +1,363 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::*;
+
+const AUDIT_CSV_HEADERS: [&str; 11] = [
+    "Time",
+    "Actor Key",
+    "Actor ID",
+    "Actor Verified",
+    "Source IP",
+    "Forwarded For",
+    "Action",
+    "Target",
+    "Before",
+    "After",
+    "ID",
+];
+
+/// Header the website and kiosks send with the college ID of whoever is logged in.
+/// Anyone with the API key can send any ID, so on its own it's only a claim.
+pub const ACTING_USER_HEADER: &str = "X-Acting-User";
+
+/// Header with the user token of whoever is logged in, which proves who they are
+pub const USER_TOKEN_HEADER: &str = "X-User-Token";
+
+/// Who changed what and when, for every privileged change.
+/// Entries can only be added, there's nothing to edit or remove them.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+/// `before` and `after` are whatever was changed, eg a user's auth level or a whole checkout,
+/// and are null when something was created or deleted
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AuditEntry {
+    pub id: String,
+    pub timestamp: u64,
+    pub actor_key: String,
+    pub actor_id: Option<u64>,
+    // False when actor_id is only what the request claimed
+    pub actor_verified: bool,
+    pub source_ip: String,
+    // What the client said it was forwarded for, which anyone can set
+    pub forwarded_for: Option<String>,
+    pub action: String,
+    pub target: String,
+    pub before: serde_json::Value,
+    pub after: serde_json::Value,
+}
+
+/// Who made a request: the name of the API key they used (never the key itself),
+/// the college ID of whoever is logged in if the request said, and where it came from.
+/// `verified` is only set when the college ID came from a valid `X-User-Token`,
+/// otherwise it's whatever was claimed in `X-Acting-User`.
+/// `source_ip` is the address that connected, and `forwarded_for` is what `X-Forwarded-For` or `Forwarded` claimed, if different.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct Actor {
+    pub key: String,
+    pub college_id: Option<u64>,
+    pub verified: bool,
+    pub source_ip: String,
+    pub forwarded_for: Option<String>,
+}
+
+/// Filters for the audit log, eg `?target=12345678` or `?actor=checkout&action=checkouts.extend`.
+/// `actor` matches either the key name or the acting college ID.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AuditQuery {
+    pub target: Option<String>,
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+}
+
+impl Actor {
+    /// Reads who's acting from the request headers. Locks the database to check
+    /// user tokens, so call it before locking it.
+    pub async fn from_request(req: &HttpRequest, api_key: &str) -> Self {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|x| x.to_str().ok())
+                .map(|x| x.trim().to_string())
+        };
+
+        let claimed_id = header(ACTING_USER_HEADER).and_then(|x| x.parse::<u64>().ok());
+
+        let verified_id = match header(USER_TOKEN_HEADER) {
+            Some(token) => MEMORY_DATABASE.lock().await.roles.get_token_user(&token),
+            None => None,
+        };
+
+        let source_ip = req
+            .peer_addr()
+            .map(|x| x.ip().to_string())
+            .unwrap_or("unknown".to_string());
+
+        let forwarded_for = req
+            .connection_info()
+            .realip_remote_addr()
+            .map(|x| x.to_string())
+            .filter(|x| x != &source_ip);
+
+        Actor {
+            key: API_KEYS.lock().await.get_key_name(api_key),
+            college_id: verified_id.or(claimed_id),
+            verified: verified_id.is_some(),
+            source_ip,
+            forwarded_for,
+        }
+    }
+
+    /// The server itself, for changes it makes on its own, eg from a printer webhook
+    pub fn system(key: &str) -> Self {
+        Actor {
+            key: key.to_string(),
+            source_ip: "server".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The acting user's college ID, but only if their user token proved it
+    pub fn verified_id(&self) -> Option<u64> {
+        self.college_id.filter(|_| self.verified)
+    }
+
+    /// Who to put on records they make, eg an order's "ordered by": the signed-in user's name
+    /// if their token proved who they are, otherwise the name of the key
+    pub fn get_name(&self, users: &Users) -> String {
+        self.verified_id()
+            .and_then(|x| users.get_user_by_id(&x))
+            .map(|x| x.get_name())
+            .unwrap_or(self.key.clone())
+    }
+}
+
+impl AuditQuery {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        let actor_matches = |actor: &String| {
+            entry.actor_key.eq_ignore_ascii_case(actor) || entry.actor_id.map(|x| x.to_string()).as_ref() == Some(actor)
+        };
+
+        self.target.as_ref().map_or(true, |x| &entry.target == x)
+            && self.actor.as_ref().map_or(true, actor_matches)
+            && self.action.as_ref().map_or(true, |x| entry.action.eq_ignore_ascii_case(x))
+            && self.since.map_or(true, |x| entry.timestamp >= x)
+            && self.until.map_or(true, |x| entry.timestamp < x)
+    }
+}
+
+impl AuditLog {
+    /// Records a change, eg `record(&actor, "users.set_auth_level", id_number, &before, &after)`
+    pub fn record(
+        &mut self,
+        actor: &Actor,
+        action: &str,
+        target: impl ToString,
+        before: &impl Serialize,
+        after: &impl Serialize,
+    ) {
+        let entry = AuditEntry {
+            id: Uuid::new_v4().to_string(),
+            timestamp: now(),
+            actor_key: actor.key.clone(),
+            actor_id: actor.college_id,
+            actor_verified: actor.verified && actor.college_id.is_some(),
+            source_ip: actor.source_ip.clone(),
+            forwarded_for: actor.forwarded_for.clone(),
+            action: action.to_string(),
+            target: target.to_string(),
+            before: serde_json::to_value(before).unwrap_or_default(),
+            after: serde_json::to_value(after).unwrap_or_default(),
+        };
+
+        info!(
+            "Audit: {} ({}{}) {} {}",
+            entry.actor_key,
+            entry.actor_id.map(|x| x.to_string()).unwrap_or_default(),
+            if entry.actor_id.is_some() && !entry.actor_verified { ", claimed" } else { "" },
+            entry.action,
+            entry.target
+        );
+
+        self.entries.push(entry);
+    }
+
+    /// Records a change to many things at once, eg an import, keeping only what changed.
+    /// `before` and `after` are keyed by `key`, and anything added or removed is missing from one side.
+    pub fn record_changes<T: Serialize>(
+        &mut self,
+        actor: &Actor,
+        action: &str,
+        target: impl ToString,
+        before: &[T],
+        after: &[T],
+        key: impl Fn(&T) -> String,
+    ) {
+        let to_map = |items: &[T]| -> BTreeMap<String, serde_json::Value> {
+            items
+                .iter()
+                .map(|x| (key(x), serde_json::to_value(x).unwrap_or_default()))
+                .collect()
+        };
+
+        let mut before = to_map(before);
+        let mut after = to_map(after);
+
+        let unchanged: BTreeSet<String> = before
+            .iter()
+            .filter(|(k, v)| after.get(*k) == Some(*v))
+            .map(|(k, _)| k.clone())
+            .collect();
+
+        before.retain(|k, _| !unchanged.contains(k));
+        after.retain(|k, _| !unchanged.contains(k));
+
+        self.record(actor, action, target, &before, &after);
+    }
+
+    /// Strips a deleted user out of the log: their ID as the actor or target becomes `anonymous_id`,
+    /// and any before/after that mentions one of their IDs or emails is dropped, since it could hold their name too.
+    /// Returns how many entries were changed.
+    pub fn anonymize_user(&mut self, ids: &[u64], emails: &[String], anonymous_id: u64) -> usize {
+        let mut changed = 0;
+
+        for entry in self.entries.iter_mut() {
+            let mut mentioned = false;
+
+            if entry.actor_id.map_or(false, |x| ids.contains(&x)) {
+                entry.actor_id = Some(anonymous_id);
+                mentioned = true;
+            }
+
+            if entry.target.parse::<u64>().map_or(false, |x| ids.contains(&x)) {
+                entry.target = anonymous_id.to_string();
+                mentioned = true;
+            }
+
+            if mentions_user(&entry.before, ids, emails) {
+                entry.before = serde_json::Value::Null;
+                mentioned = true;
+            }
+
+            if mentions_user(&entry.after, ids, emails) {
+                entry.after = serde_json::Value::Null;
+                mentioned = true;
+            }
+
+            if mentioned {
+                changed += 1;
+            }
+        }
+
+        changed
+    }
+
+    /// Entries matching the query, newest first
+    pub fn query(&self, query: &AuditQuery) -> Vec<AuditEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|x| query.matches(x))
+            .cloned()
+            .collect()
+    }
+
+    pub fn export_csv(&self, query: &AuditQuery) -> Result<String, Box<dyn std::error::Error>> {
+        let mut wtr = csv::Writer::from_writer(Vec::new());
+
+        wtr.write_record(&AUDIT_CSV_HEADERS)?;
+
+        for entry in self.query(query) {
+            wtr.write_record(&[
+                chrono::NaiveDateTime::from_timestamp_opt(entry.timestamp as i64, 0)
+                    .map(|x| x.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_default(),
+                entry.actor_key.clone(),
+                entry.actor_id.map(|x| x.to_string()).unwrap_or_default(),
+                entry.actor_verified.to_string(),
+                entry.source_ip.clone(),
+                entry.forwarded_for.clone().unwrap_or_default(),
+                entry.action.clone(),
+                entry.target.clone(),
+                entry.before.to_string(),
+                entry.after.to_string(),
+                entry.id.clone(),
+            ])?;
+        }
+
+        Ok(String::from_utf8(wtr.into_inner()?)?)
+    }
+}
+
+fn mentions_user(value: &serde_json::Value, ids: &[u64], emails: &[String]) -> bool {
+    let is_user = |text: &str| {
+        text.trim().parse::<u64>().map_or(false, |x| ids.contains(&x)) || emails.contains(&text.trim().to_lowercase())
+    };
+
+    match value {
+        serde_json::Value::Number(x) => x.as_u64().map_or(false, |x| ids.contains(&x)),
+        serde_json::Value::String(x) => is_user(x),
+        serde_json::Value::Array(x) => x.iter().any(|x| mentions_user(x, ids, emails)),
+        serde_json::Value::Object(x) => x.iter().any(|(k, v)| is_user(k) || mentions_user(v, ids, emails)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Actor, AuditLog, AuditQuery};
+    use serde_json::json;
+
+    #[test]
+    fn anonymizing_removes_the_user_from_entries() {
+        let mut log = AuditLog::default();
+
+        let steward = Actor {
+            key: "checkout".to_string(),
+            college_id: Some(12345678),
+            verified: true,
+            source_ip: "127.0.0.1".to_string(),
+            ..Default::default()
+        };
+        let admin = Actor {
+            key: "admin".to_string(),
+            ..Default::default()
+        };
+
+        log.record(&steward, "checkouts.extend", "checkout:abc", &json!(null), &json!(null));
+        log.record(&admin, "users.set_auth_level", 12345678, &json!("User"), &json!("Steward"));
+        log.record(&admin, "users.merge", 7654321, &json!({ "name": "Test", "college_id": 12345678 }), &json!(null));
+        log.record(&admin, "roles.assign", 7654321, &json!(["test@example.edu"]), &json!(null));
+        log.record(&admin, "inventory.update", "item:1", &json!({ "name": "Drill" }), &json!(null));
+
+        let changed = log.anonymize_user(&[12345678], &["test@example.edu".to_string()], 0);
+        assert_eq!(changed, 4);
+
+        let entries = log.query(&AuditQuery::default());
+        let entry = |action: &str| entries.iter().find(|x| x.action == action).unwrap().clone();
+
+        assert_eq!(entry("checkouts.extend").actor_id, Some(0));
+
+        let auth = entry("users.set_auth_level");
+        assert_eq!(auth.target, "0");
+        assert_eq!(auth.after, json!("Steward"));
+
+        let merge = entry("users.merge");
+        assert_eq!(merge.target, "7654321");
+        assert!(merge.before.is_null());
+
+        assert!(entry("roles.assign").before.is_null());
+        assert_eq!(entry("inventory.update").before, json!({ "name": "Drill" }));
+    }
+}
@@ -0,0 +1,491 @@
+use chrono::{Datelike, NaiveDate};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::*;
+
+const STATEMENT_CSV_HEADERS: [&str; 8] = [
+    "College ID",
+    "Name",
+    "Email",
+    "Month",
+    "Opening Balance",
+    "Charges",
+    "Payments and Waivers",
+    "Closing Balance",
+];
+
+/// Charges for consumable materials and the payments and waivers against them.
+/// Money is kept in whole cents, and a positive balance is owed by the user.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Billing {
+    pub prices: Vec<MaterialPrice>,
+    pub usage: Vec<MaterialUsageRecord>,
+    pub credits: Vec<BillingCredit>,
+    // Month of the last statements emailed out, eg 2026-09
+    pub last_statement_month: String,
+}
+
+/// What a material costs, eg PLA at 2.5 cents per g.
+/// Prices can be fractions of a cent, charges are rounded to whole cents when they're logged.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MaterialPrice {
+    pub material: String,
+    pub unit: String,
+    pub price_per_unit_cents: f64,
+}
+
+/// Material a user used, priced when it was logged
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MaterialUsageRecord {
+    pub id: String,
+    pub college_id: u64,
+    pub material: String,
+    pub amount: f64,
+    pub unit: String,
+    pub price_per_unit_cents: f64,
+    pub cost_cents: i64,
+    // What logged it, eg "kiosk", "printers" or "laser"
+    pub source: String,
+    pub notes: String,
+    pub timestamp: u64,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum CreditKind {
+    #[default]
+    Payment,
+    Waiver,
+}
+
+/// A payment or waiver recorded by an admin, which lowers a user's balance.
+/// `recorded_by` is filled in from whoever made the request, see `Actor::get_name`.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BillingCredit {
+    pub id: String,
+    pub college_id: u64,
+    pub kind: CreditKind,
+    pub amount_cents: i64,
+    pub notes: String,
+    pub recorded_by: String,
+    pub timestamp: u64,
+}
+
+/// Body of a request to log material as used by someone
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MaterialUsageRequest {
+    pub college_id: u64,
+    pub material: String,
+    pub amount: f64,
+    pub source: String,
+    pub notes: String,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct UserBalance {
+    pub college_id: u64,
+    pub balance_cents: i64,
+}
+
+/// A user's activity over one month
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct Statement {
+    pub college_id: u64,
+    pub name: String,
+    pub email: String,
+    pub month: String,
+    pub opening_balance_cents: i64,
+    pub charges: Vec<MaterialUsageRecord>,
+    pub credits: Vec<BillingCredit>,
+    pub closing_balance_cents: i64,
+}
+
+impl Billing {
+    /// Adds or replaces the price for a material
+    pub fn set_price(&mut self, price: MaterialPrice) -> Result<(), String> {
+        if price.material.trim().is_empty() {
+            return Err("Material must have a name".to_string());
+        }
+
+        if !price.price_per_unit_cents.is_finite() || price.price_per_unit_cents < 0.0 {
+            return Err("Price can't be negative".to_string());
+        }
+
+        info!(
+            "Setting price of {} to {} cents per {}",
+            price.material, price.price_per_unit_cents, price.unit
+        );
+
+        let pos = self
+            .prices
+            .iter()
+            .position(|x| x.material.eq_ignore_ascii_case(&price.material));
+
+        if let Some(pos) = pos {
+            self.prices[pos] = price;
+        } else {
+            self.prices.push(price);
+        }
+
+        Ok(())
+    }
+
+    pub fn get_price(&self, material: &str) -> Option<MaterialPrice> {
+        self.prices
+            .iter()
+            .find(|x| x.material.eq_ignore_ascii_case(material))
+            .cloned()
+    }
+
+    /// Charges a user for material they used, at the current price
+    pub fn log_usage(&mut self, request: MaterialUsageRequest) -> Result<MaterialUsageRecord, String> {
+        let price = self
+            .get_price(&request.material)
+            .ok_or(format!("No price set for {}", request.material))?;
+
+        if !request.amount.is_finite() || request.amount <= 0.0 {
+            return Err("Amount used must be more than 0".to_string());
+        }
+
+        let record = MaterialUsageRecord {
+            id: Uuid::new_v4().to_string(),
+            college_id: request.college_id,
+            material: price.material.clone(),
+            amount: request.amount,
+            unit: price.unit.clone(),
+            price_per_unit_cents: price.price_per_unit_cents,
+            cost_cents: (request.amount * price.price_per_unit_cents).round() as i64,
+            source: request.source,
+            notes: request.notes,
+            timestamp: now(),
+        };
+
+        info!(
+            "{} used {} {} of {} (${})",
+            record.college_id,
+            record.amount,
+            record.unit,
+            record.material,
+            format_cents(record.cost_cents)
+        );
+
+        self.usage.push(record.clone());
+
+        Ok(record)
+    }
+
+    pub fn add_credit(&mut self, mut credit: BillingCredit) -> Result<BillingCredit, String> {
+        if credit.amount_cents <= 0 {
+            return Err("Amount must be more than 0".to_string());
+        }
+
+        credit.id = Uuid::new_v4().to_string();
+        credit.timestamp = now();
+
+        info!(
+            "Recording {:?} of ${} for {}",
+            credit.kind,
+            format_cents(credit.amount_cents),
+            credit.college_id
+        );
+
+        self.credits.push(credit.clone());
+
+        Ok(credit)
+    }
+
+    /// Moves charges and credits to a new college ID, for merges and re-keys
+    pub fn rekey_user(&mut self, from: &u64, to: &u64) -> usize {
+        let mut moved = 0;
+
+        for record in self.usage.iter_mut().filter(|x| &x.college_id == from) {
+            record.college_id = *to;
+            moved += 1;
+        }
+
+        for credit in self.credits.iter_mut().filter(|x| &x.college_id == from) {
+            credit.college_id = *to;
+            moved += 1;
+        }
+
+        moved
+    }
+
+    pub fn get_balance(&self, college_id: u64) -> i64 {
+        self.get_balance_before(college_id, None)
+    }
+
+    /// Balance from everything before the start of `month`, or everything if None
+    fn get_balance_before(&self, college_id: u64, month: Option<&str>) -> i64 {
+        let before = |timestamp: u64| month.map(|m| get_month(timestamp).as_str() < m).unwrap_or(true);
+
+        let charged: i64 = self
+            .usage
+            .iter()
+            .filter(|x| x.college_id == college_id && before(x.timestamp))
+            .map(|x| x.cost_cents)
+            .sum();
+
+        let credited: i64 = self
+            .credits
+            .iter()
+            .filter(|x| x.college_id == college_id && before(x.timestamp))
+            .map(|x| x.amount_cents)
+            .sum();
+
+        charged - credited
+    }
+
+    /// Everyone's running balance, for everyone who's ever been charged
+    pub fn get_balances(&self) -> Vec<UserBalance> {
+        let mut ids: Vec<u64> = self.usage.iter().map(|x| x.college_id).collect();
+        ids.sort();
+        ids.dedup();
+
+        ids.into_iter()
+            .map(|college_id| UserBalance {
+                college_id,
+                balance_cents: self.get_balance(college_id),
+            })
+            .collect()
+    }
+
+    /// Gets a user's statement for a month, eg 2026-09
+    pub fn get_statement(&self, college_id: u64, month: &str, users: &Users) -> Statement {
+        let user = users.get_user_by_id(&college_id);
+
+        let charges: Vec<MaterialUsageRecord> = self
+            .usage
+            .iter()
+            .filter(|x| x.college_id == college_id && get_month(x.timestamp) == month)
+            .cloned()
+            .collect();
+
+        let credits: Vec<BillingCredit> = self
+            .credits
+            .iter()
+            .filter(|x| x.college_id == college_id && get_month(x.timestamp) == month)
+            .cloned()
+            .collect();
+
+        let opening_balance = self.get_balance_before(college_id, Some(month));
+
+        let closing_balance = opening_balance + charges.iter().map(|x| x.cost_cents).sum::<i64>()
+            - credits.iter().map(|x| x.amount_cents).sum::<i64>();
+
+        Statement {
+            college_id,
+            name: user.as_ref().map(|x| x.get_name()).unwrap_or_default(),
+            email: user.as_ref().map(|x| x.get_email()).unwrap_or_default(),
+            month: month.to_string(),
+            opening_balance_cents: opening_balance,
+            charges,
+            credits,
+            closing_balance_cents: closing_balance,
+        }
+    }
+
+    /// Statements for everyone with activity in the month or a balance left over
+    pub fn get_statements(&self, month: &str, users: &Users) -> Vec<Statement> {
+        let mut ids: Vec<u64> = self
+            .usage
+            .iter()
+            .map(|x| x.college_id)
+            .chain(self.credits.iter().map(|x| x.college_id))
+            .collect();
+        ids.sort();
+        ids.dedup();
+
+        ids.into_iter()
+            .map(|id| self.get_statement(id, month, users))
+            .filter(|x| x.has_activity())
+            .collect()
+    }
+
+    /// One summary row per user for the month
+    pub fn export_statements_csv(
+        &self,
+        month: &str,
+        users: &Users,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let mut wtr = csv::Writer::from_writer(Vec::new());
+
+        wtr.write_record(&STATEMENT_CSV_HEADERS)?;
+
+        for statement in self.get_statements(month, users) {
+            wtr.write_record(&[
+                statement.college_id.to_string(),
+                statement.name.clone(),
+                statement.email.clone(),
+                statement.month.clone(),
+                format_cents(statement.opening_balance_cents),
+                format_cents(statement.get_total_charges()),
+                format_cents(statement.get_total_credits()),
+                format_cents(statement.closing_balance_cents),
+            ])?;
+        }
+
+        Ok(String::from_utf8(wtr.into_inner()?)?)
+    }
+}
+
+impl Statement {
+    pub fn has_activity(&self) -> bool {
+        !self.charges.is_empty()
+            || !self.credits.is_empty()
+            || self.closing_balance_cents != 0
+    }
+
+    pub fn get_total_charges(&self) -> i64 {
+        self.charges.iter().map(|x| x.cost_cents).sum()
+    }
+
+    pub fn get_total_credits(&self) -> i64 {
+        self.credits.iter().map(|x| x.amount_cents).sum()
+    }
+
+    /// Table rows for the statement email, one for each charge and credit
+    pub fn to_html_rows(&self) -> String {
+        let charges = self.charges.iter().map(|x| {
+            format!(
+                "<tr style=\"border: 1px solid black; border-collapse: collapse;\">
+                    <td style=\"border: 1px solid black; border-collapse: collapse; padding: 5px;\">{}</td>
+                    <td style=\"border: 1px solid black; border-collapse: collapse; padding: 5px;\">{} {} of {}</td>
+                    <td style=\"border: 1px solid black; border-collapse: collapse; padding: 5px;\">${}</td>
+                </tr>",
+                get_date(x.timestamp), x.amount, x.unit, x.material, format_cents(x.cost_cents)
+            )
+        });
+
+        let credits = self.credits.iter().map(|x| {
+            format!(
+                "<tr style=\"border: 1px solid black; border-collapse: collapse;\">
+                    <td style=\"border: 1px solid black; border-collapse: collapse; padding: 5px;\">{}</td>
+                    <td style=\"border: 1px solid black; border-collapse: collapse; padding: 5px;\">{:?}</td>
+                    <td style=\"border: 1px solid black; border-collapse: collapse; padding: 5px;\">-${}</td>
+                </tr>",
+                get_date(x.timestamp), x.kind, format_cents(x.amount_cents)
+            )
+        });
+
+        charges.chain(credits).collect::<Vec<String>>().join("\n")
+    }
+}
+
+/// Emails everyone their statement for a month, then remembers it was sent.
+/// Re-sending an older month doesn't move `last_statement_month` back, or the next loop would send last month's again.
+pub async fn send_statements(month: &str) {
+    let data = MEMORY_DATABASE.lock().await;
+    let statements = data.billing.get_statements(month, &data.users);
+    drop(data);
+
+    info!("Sending {} material statements for {}", statements.len(), month);
+
+    for statement in statements {
+        if statement.email.is_empty() {
+            warn!("No email for {}, skipping their statement", statement.college_id);
+            continue;
+        }
+
+        let body = EMAIL_TEMPLATES.lock().await.get_material_statement(
+            month,
+            &statement.to_html_rows(),
+            &format_cents(statement.opening_balance_cents),
+            &format_cents(statement.closing_balance_cents),
+        );
+
+        let _ = send_individual_email(
+            statement.email.clone(),
+            None,
+            format!("Makerspace Material Statement for {}", month),
+            body,
+        )
+        .await;
+    }
+
+    let mut data = MEMORY_DATABASE.lock().await;
+
+    // Months are eg 2026-09, so they sort as text
+    if month > data.billing.last_statement_month.as_str() {
+        data.billing.last_statement_month = month.to_string();
+    }
+}
+
+/// Gets the month a timestamp falls in, eg 2026-09
+pub fn get_month(timestamp: u64) -> String {
+    chrono::NaiveDateTime::from_timestamp_opt(timestamp as i64, 0)
+        .map(|x| x.format("%Y-%m").to_string())
+        .unwrap_or_default()
+}
+
+/// Gets the month before the current one, eg 2026-09 during October 2026
+pub fn get_previous_month() -> String {
+    let today = chrono::Utc::now().date_naive();
+
+    let first_of_month = NaiveDate::from_ymd_opt(today.year(), today.month(), 1).unwrap();
+    let last_month = first_of_month.pred_opt().unwrap();
+
+    last_month.format("%Y-%m").to_string()
+}
+
+pub fn is_month(month: &str) -> bool {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").is_ok()
+}
+
+fn get_date(timestamp: u64) -> String {
+    chrono::NaiveDateTime::from_timestamp_opt(timestamp as i64, 0)
+        .map(|x| x.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Billing, BillingCredit, MaterialPrice, MaterialUsageRequest};
+
+    #[test]
+    fn charges_are_rounded_to_whole_cents() {
+        let mut billing = Billing::default();
+        billing
+            .set_price(MaterialPrice {
+                material: "PLA".to_string(),
+                unit: "g".to_string(),
+                price_per_unit_cents: 2.5,
+            })
+            .unwrap();
+
+        for _ in 0..3 {
+            let record = billing
+                .log_usage(MaterialUsageRequest {
+                    college_id: 1,
+                    material: "pla".to_string(),
+                    amount: 13.1,
+                    ..Default::default()
+                })
+                .unwrap();
+
+            assert_eq!(record.cost_cents, 33);
+        }
+
+        billing
+            .add_credit(BillingCredit {
+                college_id: 1,
+                amount_cents: 99,
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(billing.get_balance(1), 0);
+        assert!(billing
+            .add_credit(BillingCredit {
+                college_id: 1,
+                ..Default::default()
+            })
+            .is_err());
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+
+use chrono::Datelike;
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::inventory::inventory::Inventory;
+use crate::inventory::restock::{RestockStatus, RestockUpdate};
+use crate::{format_cents, now};
+
+// The fiscal year starts in July, so July 2025 to June 2026 is FY2026
+const FISCAL_YEAR_START_MONTH: u32 = 7;
+
+const LEDGER_CSV_HEADERS: [&str; 13] = [
+    "Date",
+    "Purchase Order",
+    "Fiscal Period",
+    "Category",
+    "Vendor",
+    "Description",
+    "Inventory Item",
+    "Restock Request",
+    "Quantity",
+    "Unit Cost",
+    "Total",
+    "Ordered By",
+    "Notes",
+];
+
+/// Spending on supplies, tracked as purchase orders against
+/// budget categories for each fiscal period. Money is kept in whole cents.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Budget {
+    pub categories: Vec<BudgetCategory>,
+    pub purchase_orders: Vec<PurchaseOrder>,
+}
+
+/// A pot of money, eg "Consumables" or "Equipment".
+/// Allocations are keyed by fiscal period, eg "FY2026".
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BudgetCategory {
+    pub name: String,
+    pub allocations_cents: BTreeMap<String, i64>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PurchaseOrder {
+    pub id: String,
+    pub vendor: String,
+    pub category: String,
+    // Taken from the order date when left empty
+    pub fiscal_period: String,
+    pub timestamp_ordered: u64,
+    pub ordered_by: String,
+    pub line_items: Vec<LineItem>,
+    pub shipping_cents: i64,
+    pub tax_cents: i64,
+    pub notes: String,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LineItem {
+    pub description: String,
+    pub item_id: Option<String>,
+    pub restock_id: Option<String>,
+    pub quantity: u64,
+    pub unit_cost_cents: i64,
+}
+
+/// How much of a category's allocation is left in a fiscal period
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct BudgetSummary {
+    pub category: String,
+    pub fiscal_period: String,
+    pub allocated_cents: i64,
+    pub spent_cents: i64,
+    pub remaining_cents: i64,
+}
+
+/// Query parameters for a ledger export. Leaving out the period exports every order.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LedgerQuery {
+    pub fiscal_period: Option<String>,
+}
+
+/// Body of a request to set a category's allocation for a fiscal period
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BudgetAllocation {
+    pub category: String,
+    pub fiscal_period: String,
+    pub amount_cents: i64,
+}
+
+impl Budget {
+    /// Sets how much a category has to spend in a fiscal period,
+    /// creating the category if it doesn't exist yet
+    pub fn set_allocation(&mut self, allocation: BudgetAllocation) -> Result<(), String> {
+        if allocation.category.trim().is_empty() {
+            return Err("Category must have a name".to_string());
+        }
+
+        if !is_fiscal_period(&allocation.fiscal_period) {
+            return Err(format!(
+                "Invalid fiscal period {}, expected eg FY2026",
+                allocation.fiscal_period
+            ));
+        }
+
+        if allocation.amount_cents < 0 {
+            return Err("Allocation can't be negative".to_string());
+        }
+
+        let pos = self
+            .categories
+            .iter()
+            .position(|x| x.name == allocation.category);
+
+        let category = if let Some(pos) = pos {
+            &mut self.categories[pos]
+        } else {
+            info!("Creating budget category {}", allocation.category);
+
+            self.categories.push(BudgetCategory {
+                name: allocation.category.clone(),
+                allocations_cents: BTreeMap::new(),
+            });
+            self.categories.last_mut().unwrap()
+        };
+
+        category
+            .allocations_cents
+            .insert(allocation.fiscal_period, allocation.amount_cents);
+
+        Ok(())
+    }
+
+    /// Records a purchase order, checking its category and links.
+    /// Linked restock requests that haven't been ordered yet are marked ordered.
+    pub fn add_purchase_order(
+        &mut self,
+        mut order: PurchaseOrder,
+        inventory: &mut Inventory,
+    ) -> Result<PurchaseOrder, String> {
+        if !self.categories.iter().any(|x| x.name == order.category) {
+            return Err(format!("No budget category named {}", order.category));
+        }
+
+        if order.line_items.is_empty() {
+            return Err("Purchase order has no line items".to_string());
+        }
+
+        if order.shipping_cents < 0 || order.tax_cents < 0 {
+            return Err("Shipping and tax can't be negative".to_string());
+        }
+
+        for line in order.line_items.iter() {
+            if line.quantity == 0 || line.unit_cost_cents < 0 {
+                return Err(format!("Line item {} needs a quantity of at least 1 and a cost that isn't negative", line.description));
+            }
+
+            let total = i64::try_from(line.quantity)
+                .ok()
+                .and_then(|x| x.checked_mul(line.unit_cost_cents));
+
+            if total.is_none() {
+                return Err(format!("Line item {} costs too much", line.description));
+            }
+
+            if let Some(item_id) = &line.item_id {
+                if inventory.get_item_by_id(item_id).is_none() {
+                    return Err(format!("No item found with id {}", item_id));
+                }
+            }
+
+            if let Some(restock_id) = &line.restock_id {
+                if inventory.get_restock_notice(restock_id).is_none() {
+                    return Err(format!("No restock request found with id {}", restock_id));
+                }
+            }
+        }
+
+        order.id = Uuid::new_v4().to_string();
+
+        if order.timestamp_ordered == 0 {
+            order.timestamp_ordered = now();
+        }
+
+        if order.fiscal_period.is_empty() {
+            order.fiscal_period = get_fiscal_period(order.timestamp_ordered);
+        } else if !is_fiscal_period(&order.fiscal_period) {
+            return Err(format!(
+                "Invalid fiscal period {}, expected eg FY2026",
+                order.fiscal_period
+            ));
+        }
+
+        for line in order.line_items.iter() {
+            if let Some(restock_id) = &line.restock_id {
+                let notice = inventory.get_restock_notice(restock_id).unwrap();
+
+                if notice.status < RestockStatus::Ordered {
+                    let _ = inventory.update_restock_notice(
+                        restock_id,
+                        RestockUpdate {
+                            status: Some(RestockStatus::Ordered),
+                            cost_cents: Some(line.get_total()),
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+        }
+
+        info!(
+            "Adding purchase order {} from {} for ${}",
+            order.id,
+            order.vendor,
+            format_cents(order.get_total())
+        );
+
+        self.purchase_orders.push(order.clone());
+
+        Ok(order)
+    }
+
+    pub fn delete_purchase_order(&mut self, id: &str) -> Result<PurchaseOrder, String> {
+        let pos = self.purchase_orders.iter().position(|x| x.id == id);
+
+        if let Some(pos) = pos {
+            info!("Deleting purchase order {}", id);
+
+            Ok(self.purchase_orders.remove(pos))
+        } else {
+            Err(format!("No purchase order found with id {}", id))
+        }
+    }
+
+    /// Allocated, spent and remaining amounts for every category in a fiscal period
+    pub fn get_summary(&self, fiscal_period: &str) -> Vec<BudgetSummary> {
+        self.categories
+            .iter()
+            .map(|category| {
+                let allocated = category
+                    .allocations_cents
+                    .get(fiscal_period)
+                    .cloned()
+                    .unwrap_or(0);
+
+                let spent: i64 = self
+                    .purchase_orders
+                    .iter()
+                    .filter(|x| x.category == category.name && x.fiscal_period == fiscal_period)
+                    .map(|x| x.get_total())
+                    .sum();
+
+                BudgetSummary {
+                    category: category.name.clone(),
+                    fiscal_period: fiscal_period.to_string(),
+                    allocated_cents: allocated,
+                    spent_cents: spent,
+                    remaining_cents: allocated - spent,
+                }
+            })
+            .collect()
+    }
+
+    /// Exports every line of every purchase order as a CSV ledger.
+    /// Shipping and tax get their own rows so the totals add up.
+    pub fn export_ledger_csv(
+        &self,
+        fiscal_period: Option<&str>,
+        inventory: &Inventory,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let mut wtr = csv::Writer::from_writer(Vec::new());
+
+        wtr.write_record(&LEDGER_CSV_HEADERS)?;
+
+        let mut orders: Vec<&PurchaseOrder> = self
+            .purchase_orders
+            .iter()
+            .filter(|x| fiscal_period.is_none() || Some(x.fiscal_period.as_str()) == fiscal_period)
+            .collect();
+
+        orders.sort_by_key(|x| x.timestamp_ordered);
+
+        for order in orders {
+            let date = chrono::NaiveDateTime::from_timestamp_opt(order.timestamp_ordered as i64, 0)
+                .map(|x| x.format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+
+            let mut lines = order.line_items.clone();
+
+            if order.shipping_cents != 0 {
+                lines.push(LineItem {
+                    description: "Shipping".to_string(),
+                    quantity: 1,
+                    unit_cost_cents: order.shipping_cents,
+                    ..Default::default()
+                });
+            }
+
+            if order.tax_cents != 0 {
+                lines.push(LineItem {
+                    description: "Tax".to_string(),
+                    quantity: 1,
+                    unit_cost_cents: order.tax_cents,
+                    ..Default::default()
+                });
+            }
+
+            for line in lines {
+                let item_name = line
+                    .item_id
+                    .as_ref()
+                    .and_then(|x| inventory.get_item_by_id(x))
+                    .map(|x| x.name)
+                    .unwrap_or_default();
+
+                wtr.write_record(&[
+                    date.clone(),
+                    order.id.clone(),
+                    order.fiscal_period.clone(),
+                    order.category.clone(),
+                    order.vendor.clone(),
+                    line.description.clone(),
+                    item_name,
+                    line.restock_id.clone().unwrap_or_default(),
+                    line.quantity.to_string(),
+                    format_cents(line.unit_cost_cents),
+                    format_cents(line.get_total()),
+                    order.ordered_by.clone(),
+                    order.notes.clone(),
+                ])?;
+            }
+        }
+
+        Ok(String::from_utf8(wtr.into_inner()?)?)
+    }
+}
+
+impl PurchaseOrder {
+    /// Total in cents, including shipping and tax
+    pub fn get_total(&self) -> i64 {
+        self.line_items.iter().map(|x| x.get_total()).sum::<i64>() + self.shipping_cents + self.tax_cents
+    }
+}
+
+impl LineItem {
+    pub fn get_total(&self) -> i64 {
+        self.unit_cost_cents * self.quantity as i64
+    }
+}
+
+/// Gets the fiscal period a timestamp falls in, eg "FY2026"
+pub fn get_fiscal_period(timestamp: u64) -> String {
+    let date = chrono::NaiveDateTime::from_timestamp_opt(timestamp as i64, 0).unwrap_or_default();
+
+    let year = if date.month() >= FISCAL_YEAR_START_MONTH {
+        date.year() + 1
+    } else {
+        date.year()
+    };
+
+    format!("FY{}", year)
+}
+
+pub fn get_current_fiscal_period() -> String {
+    get_fiscal_period(now())
+}
+
+fn is_fiscal_period(period: &str) -> bool {
+    period.len() == 6
+        && period.starts_with("FY")
+        && period[2..].chars().all(|c| c.is_ascii_digit())
+}
@@ -0,0 +1,291 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::inventory::checkout::CheckoutLog;
+use crate::inventory::inventory::Inventory;
+use crate::machines::maintenance::{Maintenance, MaintenanceTarget, OutOfService};
+use crate::now;
+use crate::people::suspensions::{Suspension, SuspensionScope, Suspensions};
+use crate::people::users::{AuthLevel, Users};
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum IncidentKind {
+    #[default]
+    Damage,
+    Injury,
+    Misconduct,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd)]
+pub enum IncidentSeverity {
+    #[default]
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum IncidentStatus {
+    #[default]
+    Open,
+    FollowingUp,
+    Resolved,
+}
+
+/// Reports of broken tools, injuries and misconduct.
+/// Users with `ban_after_strikes` or more strikes are suspended until an admin lifts it, if it's set.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Incidents {
+    pub reports: Vec<Incident>,
+    pub ban_after_strikes: Option<u64>,
+}
+
+/// What happened, and everything it involved.
+/// Everything it can be linked to is optional, eg an injury may not involve a checkout.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Incident {
+    pub id: String,
+    pub kind: IncidentKind,
+    pub severity: IncidentSeverity,
+    pub status: IncidentStatus,
+    pub description: String,
+    pub reported_by: String,
+    pub checkout_uuid: Option<String>,
+    pub item_id: Option<String>,
+    pub machine: Option<String>,
+    pub college_id: Option<u64>,
+    // Counts against the user, see `Incidents::ban_after_strikes`
+    pub strike: bool,
+    pub follow_up_notes: Vec<String>,
+    pub timestamp_reported: u64,
+    pub timestamp_resolved: Option<u64>,
+}
+
+/// Body of a request to follow up on an incident.
+/// Fields left out are kept as they are.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct IncidentUpdate {
+    pub status: Option<IncidentStatus>,
+    pub severity: Option<IncidentSeverity>,
+    pub strike: Option<bool>,
+    pub note: Option<String>,
+}
+
+impl Incidents {
+    /// Files a report, filling in the user and item from the checkout it's linked to.
+    /// Damaged items and machines are taken out of service.
+    pub fn report(
+        &mut self,
+        mut incident: Incident,
+        checkout_log: &CheckoutLog,
+        inventory: &Inventory,
+        maintenance: &mut Maintenance,
+        users: &Users,
+    ) -> Result<Incident, String> {
+        if incident.description.trim().is_empty() {
+            return Err("Incident must have a description".to_string());
+        }
+
+        if let Some(checkout_uuid) = &incident.checkout_uuid {
+            let checkout = checkout_log
+                .get_entry(checkout_uuid)
+                .ok_or(format!("No checkout found with UUID {}", checkout_uuid))?;
+
+            if incident.college_id.is_none() {
+                incident.college_id = Some(checkout.college_id);
+            }
+
+            // Only guess the item when there's no doubt which one it was
+            if incident.item_id.is_none() && checkout.items.len() == 1 {
+                incident.item_id = inventory.get_item_by_name(&checkout.items[0]).map(|x| x.id);
+            }
+        }
+
+        if let Some(item_id) = &incident.item_id {
+            if inventory.get_item_by_id(item_id).is_none() {
+                return Err(format!("No item found with id {}", item_id));
+            }
+        }
+
+        if let Some(college_id) = &incident.college_id {
+            if !users.exists(college_id) {
+                return Err(format!("No user found with id {}", college_id));
+            }
+        }
+
+        incident.id = Uuid::new_v4().to_string();
+        incident.status = IncidentStatus::Open;
+        incident.timestamp_reported = now();
+        incident.timestamp_resolved = None;
+
+        info!("Reporting {:?} incident {}", incident.kind, incident.id);
+
+        if incident.kind == IncidentKind::Damage {
+            let targets = incident
+                .item_id
+                .iter()
+                .map(|x| MaintenanceTarget::Item(x.clone()))
+                .chain(incident.machine.iter().map(|x| MaintenanceTarget::Machine(x.clone())));
+
+            for target in targets {
+                maintenance.set_out_of_service(OutOfService {
+                    target,
+                    reason: format!("Damage reported: {}", incident.description),
+                    set_by: incident.reported_by.clone(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        self.reports.push(incident.clone());
+
+        Ok(incident)
+    }
+
+    pub fn update(&mut self, id: &str, update: IncidentUpdate) -> Result<Incident, String> {
+        let incident = self
+            .reports
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or(format!("No incident found with id {}", id))?;
+
+        if let Some(status) = update.status {
+            incident.timestamp_resolved = if status == IncidentStatus::Resolved {
+                Some(now())
+            } else {
+                None
+            };
+            incident.status = status;
+        }
+        if let Some(severity) = update.severity {
+            incident.severity = severity;
+        }
+        if let Some(strike) = update.strike {
+            incident.strike = strike;
+        }
+        if let Some(note) = update.note.filter(|x| !x.trim().is_empty()) {
+            incident.follow_up_notes.push(note);
+        }
+
+        info!("Incident {} is now {:?}", incident.id, incident.status);
+
+        Ok(incident.clone())
+    }
+
+    pub fn get_incident(&self, id: &str) -> Option<Incident> {
+        self.reports.iter().find(|x| x.id == id).cloned()
+    }
+
+    /// Moves incidents, and the strikes they carry, to a new college ID
+    pub fn rekey_user(&mut self, from: &u64, to: &u64) -> usize {
+        let mut moved = 0;
+
+        for incident in self.reports.iter_mut().filter(|x| x.college_id.as_ref() == Some(from)) {
+            incident.college_id = Some(*to);
+            moved += 1;
+        }
+
+        moved
+    }
+
+    pub fn get_incidents_for_id(&self, college_id: &u64) -> Vec<Incident> {
+        self.reports
+            .iter()
+            .filter(|x| x.college_id.as_ref() == Some(college_id))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_strikes(&self, college_id: &u64) -> u64 {
+        self.reports
+            .iter()
+            .filter(|x| x.strike && x.college_id.as_ref() == Some(college_id))
+            .count() as u64
+    }
+
+    /// Suspends the incident's user from everything if it took them to the strike limit,
+    /// unless they already are. Stewards and above are left for an admin to deal with.
+    /// Call after reporting or updating an incident, and email the user if they were suspended.
+    pub fn apply_strikes(
+        &self,
+        incident: &Incident,
+        issued_by: u64,
+        users: &Users,
+        suspensions: &mut Suspensions,
+    ) -> Option<Suspension> {
+        let (limit, college_id) = match (self.ban_after_strikes, incident.college_id) {
+            (Some(limit), Some(college_id)) => (limit, college_id),
+            _ => return None,
+        };
+
+        if !incident.strike || self.get_strikes(&college_id) < limit {
+            return None;
+        }
+
+        let user = users.get_user_by_id(&college_id)?;
+
+        if user.get_auth_level() != AuthLevel::User {
+            return None;
+        }
+
+        let already_suspended = suspensions
+            .get_active_for_id(&college_id)
+            .iter()
+            .any(|x| x.scope == SuspensionScope::All);
+
+        if already_suspended {
+            return None;
+        }
+
+        Some(suspensions.suspend_for_strikes(college_id, limit, issued_by))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Incident, Incidents};
+    use crate::people::quizzes::Response;
+    use crate::people::suspensions::{SuspensionScope, Suspensions};
+    use crate::people::users::{AuthLevel, User, Users};
+
+    #[test]
+    fn reaching_the_strike_limit_suspends_once() {
+        let mut users = Users::default();
+        users.add_set_user(User::from_response(&Response {
+            name: "Test".to_string(),
+            college_id: 12345678,
+            ..Default::default()
+        }));
+
+        let mut incidents = Incidents {
+            ban_after_strikes: Some(2),
+            ..Default::default()
+        };
+        let mut suspensions = Suspensions::default();
+
+        let strike = Incident {
+            college_id: Some(12345678),
+            strike: true,
+            ..Default::default()
+        };
+
+        incidents.reports.push(strike.clone());
+        assert!(incidents.apply_strikes(&strike, 1, &users, &mut suspensions).is_none());
+
+        incidents.reports.push(strike.clone());
+        let suspension = incidents.apply_strikes(&strike, 1, &users, &mut suspensions).unwrap();
+        assert_eq!(suspension.scope, SuspensionScope::All);
+        assert_eq!(suspension.issued_by, 1);
+        assert!(suspension.timestamp_expires.is_none());
+        assert_eq!(users.get_user_by_id(&12345678).unwrap().get_auth_level(), AuthLevel::User);
+
+        incidents.reports.push(strike.clone());
+        assert!(incidents.apply_strikes(&strike, 1, &users, &mut suspensions).is_none());
+        assert_eq!(suspensions.get_for_id(&12345678).len(), 1);
+    }
+}
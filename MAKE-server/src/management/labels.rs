@@ -0,0 +1,500 @@
+use std::io::{Cursor, Write};
+
+use image::{GrayImage, ImageOutputFormat, Luma};
+use imageproc::drawing::{draw_filled_rect_mut, draw_text_mut, text_size};
+use imageproc::rect::Rect;
+use qrcode::{Color, QrCode};
+use rusttype::{Font, Scale};
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+// Sheets are rendered at 300 DPI on US Letter paper
+const DPI: f32 = 300.0;
+const PAGE_WIDTH_IN: f32 = 8.5;
+const PAGE_HEIGHT_IN: f32 = 11.0;
+// Padding inside each label, in inches
+const LABEL_PADDING_IN: f32 = 0.06;
+// Number of light modules around each QR code
+const QR_QUIET_ZONE: u32 = 2;
+
+const FONT_DATA: &[u8] = include_bytes!("../../fonts/DejaVuSans.ttf");
+
+/// Avery-style label sheet layouts, all on US Letter paper
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum LabelLayout {
+    /// 30 labels per sheet, 2.625" x 1"
+    #[default]
+    Avery5160,
+    /// 10 labels per sheet, 4" x 2"
+    Avery5163,
+    /// 80 labels per sheet, 1.75" x 0.5"
+    Avery5167,
+}
+
+/// Measurements of a layout, in inches
+struct LayoutSpec {
+    columns: u32,
+    rows: u32,
+    label_width: f32,
+    label_height: f32,
+    margin_left: f32,
+    margin_top: f32,
+    pitch_x: f32,
+    pitch_y: f32,
+}
+
+impl LabelLayout {
+    fn spec(&self) -> LayoutSpec {
+        match self {
+            LabelLayout::Avery5160 => LayoutSpec {
+                columns: 3,
+                rows: 10,
+                label_width: 2.625,
+                label_height: 1.0,
+                margin_left: 0.1875,
+                margin_top: 0.5,
+                pitch_x: 2.75,
+                pitch_y: 1.0,
+            },
+            LabelLayout::Avery5163 => LayoutSpec {
+                columns: 2,
+                rows: 5,
+                label_width: 4.0,
+                label_height: 2.0,
+                margin_left: 0.15625,
+                margin_top: 0.5,
+                pitch_x: 4.1875,
+                pitch_y: 2.0,
+            },
+            LabelLayout::Avery5167 => LayoutSpec {
+                columns: 4,
+                rows: 20,
+                label_width: 1.75,
+                label_height: 0.5,
+                margin_left: 0.3,
+                margin_top: 0.5,
+                pitch_x: 2.05,
+                pitch_y: 0.5,
+            },
+        }
+    }
+
+    pub fn labels_per_page(&self) -> usize {
+        let spec = self.spec();
+        (spec.columns * spec.rows) as usize
+    }
+}
+
+/// Rendered sheets, ready to send
+#[derive(Debug)]
+pub enum RenderedLabels {
+    Png(Vec<Vec<u8>>),
+    Pdf(Vec<u8>),
+}
+
+impl LabelRequest {
+    pub fn check_skip(&self) -> Result<(), String> {
+        let per_page = self.layout.labels_per_page();
+
+        if self.skip >= per_page {
+            return Err(format!("Can't skip {} labels, a {:?} sheet only has {}", self.skip, self.layout, per_page));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum LabelFormat {
+    #[default]
+    Png,
+    Pdf,
+}
+
+/// Body of a label sheet request.
+///
+/// Labels are made for every item in `items` (by ID), every item in `room`,
+/// every item in `kit`, and every slot in `slots` (or all slots if `all_slots` is set).
+/// `skip` leaves that many labels blank at the start, to reuse a partly used sheet, so it has to be less than a sheet.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LabelRequest {
+    pub layout: LabelLayout,
+    pub format: LabelFormat,
+    pub items: Vec<String>,
+    pub room: Option<String>,
+    pub kit: Option<String>,
+    pub slots: Vec<String>,
+    pub all_slots: bool,
+    pub spools: Vec<String>,
+    pub skip: usize,
+}
+
+/// What gets printed on one label
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct Label {
+    pub code: String,
+    pub title: String,
+    pub subtitle: String,
+}
+
+impl Label {
+    /// One label per physical UUID, or one with the item ID if the item has none
+    pub fn from_item(item: &InventoryItem) -> Vec<Self> {
+        let location = [item.location_room.as_str(), item.location_area.as_str()]
+            .iter()
+            .filter(|x| !x.is_empty())
+            .cloned()
+            .collect::<Vec<&str>>()
+            .join(" - ");
+
+        let codes = if item.uuids.is_empty() {
+            vec![item.id.clone()]
+        } else {
+            item.uuids.clone()
+        };
+
+        codes
+            .into_iter()
+            .map(|code| Label {
+                code,
+                title: item.name.clone(),
+                subtitle: location.clone(),
+            })
+            .collect()
+    }
+
+    pub fn from_spool(spool: &Spool) -> Self {
+        Label {
+            code: spool.uuid.clone(),
+            title: format!("{} {}", spool.color, spool.material),
+            subtitle: format!("{:.0} g spool", spool.initial_grams),
+        }
+    }
+
+    pub fn from_slot(slot: &Slot) -> Self {
+        Label {
+            code: slot.id.clone(),
+            title: format!("Storage {}", slot.id),
+            subtitle: format!("{:?} slot", slot.size),
+        }
+    }
+}
+
+impl LabelRequest {
+    /// Gathers the labels for everything the request selects, without duplicates
+    pub fn collect_labels(
+        &self,
+        inventory: &Inventory,
+        student_storage: &StudentStorage,
+        printers: &Printers,
+    ) -> Vec<Label> {
+        let mut items: Vec<&InventoryItem> = Vec::new();
+
+        for item in inventory.items.iter() {
+            let selected = self.items.contains(&item.id)
+                || self
+                    .room
+                    .as_ref()
+                    .map(|room| item.location_room.eq_ignore_ascii_case(room))
+                    == Some(true)
+                || self
+                    .kit
+                    .as_ref()
+                    .map(|kit| item.kit.as_ref() == Some(kit) || (item.is_kit && &item.name == kit))
+                    == Some(true);
+
+            if selected {
+                items.push(item);
+            }
+        }
+
+        let mut labels: Vec<Label> = items.iter().flat_map(|item| Label::from_item(item)).collect();
+
+        for slot in student_storage.slots.iter() {
+            if self.all_slots || self.slots.contains(&slot.id) {
+                labels.push(Label::from_slot(slot));
+            }
+        }
+
+        for spool in printers.get_spools() {
+            if self.spools.contains(&spool.uuid) {
+                labels.push(Label::from_spool(&spool));
+            }
+        }
+
+        labels
+    }
+}
+
+fn inches_to_px(inches: f32) -> u32 {
+    (inches * DPI).round() as u32
+}
+
+/// Renders the labels onto as many sheets as needed
+pub fn render_label_pages(
+    labels: &Vec<Label>,
+    layout: &LabelLayout,
+    skip: usize,
+) -> Result<Vec<GrayImage>, String> {
+    let font = Font::try_from_bytes(FONT_DATA).ok_or("Could not load label font".to_string())?;
+    let spec = layout.spec();
+    let per_page = layout.labels_per_page();
+
+    if skip >= per_page {
+        return Err(format!("Can't skip {} labels, a sheet only has {}", skip, per_page));
+    }
+
+    let mut pages = Vec::new();
+    let total = skip + labels.len();
+
+    for page_index in 0..((total + per_page - 1) / per_page).max(1) {
+        let mut page = GrayImage::from_pixel(
+            inches_to_px(PAGE_WIDTH_IN),
+            inches_to_px(PAGE_HEIGHT_IN),
+            Luma([255]),
+        );
+
+        for position in 0..per_page {
+            let index = page_index * per_page + position;
+
+            if index < skip || index >= total {
+                continue;
+            }
+
+            let column = position as u32 % spec.columns;
+            let row = position as u32 / spec.columns;
+
+            let x = inches_to_px(spec.margin_left + column as f32 * spec.pitch_x);
+            let y = inches_to_px(spec.margin_top + row as f32 * spec.pitch_y);
+
+            draw_label(
+                &mut page,
+                &labels[index - skip],
+                &font,
+                x,
+                y,
+                inches_to_px(spec.label_width),
+                inches_to_px(spec.label_height),
+            )?;
+        }
+
+        pages.push(page);
+    }
+
+    Ok(pages)
+}
+
+fn draw_label(
+    page: &mut GrayImage,
+    label: &Label,
+    font: &Font,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> Result<(), String> {
+    let padding = inches_to_px(LABEL_PADDING_IN);
+    let qr_size = height - 2 * padding;
+
+    draw_qr_code(page, &label.code, x + padding, y + padding, qr_size)?;
+
+    // Text goes to the right of the QR code
+    let text_x = x + qr_size + 3 * padding;
+    let text_width = width.saturating_sub(qr_size + 4 * padding) as i32;
+
+    let title_size = qr_size as f32 / 3.5;
+    let small_size = qr_size as f32 / 5.5;
+
+    let lines = [
+        (label.title.as_str(), title_size),
+        (label.subtitle.as_str(), small_size),
+        (label.code.as_str(), small_size),
+    ];
+
+    let mut text_y = y + padding;
+
+    for (text, size) in lines.iter() {
+        let scale = Scale::uniform(*size);
+        let text = fit_text(text, font, scale, text_width);
+
+        draw_text_mut(page, Luma([0]), text_x as i32, text_y as i32, scale, font, &text);
+
+        text_y += (*size * 1.15) as u32;
+    }
+
+    Ok(())
+}
+
+/// Shortens text with an ellipsis until it fits in the width
+fn fit_text(text: &str, font: &Font, scale: Scale, width: i32) -> String {
+    if text_size(scale, font, text).0 <= width {
+        return text.to_string();
+    }
+
+    let mut chars: Vec<char> = text.chars().collect();
+
+    while !chars.is_empty() {
+        chars.pop();
+
+        let shortened = format!("{}…", chars.iter().collect::<String>().trim_end());
+
+        if text_size(scale, font, &shortened).0 <= width {
+            return shortened;
+        }
+    }
+
+    String::new()
+}
+
+fn draw_qr_code(page: &mut GrayImage, data: &str, x: u32, y: u32, size: u32) -> Result<(), String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| format!("Could not make QR code: {}", e))?;
+
+    let modules = code.width() as u32;
+    let colors = code.to_colors();
+
+    let module_size = size / (modules + 2 * QR_QUIET_ZONE);
+
+    if module_size == 0 {
+        return Err(format!("Label is too small for QR code of {}", data));
+    }
+
+    // Center the code in the space it was given
+    let offset = (size - module_size * modules) / 2;
+
+    for (i, color) in colors.iter().enumerate() {
+        if *color == Color::Dark {
+            let module_x = (i as u32 % modules) * module_size + x + offset;
+            let module_y = (i as u32 / modules) * module_size + y + offset;
+
+            draw_filled_rect_mut(
+                page,
+                Rect::at(module_x as i32, module_y as i32).of_size(module_size, module_size),
+                Luma([0]),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders the labels and encodes the sheets in the requested format.
+/// This is slow, so routes run it with `web::block`.
+pub fn render_labels_as(
+    labels: &Vec<Label>,
+    layout: &LabelLayout,
+    skip: usize,
+    format: &LabelFormat,
+) -> Result<RenderedLabels, String> {
+    let pages = render_label_pages(labels, layout, skip)?;
+
+    match format {
+        LabelFormat::Png => Ok(RenderedLabels::Png(pages_to_png(&pages)?)),
+        LabelFormat::Pdf => Ok(RenderedLabels::Pdf(pages_to_pdf(&pages)?)),
+    }
+}
+
+pub fn pages_to_png(pages: &Vec<GrayImage>) -> Result<Vec<Vec<u8>>, String> {
+    pages
+        .iter()
+        .map(|page| {
+            let mut buffer: Vec<u8> = Vec::new();
+            image::DynamicImage::ImageLuma8(page.clone())
+                .write_to(&mut Cursor::new(&mut buffer), ImageOutputFormat::Png)
+                .map_err(|e| e.to_string())?;
+            Ok(buffer)
+        })
+        .collect()
+}
+
+/// Writes the pages into a PDF, one full-page JPEG per page
+pub fn pages_to_pdf(pages: &Vec<GrayImage>) -> Result<Vec<u8>, String> {
+    let page_width_pt = PAGE_WIDTH_IN * 72.0;
+    let page_height_pt = PAGE_HEIGHT_IN * 72.0;
+
+    let mut pdf: Vec<u8> = Vec::new();
+    let mut offsets: Vec<usize> = Vec::new();
+
+    // Objects 1 and 2 are the catalog and page tree,
+    // then each page takes three: page, contents and image
+    let page_ids: Vec<usize> = (0..pages.len()).map(|i| 3 + i * 3).collect();
+
+    let _ = write!(pdf, "%PDF-1.4\n");
+
+    offsets.push(pdf.len());
+    let _ = write!(pdf, "1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n");
+
+    offsets.push(pdf.len());
+    let _ = write!(
+        pdf,
+        "2 0 obj\n<< /Type /Pages /Kids [{}] /Count {} >>\nendobj\n",
+        page_ids
+            .iter()
+            .map(|id| format!("{} 0 R", id))
+            .collect::<Vec<String>>()
+            .join(" "),
+        pages.len()
+    );
+
+    for (page, page_id) in pages.iter().zip(page_ids.iter()) {
+        let mut jpeg: Vec<u8> = Vec::new();
+        image::DynamicImage::ImageLuma8(page.clone())
+            .write_to(&mut Cursor::new(&mut jpeg), ImageOutputFormat::Jpeg(95))
+            .map_err(|e| e.to_string())?;
+
+        let contents = format!(
+            "q {} 0 0 {} 0 0 cm /Im0 Do Q",
+            page_width_pt, page_height_pt
+        );
+
+        offsets.push(pdf.len());
+        let _ = write!(
+            pdf,
+            "{} 0 obj\n<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents {} 0 R /Resources << /XObject << /Im0 {} 0 R >> >> >>\nendobj\n",
+            page_id,
+            page_width_pt,
+            page_height_pt,
+            page_id + 1,
+            page_id + 2
+        );
+
+        offsets.push(pdf.len());
+        let _ = write!(
+            pdf,
+            "{} 0 obj\n<< /Length {} >>\nstream\n{}\nendstream\nendobj\n",
+            page_id + 1,
+            contents.len(),
+            contents
+        );
+
+        offsets.push(pdf.len());
+        let _ = write!(
+            pdf,
+            "{} 0 obj\n<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>\nstream\n",
+            page_id + 2,
+            page.width(),
+            page.height(),
+            jpeg.len()
+        );
+        pdf.extend_from_slice(&jpeg);
+        let _ = write!(pdf, "\nendstream\nendobj\n");
+    }
+
+    let xref_offset = pdf.len();
+
+    let _ = write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+
+    for offset in offsets.iter() {
+        let _ = write!(pdf, "{:010} 00000 n \n", offset);
+    }
+
+    let _ = write!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        offsets.len() + 1,
+        xref_offset
+    );
+
+    Ok(pdf)
+}
@@ -0,0 +1,219 @@
+use std::io::Cursor;
+
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use image::{io::Reader, ImageFormat};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::*;
+
+const LOST_AND_FOUND_DIR: &str = "lost_and_found";
+const THUMBNAIL_SIZE: u32 = 256;
+// Hold found items for 30 days before they can be disposed of
+const LOST_AND_FOUND_HOLD_PERIOD: u64 = 30 * 24 * 60 * 60;
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum LostItemStatus {
+    #[default]
+    Held,
+    Claimed,
+    Disposed,
+}
+
+/// Items found around the makerspace, left in expired student storage slots
+/// or left behind with tools
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LostAndFound {
+    pub items: Vec<LostItem>,
+}
+
+impl LostAndFound {
+    pub fn add_item(&mut self, item: LostItem) {
+        info!("Logging lost and found item: {:?}", item.description);
+        self.items.push(item);
+    }
+
+    pub fn get_item_by_uuid(&self, uuid: &str) -> Option<LostItem> {
+        self.items.iter().find(|item| item.uuid == uuid).cloned()
+    }
+
+    pub fn get_items_for_id(&self, college_id: &u64) -> Vec<LostItem> {
+        self.items
+            .iter()
+            .filter(|item| item.college_id.as_ref() == Some(college_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Moves items left by or claimed by someone to a new college ID
+    pub fn rekey_user(&mut self, from: &u64, to: &u64) -> usize {
+        let mut moved = 0;
+
+        for item in self.items.iter_mut() {
+            if item.college_id.as_ref() == Some(from) {
+                item.college_id = Some(*to);
+                moved += 1;
+            }
+
+            if item.claimed_by.as_ref() == Some(from) {
+                item.claimed_by = Some(*to);
+            }
+        }
+
+        moved
+    }
+
+    /// Items that are still held but whose hold deadline has passed
+    pub fn get_ready_for_disposal(&self) -> Vec<LostItem> {
+        self.items
+            .iter()
+            .filter(|item| item.status == LostItemStatus::Held && item.is_past_hold())
+            .cloned()
+            .collect()
+    }
+
+    pub fn claim(&mut self, uuid: &str, college_id: u64) -> Result<(), String> {
+        let item = self.items.iter_mut().find(|item| item.uuid == uuid);
+
+        if let Some(item) = item {
+            if item.status != LostItemStatus::Held {
+                return Err(format!("Item is already {:?}", item.status));
+            }
+
+            item.status = LostItemStatus::Claimed;
+            item.claimed_by = Some(college_id);
+            item.timestamp_resolved = Some(now());
+
+            Ok(())
+        } else {
+            Err("Item not found".to_string())
+        }
+    }
+
+    /// Marks an item as thrown out or donated, once its hold deadline has passed
+    pub fn dispose(&mut self, uuid: &str) -> Result<(), String> {
+        let item = self.items.iter_mut().find(|item| item.uuid == uuid);
+
+        if let Some(item) = item {
+            if item.status != LostItemStatus::Held {
+                return Err(format!("Item is already {:?}", item.status));
+            }
+
+            if !item.is_past_hold() {
+                return Err(format!("Item is held until {}", item.get_hold_until_string()));
+            }
+
+            item.status = LostItemStatus::Disposed;
+            item.timestamp_resolved = Some(now());
+
+            Ok(())
+        } else {
+            Err("Item not found".to_string())
+        }
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LostItem {
+    pub uuid: String,
+    pub description: String,
+    pub location_found: String,
+    pub slot_id: Option<String>,
+    pub college_id: Option<u64>,
+    pub has_photo: bool,
+    pub status: LostItemStatus,
+    pub timestamp_logged: u64,
+    pub timestamp_hold_until: u64,
+    pub timestamp_resolved: Option<u64>,
+    pub claimed_by: Option<u64>,
+    pub owner_notified: bool,
+}
+
+impl LostItem {
+    pub fn new(request: &LostItemRequest, college_id: Option<u64>) -> Self {
+        let timestamp_logged = now();
+
+        LostItem {
+            uuid: Uuid::new_v4().to_string(),
+            description: request.description.clone(),
+            location_found: request.location_found.clone(),
+            slot_id: request.slot_id.clone(),
+            college_id,
+            has_photo: false,
+            status: LostItemStatus::Held,
+            timestamp_logged,
+            timestamp_hold_until: timestamp_logged + LOST_AND_FOUND_HOLD_PERIOD,
+            timestamp_resolved: None,
+            claimed_by: None,
+            owner_notified: false,
+        }
+    }
+
+    pub fn is_past_hold(&self) -> bool {
+        now() > self.timestamp_hold_until
+    }
+
+    pub fn get_hold_until_string(&self) -> String {
+        chrono::NaiveDateTime::from_timestamp_opt(self.timestamp_hold_until as i64, 0)
+            .map(|x| x.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Body of a request to log a found item.
+/// `photo` is a base64 encoded image with the given `photo_extension`.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LostItemRequest {
+    pub description: String,
+    pub location_found: String,
+    pub slot_id: Option<String>,
+    pub college_id: Option<u64>,
+    pub photo: Option<String>,
+    pub photo_extension: Option<String>,
+}
+
+pub fn get_photo_path(uuid: &str, thumbnail: bool) -> String {
+    if thumbnail {
+        format!("{}/{}_thumb.jpg", LOST_AND_FOUND_DIR, uuid)
+    } else {
+        format!("{}/{}.jpg", LOST_AND_FOUND_DIR, uuid)
+    }
+}
+
+/// Decodes a base64 photo, then saves it and a thumbnail as jpgs
+pub fn save_lost_item_photo(uuid: &str, b64_file: &str, file_extension: &str) -> Result<(), String> {
+    let decoded = BASE64_STANDARD
+        .decode(b64_file)
+        .map_err(|e| format!("Invalid base64: {}", e))?;
+
+    let format = ImageFormat::from_extension(file_extension)
+        .ok_or(format!("Unknown image extension {}", file_extension))?;
+
+    let mut reader = Reader::new(Cursor::new(decoded));
+    reader.set_format(format);
+
+    let img = reader
+        .decode()
+        .map_err(|e| format!("Could not decode image: {}", e))?;
+
+    // jpg has no alpha channel
+    let img = image::DynamicImage::ImageRgb8(img.to_rgb8());
+    let thumbnail = img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+
+    std::fs::create_dir_all(LOST_AND_FOUND_DIR).map_err(|e| e.to_string())?;
+
+    img.save_with_format(get_photo_path(uuid, false), ImageFormat::Jpeg)
+        .map_err(|e| e.to_string())?;
+    thumbnail
+        .save_with_format(get_photo_path(uuid, true), ImageFormat::Jpeg)
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub fn load_lost_item_photo(uuid: &str, thumbnail: bool) -> Option<Vec<u8>> {
+    std::fs::read(get_photo_path(uuid, thumbnail)).ok()
+}
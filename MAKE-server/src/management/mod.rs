@@ -1,4 +1,10 @@
+pub mod audit_log;
+pub mod billing;
+pub mod budget;
 pub mod emails;
+pub mod incidents;
+pub mod labels;
+pub mod lost_and_found;
 pub mod student_storage;
 pub mod workshops;
 pub mod spotify;
\ No newline at end of file
@@ -1,28 +1,96 @@
-use std::{time::SystemTime};
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use toml::Value;
 
 use crate::people::users::User;
 use crate::*;
 
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum SlotSize {
+    Large,
+    #[default] Small,
+}
+
+/// Splits a slot id into its row and number, eg "A12" -> ("A", "12")
+pub fn split_slot_id(slot_id: &str) -> (String, String) {
+    let row: String = slot_id.chars().take_while(|c| c.is_alphabetic()).collect();
+    let number = slot_id[row.len()..].to_string();
+
+    (row, number)
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct StudentStorage {
     pub slots: Vec<Slot>,
+    #[serde(default)]
+    pub waitlist: Vec<WaitlistEntry>,
+    #[serde(default)]
+    pub orphaned: Vec<OrphanedAssignment>,
+    #[serde(default)]
+    pub migration_reports: Vec<MigrationReport>,
+    // Row renames from [renames] that have been done, as (old, new), so they aren't done again
+    #[serde(default)]
+    pub applied_renames: Vec<(String, String)>,
 }
 
 impl StudentStorage {
     pub fn new() -> Self {
         StudentStorage {
             slots: Vec::new(),
+            waitlist: Vec::new(),
+            orphaned: Vec::new(),
+            migration_reports: Vec::new(),
+            applied_renames: Vec::new(),
         }
     }
 
-    pub fn new_defined() -> Self {
+    fn load_layout() -> Value {
         let mut file = std::fs::File::open("student_storage.toml").unwrap();
         let mut contents = String::new();
         file.read_to_string(&mut contents).unwrap();
-        let storage_rows: Value = toml::from_str(&contents).unwrap();
-        let storage_rows: Vec<(String, i64)> = storage_rows
+        toml::from_str(&contents).unwrap()
+    }
+
+    /// Gets the optional [renames] table, mapping old row names to new ones
+    pub fn get_row_renames() -> HashMap<String, String> {
+        let layout = StudentStorage::load_layout();
+
+        match layout.get("renames").and_then(|x| x.as_table()) {
+            Some(renames) => renames
+                .iter()
+                .filter_map(|(k, v)| v.as_str().map(|v| (k.to_string(), v.to_string())))
+                .collect(),
+            None => HashMap::new(),
+        }
+    }
+
+    /// Gets the optional [sizes] table, mapping row names to their slot size.
+    /// Rows that aren't listed hold small bins.
+    pub fn get_row_sizes(layout: &Value) -> HashMap<String, SlotSize> {
+        match layout.get("sizes").and_then(|x| x.as_table()) {
+            Some(sizes) => sizes
+                .iter()
+                .filter_map(|(k, v)| match v.as_str().map(|x| x.trim().to_lowercase()).as_deref() {
+                    Some("large") => Some((k.to_string(), SlotSize::Large)),
+                    Some("small") => Some((k.to_string(), SlotSize::Small)),
+                    _ => {
+                        warn!("Unknown size {} for row {} in student_storage.toml", v, k);
+                        None
+                    }
+                })
+                .collect(),
+            None => HashMap::new(),
+        }
+    }
+
+    pub fn new_defined() -> Self {
+        StudentStorage::from_layout(&StudentStorage::load_layout())
+    }
+
+    /// Builds empty slots from a layout with [sizings] and [sizes] tables
+    pub fn from_layout(layout: &Value) -> Self {
+        let sizes = StudentStorage::get_row_sizes(layout);
+        let storage_rows: Vec<(String, i64)> = layout
             .get("sizings")
             .unwrap()
             .as_table()
@@ -34,12 +102,17 @@ impl StudentStorage {
         let mut slots = Vec::new();
         for (name, size) in storage_rows {
             for i in 0..size {
-                slots.push(Slot::new(format!("{}{}", name, i+1)));
+                let size = sizes.get(&name).cloned().unwrap_or_default();
+                slots.push(Slot::new(format!("{}{}", name, i+1), size));
             }
         }
 
         StudentStorage {
             slots,
+            waitlist: Vec::new(),
+            orphaned: Vec::new(),
+            migration_reports: Vec::new(),
+            applied_renames: Vec::new(),
         }
     }
 
@@ -50,6 +123,169 @@ impl StudentStorage {
             new.slots.iter().enumerate().any(|(i, s)| s.id != self.slots[i].id)
     }
 
+    /// Migrates the saved slots to the layout in student_storage.toml in place.
+    ///
+    /// Rows listed under [renames] are moved to their new name first, once. Occupied slots
+    /// that still exist keep their details, and occupied slots that no longer exist
+    /// are moved to `orphaned` for an admin to review.
+    pub fn migrate_layout(&mut self) -> MigrationReport {
+        self.migrate_to(StudentStorage::new_defined(), StudentStorage::get_row_renames())
+    }
+
+    pub fn migrate_to(&mut self, mut new_layout: StudentStorage, renames: HashMap<String, String>) -> MigrationReport {
+        let mut report = MigrationReport::new();
+
+        // Renames left in the config would otherwise move a row that's been re-added on every later migration
+        let renames: HashMap<String, String> = renames
+            .into_iter()
+            .filter(|(from, to)| {
+                let applied = self.applied_renames.contains(&(from.clone(), to.clone()));
+
+                if applied {
+                    warn!("Row rename {} -> {} was already done, remove it from [renames]", from, to);
+                }
+
+                !applied
+            })
+            .collect();
+
+        let mut old_slots: HashMap<String, Slot> = HashMap::new();
+
+        for slot in self.slots.iter() {
+            let (row, number) = split_slot_id(&slot.id);
+
+            let id = match renames.get(&row) {
+                Some(new_row) => {
+                    let new_id = format!("{}{}", new_row, number);
+                    report.renamed.push((slot.id.clone(), new_id.clone()));
+                    new_id
+                }
+                None => slot.id.clone(),
+            };
+
+            // A row renamed onto one that's still there, or two rows renamed to the same one,
+            // land on the same slot. Keep whichever is occupied and orphan the other.
+            let displaced = match old_slots.remove(&id) {
+                Some(existing) if existing.is_occupied() || !slot.is_occupied() => {
+                    old_slots.insert(id.clone(), existing);
+                    slot.clone()
+                }
+                Some(existing) => {
+                    old_slots.insert(id.clone(), slot.clone());
+                    existing
+                }
+                None => {
+                    old_slots.insert(id, slot.clone());
+                    continue;
+                }
+            };
+
+            report.collisions.push((displaced.id.clone(), id));
+
+            if let Some(details) = displaced.occupied_details {
+                report.orphaned.push(displaced.id.clone());
+                self.orphaned.push(OrphanedAssignment::new(displaced.id, details));
+            }
+        }
+
+        for slot in new_layout.slots.iter_mut() {
+            match old_slots.remove(&slot.id) {
+                Some(old_slot) => {
+                    slot.previous_details = old_slot.previous_details.clone();
+
+                    if old_slot.is_occupied() {
+                        slot.occupied = true;
+                        slot.occupied_details = old_slot.occupied_details;
+                        report.preserved.push(slot.id.clone());
+                    }
+                }
+                None => report.added.push(slot.id.clone()),
+            }
+        }
+
+        for (id, old_slot) in old_slots.into_iter() {
+            report.removed.push(id.clone());
+
+            if let Some(details) = old_slot.occupied_details {
+                report.orphaned.push(id.clone());
+                self.orphaned.push(OrphanedAssignment::new(id, details));
+            }
+        }
+
+        // Offers on slots that are gone will be made again from the new layout
+        for entry in self.waitlist.iter_mut() {
+            if let Some(offered) = &entry.offered_slot {
+                if !new_layout.slots.iter().any(|slot| &slot.id == offered) {
+                    entry.offered_slot = None;
+                    entry.timestamp_offer_expires = None;
+                }
+            }
+        }
+
+        report.added.sort();
+        report.removed.sort();
+        report.orphaned.sort();
+
+        self.slots = new_layout.slots;
+        self.migration_reports.push(report.clone());
+        self.applied_renames.extend(renames);
+
+        report
+    }
+
+    /// Gives an orphaned assignment a slot in the current layout
+    pub fn reassign_orphan(&mut self, old_slot_id: &String, new_slot_id: &String) -> Result<(), String> {
+        let pos = self.orphaned.iter().position(|x| &x.slot_id == old_slot_id);
+
+        if pos.is_none() {
+            return Err(format!("No orphaned assignment for slot {}", old_slot_id));
+        }
+
+        let slot = self.slots.iter_mut().find(|slot| slot.get_id() == new_slot_id);
+
+        if let Some(slot) = slot {
+            if slot.is_occupied() {
+                return Err(format!("Slot {} is already occupied", new_slot_id));
+            }
+
+            let orphan = self.orphaned.remove(pos.unwrap());
+
+            slot.occupied = true;
+            slot.occupied_details = Some(orphan.occupied_details);
+
+            Ok(())
+        } else {
+            Err(format!("No slot found with id {}", new_slot_id))
+        }
+    }
+
+    pub fn get_orphan(&self, old_slot_id: &String) -> Option<OrphanedAssignment> {
+        self.orphaned.iter().find(|x| &x.slot_id == old_slot_id).cloned()
+    }
+
+    /// Drops an orphaned assignment once an admin has dealt with it
+    pub fn dismiss_orphan(&mut self, old_slot_id: &String) -> Result<(), String> {
+        let pos = self.orphaned.iter().position(|x| &x.slot_id == old_slot_id);
+
+        if let Some(pos) = pos {
+            self.orphaned.remove(pos);
+            Ok(())
+        } else {
+            Err(format!("No orphaned assignment for slot {}", old_slot_id))
+        }
+    }
+
+    /// Recomputes the size class of every slot from its row's entry in [sizes].
+    /// Slots saved before sizes existed deserialize as the default size.
+    pub fn update_sizes(&mut self) {
+        let sizes = StudentStorage::get_row_sizes(&StudentStorage::load_layout());
+
+        for slot in self.slots.iter_mut() {
+            let (row, _) = split_slot_id(&slot.id);
+            slot.size = sizes.get(&row).cloned().unwrap_or_default();
+        }
+    }
+
     pub fn view_for_id(&self, college_id: &u64) -> Self {
         let mut slots = Vec::new();
 
@@ -61,8 +297,22 @@ impl StudentStorage {
             }
         }
 
+        let mut waitlist = Vec::new();
+
+        for entry in self.waitlist.iter() {
+            if entry.college_id == *college_id {
+                waitlist.push(entry.clone());
+            } else {
+                waitlist.push(entry.censor());
+            }
+        }
+
         StudentStorage {
             slots,
+            waitlist,
+            orphaned: Vec::new(),
+            migration_reports: Vec::new(),
+            applied_renames: Vec::new(),
         }
     }
 
@@ -80,14 +330,206 @@ impl StudentStorage {
         Err(format!("No slot found with id {}", slot_id))
     }
 
-    pub fn checkout_slot_by_id(&mut self, college_id: &u64, slot_id: &String) -> bool {
+    pub fn get_slot(&self, slot_id: &String) -> Option<Slot> {
+        self.slots.iter().find(|x| x.get_id() == slot_id).cloned()
+    }
+
+    pub fn get_slots_for_id(&self, college_id: &u64) -> Vec<Slot> {
+        self.slots
+            .iter()
+            .filter(|slot| slot.is_owner(college_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Gets the waitlist entry that currently holds an offer on the slot, if any
+    pub fn get_offer_for_slot(&self, slot_id: &String) -> Option<&WaitlistEntry> {
+        self.waitlist
+            .iter()
+            .find(|entry| entry.offered_slot.as_ref() == Some(slot_id))
+    }
+
+    /// Checks out a slot for a user.
+    ///
+    /// Fails if the slot is occupied, is being held for someone on the waitlist,
+    /// or the user already has `MAX_SLOTS_PER_USER` slots.
+    pub fn checkout_slot_by_id(&mut self, college_id: &u64, slot_id: &String) -> Result<(), String> {
+        let pos = self.slots.iter().position(|slot| slot.get_id() == slot_id);
+
+        if pos.is_none() {
+            return Err(format!("No slot found with id {}", slot_id));
+        }
+
+        let pos = pos.unwrap();
+
+        if self.slots[pos].is_occupied() {
+            return Err(format!("Slot {} is already occupied", slot_id));
+        }
+
+        if self.get_slots_for_id(college_id).len() >= MAX_SLOTS_PER_USER {
+            return Err(format!("User already has {} slot(s)", MAX_SLOTS_PER_USER));
+        }
+
+        if let Some(offer) = self.get_offer_for_slot(slot_id) {
+            if offer.college_id != *college_id {
+                return Err(format!("Slot {} is being held for the waitlist", slot_id));
+            }
+        }
+
+        self.slots[pos].checkout(*college_id);
+
+        // Anyone who gets a slot no longer needs to wait for one
+        self.waitlist.retain(|entry| entry.college_id != *college_id);
+
+        Ok(())
+    }
+
+    /// Admin override: assigns a slot regardless of occupancy, offers or limits
+    pub fn override_slot_by_id(&mut self, college_id: &u64, slot_id: &String) -> Result<(), String> {
         for slot in self.slots.iter_mut() {
             if slot.get_id() == slot_id {
                 slot.checkout(*college_id);
-                return true;
+
+                self.waitlist.retain(|entry| {
+                    entry.college_id != *college_id && entry.offered_slot.as_ref() != Some(slot_id)
+                });
+
+                return Ok(());
+            }
+        }
+
+        Err(format!("No slot found with id {}", slot_id))
+    }
+
+    /// Admin transfer: moves an occupied slot to a new owner, keeping its expiry and renewals
+    pub fn transfer_slot_by_id(&mut self, slot_id: &String, new_college_id: &u64) -> Result<(), String> {
+        for slot in self.slots.iter_mut() {
+            if slot.get_id() == slot_id {
+                return slot.transfer(*new_college_id);
             }
         }
-        false
+
+        Err(format!("No slot found with id {}", slot_id))
+    }
+
+    pub fn join_waitlist(&mut self, user: &User, size: SlotSize) -> Result<(), String> {
+        if self.get_slots_for_id(&user.get_id()).len() >= MAX_SLOTS_PER_USER {
+            return Err(format!("User already has {} slot(s)", MAX_SLOTS_PER_USER));
+        }
+
+        if self.waitlist.iter().any(|entry| entry.college_id == user.get_id() && entry.size == size) {
+            return Err("User is already on the waitlist".to_string());
+        }
+
+        let available = self.slots.iter().any(|slot| {
+            slot.size == size
+                && !slot.is_occupied()
+                && self.get_offer_for_slot(slot.get_id()).is_none()
+        });
+
+        if available {
+            return Err(format!("{:?} slots are currently available", size));
+        }
+
+        self.waitlist.push(WaitlistEntry::new(user, size));
+
+        Ok(())
+    }
+
+    pub fn leave_waitlist(&mut self, college_id: &u64, size: SlotSize) -> Result<(), String> {
+        let pos = self
+            .waitlist
+            .iter()
+            .position(|entry| entry.college_id == *college_id && entry.size == size);
+
+        if let Some(pos) = pos {
+            self.waitlist.remove(pos);
+            Ok(())
+        } else {
+            Err("User is not on the waitlist".to_string())
+        }
+    }
+
+    /// Moves every slot and waitlist entry from one college ID to another, for fixing identities.
+    /// Returns how many were moved.
+    pub fn rekey_user(&mut self, from: &u64, to: &u64) -> usize {
+        let mut moved = 0;
+
+        for slot in self.slots.iter_mut() {
+            for details in slot.occupied_details.iter_mut().chain(slot.previous_details.iter_mut()) {
+                if &details.college_id == from {
+                    details.college_id = *to;
+                    moved += 1;
+                }
+            }
+        }
+
+        for entry in self.waitlist.iter_mut().filter(|x| &x.college_id == from) {
+            entry.college_id = *to;
+            moved += 1;
+        }
+
+        for orphan in self.orphaned.iter_mut().filter(|x| &x.occupied_details.college_id == from) {
+            orphan.occupied_details.college_id = *to;
+            moved += 1;
+        }
+
+        moved
+    }
+
+    pub fn get_waitlist_pos_for(&self, college_id: &u64, size: &SlotSize) -> Option<usize> {
+        self.waitlist
+            .iter()
+            .filter(|entry| &entry.size == size)
+            .position(|entry| entry.college_id == *college_id)
+    }
+
+    /// Drops waitlist entries whose offer expired unclaimed, then offers every
+    /// free, unheld slot to the next person waiting for that size.
+    ///
+    /// Returns the entries that received a new offer so they can be emailed.
+    pub fn process_waitlist(&mut self) -> Vec<WaitlistEntry> {
+        let now = now();
+
+        self.waitlist.retain(|entry| {
+            if entry.offer_has_expired(now) {
+                info!(
+                    "Waitlist offer of slot {:?} to {} expired",
+                    entry.offered_slot, entry.college_id
+                );
+                false
+            } else {
+                true
+            }
+        });
+
+        let mut offered = Vec::new();
+
+        // Someone waiting for both sizes only gets one slot held for them at a time
+        let mut holding_offer: Vec<u64> = self
+            .waitlist
+            .iter()
+            .filter(|entry| entry.offered_slot.is_some())
+            .map(|entry| entry.college_id)
+            .collect();
+
+        for slot in self.slots.iter() {
+            if slot.is_occupied() || self.get_offer_for_slot(slot.get_id()).is_some() {
+                continue;
+            }
+
+            let next = self.waitlist.iter_mut().find(|entry| {
+                entry.size == slot.size && entry.offered_slot.is_none() && !holding_offer.contains(&entry.college_id)
+            });
+
+            if let Some(entry) = next {
+                entry.offer(slot.get_id(), now);
+                holding_offer.push(entry.college_id);
+                offered.push(entry.clone());
+            }
+        }
+
+        offered
     }
 
     pub fn release_by_id(&mut self, college_id: &u64, slot_id: &String) {
@@ -116,16 +558,23 @@ impl Default for StudentStorage {
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 pub struct Slot {
     pub id: String,
+    #[serde(default)]
+    pub size: SlotSize,
     pub occupied: bool,
     pub occupied_details: Option<OccupiedDetails>,
+    /// Details of the last occupant, kept after release so left-behind items can be traced
+    #[serde(default)]
+    pub previous_details: Option<OccupiedDetails>,
 }
 
 impl Slot {
-    pub fn new(id: String) -> Self {
+    pub fn new(id: String, size: SlotSize) -> Self {
         Slot {
+            size,
             id,
             occupied: false,
             occupied_details: None,
+            previous_details: None,
         }
     }
 
@@ -140,14 +589,18 @@ impl Slot {
         if let Some(details) = details {
             Slot {
                 id: self.id.clone(),
+                size: self.size.clone(),
                 occupied: self.is_occupied(),
                 occupied_details: Some(details.censor()),
+                previous_details: None,
             }
         } else {
             Slot {
                 id: self.id.clone(),
+                size: self.size.clone(),
                 occupied: self.is_occupied(),
                 occupied_details: None,
+                previous_details: None,
             }
         }        
     }
@@ -186,18 +639,37 @@ impl Slot {
         }
     }
 
+    pub fn transfer(&mut self, college_id: u64) -> Result<(), String> {
+        if let Some(details) = &mut self.occupied_details {
+            details.college_id = college_id;
+            Ok(())
+        } else {
+            Err(format!("Slot {} is not occupied", self.id))
+        }
+    }
+
     pub fn release(&mut self, college_id: u64) {
         if let Some(details) = &self.occupied_details {
             if details.college_id == college_id {
                 self.occupied = false;
-                self.occupied_details = None;
+                self.previous_details = self.occupied_details.take();
             }
         }
     }
 
     pub fn server_release(&mut self) {
         self.occupied = false;
-        self.occupied_details = None;
+        if self.occupied_details.is_some() {
+            self.previous_details = self.occupied_details.take();
+        }
+    }
+
+    /// Gets the college ID of whoever is or was last in the slot
+    pub fn get_last_owner(&self) -> Option<u64> {
+        self.occupied_details
+            .as_ref()
+            .or(self.previous_details.as_ref())
+            .map(|details| details.college_id)
     }
 }
 
@@ -211,10 +683,7 @@ pub struct OccupiedDetails {
 
 impl OccupiedDetails {
     pub fn new(college_id: u64, length_of_checkout: u64) -> Self {
-        let timestamp_start = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
+        let timestamp_start = now();
         
         let timestamp_end = timestamp_start + length_of_checkout;
         
@@ -227,10 +696,7 @@ impl OccupiedDetails {
     }
 
     pub fn is_overdue(&self) -> bool {
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
+        let now = now();
         
         now > self.timestamp_end
     }
@@ -258,10 +724,7 @@ impl OccupiedDetails {
 
     pub fn renew(&mut self) -> Result<(), String> {
         // Extend checkout length by RENEW_LENGTH from now
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
+        let now = now();
 
         if self.renewals_left <= 0 {
             return Err(format!("No renewals left"));
@@ -277,3 +740,244 @@ impl OccupiedDetails {
         }
     }
 }
+
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct WaitlistEntry {
+    pub college_id: u64,
+    pub email: String,
+    pub size: SlotSize,
+    pub timestamp_joined: u64,
+    pub offered_slot: Option<String>,
+    pub timestamp_offer_expires: Option<u64>,
+}
+
+impl WaitlistEntry {
+    pub fn new(user: &User, size: SlotSize) -> Self {
+        WaitlistEntry {
+            college_id: user.get_id(),
+            email: user.get_email(),
+            size,
+            timestamp_joined: now(),
+            offered_slot: None,
+            timestamp_offer_expires: None,
+        }
+    }
+
+    pub fn offer(&mut self, slot_id: &String, now: u64) {
+        self.offered_slot = Some(slot_id.clone());
+        self.timestamp_offer_expires = Some(now + WAITLIST_CLAIM_PERIOD);
+    }
+
+    pub fn offer_has_expired(&self, now: u64) -> bool {
+        match self.timestamp_offer_expires {
+            Some(expires) => now > expires,
+            None => false,
+        }
+    }
+
+    pub fn censor(&self) -> Self {
+        WaitlistEntry {
+            college_id: 0,
+            email: String::new(),
+            size: self.size.clone(),
+            timestamp_joined: self.timestamp_joined,
+            offered_slot: None,
+            timestamp_offer_expires: None,
+        }
+    }
+
+    /// Email the user that a slot is being held for them until the claim deadline
+    pub async fn notify(&self) {
+        if let (Some(slot_id), Some(expires)) = (&self.offered_slot, self.timestamp_offer_expires) {
+            let deadline = chrono::NaiveDateTime::from_timestamp_opt(expires as i64, 0)
+                .map(|x| format!("{} UTC", x.format("%Y-%m-%d %H:%M")))
+                .unwrap_or_default();
+
+            let _ = send_individual_email(
+                self.email.clone(),
+                None,
+                "MAKE Student Storage Waitlist".to_string(),
+                EMAIL_TEMPLATES
+                    .lock()
+                    .await
+                    .get_student_storage_offer(slot_id, &deadline),
+            )
+            .await;
+        }
+    }
+}
+
+/// An assignment whose slot disappeared during a layout migration
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct OrphanedAssignment {
+    pub slot_id: String,
+    pub occupied_details: OccupiedDetails,
+    pub timestamp_orphaned: u64,
+}
+
+impl OrphanedAssignment {
+    pub fn new(slot_id: String, occupied_details: OccupiedDetails) -> Self {
+        OrphanedAssignment {
+            slot_id,
+            occupied_details,
+            timestamp_orphaned: now(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MigrationReport {
+    pub timestamp: u64,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub renamed: Vec<(String, String)>,
+    pub preserved: Vec<String>,
+    pub orphaned: Vec<String>,
+    // Old slots that were renamed onto the same new slot as another, and that slot
+    pub collisions: Vec<(String, String)>,
+}
+
+impl MigrationReport {
+    pub fn new() -> Self {
+        MigrationReport {
+            timestamp: now(),
+            ..Default::default()
+        }
+    }
+
+    pub fn log(&self) {
+        info!("Student storage migration report:");
+        info!("    {} slot(s) added: {:?}", self.added.len(), self.added);
+        info!("    {} slot(s) removed: {:?}", self.removed.len(), self.removed);
+        info!("    {} slot(s) renamed", self.renamed.len());
+        info!("    {} assignment(s) preserved", self.preserved.len());
+
+        if self.collisions.len() > 0 {
+            warn!("    {} slot(s) renamed onto another slot: {:?}", self.collisions.len(), self.collisions);
+        }
+
+        if self.orphaned.len() > 0 {
+            warn!(
+                "    {} assignment(s) orphaned, needs admin review: {:?}",
+                self.orphaned.len(),
+                self.orphaned
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use toml::Value;
+
+    use super::{SlotSize, StudentStorage, WaitlistEntry};
+    use crate::people::quizzes::Response;
+    use crate::people::users::User;
+
+    fn layout(toml_text: &str) -> Value {
+        toml::from_str(toml_text).unwrap()
+    }
+
+    fn user(college_id: u64) -> User {
+        User::from_response(&Response {
+            college_id,
+            ..Default::default()
+        })
+    }
+
+    fn occupied(storage: &mut StudentStorage, slot_id: &str, college_id: u64) {
+        storage.checkout_slot_by_id(&college_id, &slot_id.to_string()).unwrap();
+    }
+
+    #[test]
+    fn migration_keeps_assignments() {
+        let mut storage = StudentStorage::from_layout(&layout("[sizings]\nA = 2\nB = 2"));
+        occupied(&mut storage, "A1", 1);
+        occupied(&mut storage, "B2", 2);
+
+        let report = storage.migrate_to(StudentStorage::from_layout(&layout("[sizings]\nA = 2")), HashMap::new());
+
+        assert_eq!(report.preserved, vec!["A1".to_string()]);
+        assert_eq!(report.orphaned, vec!["B2".to_string()]);
+        assert_eq!(storage.orphaned[0].occupied_details.college_id, 2);
+        assert!(storage.get_slot(&"A1".to_string()).unwrap().is_owner(&1));
+    }
+
+    #[test]
+    fn migration_orphans_renames_onto_occupied_slots() {
+        let mut storage = StudentStorage::from_layout(&layout("[sizings]\nA = 1\nB = 1\nC = 1"));
+        occupied(&mut storage, "A1", 1);
+        occupied(&mut storage, "B1", 2);
+        occupied(&mut storage, "C1", 3);
+
+        // A is renamed onto B, which still exists, and C is renamed onto B too
+        let renames = HashMap::from([("A".to_string(), "B".to_string()), ("C".to_string(), "B".to_string())]);
+        let report = storage.migrate_to(StudentStorage::from_layout(&layout("[sizings]\nB = 1")), renames);
+
+        let mut orphaned: Vec<u64> = storage.orphaned.iter().map(|x| x.occupied_details.college_id).collect();
+        orphaned.sort();
+
+        assert_eq!(report.collisions.len(), 2);
+        assert_eq!(orphaned.len(), 2);
+        assert_eq!(report.preserved, vec!["B1".to_string()]);
+
+        let kept = storage.get_slot(&"B1".to_string()).unwrap().get_details().unwrap().college_id;
+        assert!(!orphaned.contains(&kept));
+    }
+
+    #[test]
+    fn renames_are_only_applied_once() {
+        let renames = || HashMap::from([("A".to_string(), "C".to_string())]);
+
+        let mut storage = StudentStorage::from_layout(&layout("[sizings]\nA = 1"));
+        occupied(&mut storage, "A1", 1);
+
+        storage.migrate_to(StudentStorage::from_layout(&layout("[sizings]\nC = 1")), renames());
+        assert!(storage.get_slot(&"C1".to_string()).unwrap().is_owner(&1));
+
+        // Row A comes back and is used, then the layout changes again with the rename still in the config
+        storage.migrate_to(StudentStorage::from_layout(&layout("[sizings]\nA = 1\nC = 1")), renames());
+        occupied(&mut storage, "A1", 2);
+
+        let report = storage.migrate_to(StudentStorage::from_layout(&layout("[sizings]\nA = 1\nC = 1\nD = 1")), renames());
+
+        assert!(report.renamed.is_empty());
+        assert!(storage.orphaned.is_empty());
+        assert!(storage.get_slot(&"A1".to_string()).unwrap().is_owner(&2));
+        assert!(storage.get_slot(&"C1".to_string()).unwrap().is_owner(&1));
+    }
+
+    #[test]
+    fn sizes_come_from_config() {
+        let storage = StudentStorage::from_layout(&layout(
+            "[sizings]\nA = 1\nJ = 1\nK = 1\n\n[sizes]\nJ = \"Large\"\nK = \"small\"",
+        ));
+
+        let sizes: Vec<(String, SlotSize)> = storage.slots.iter().map(|x| (x.id.clone(), x.size.clone())).collect();
+
+        assert!(sizes.contains(&("A1".to_string(), SlotSize::Small)));
+        assert!(sizes.contains(&("J1".to_string(), SlotSize::Large)));
+        assert!(sizes.contains(&("K1".to_string(), SlotSize::Small)));
+    }
+
+    #[test]
+    fn waitlist_offers_one_slot_per_user() {
+        let mut storage = StudentStorage::from_layout(&layout(
+            "[sizings]\nA = 1\nE = 1\n\n[sizes]\nA = \"Large\"",
+        ));
+
+        storage.waitlist.push(WaitlistEntry::new(&user(1), SlotSize::Large));
+        storage.waitlist.push(WaitlistEntry::new(&user(1), SlotSize::Small));
+        storage.waitlist.push(WaitlistEntry::new(&user(2), SlotSize::Small));
+
+        let offered = storage.process_waitlist();
+
+        assert_eq!(offered.len(), 2);
+        assert_eq!(offered.iter().filter(|x| x.college_id == 1).count(), 1);
+        assert!(offered.iter().any(|x| x.college_id == 2 && x.size == SlotSize::Small));
+    }
+}
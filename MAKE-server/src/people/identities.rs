@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::people::quizzes::Response;
+use crate::people::users::{AuthLevel, User, Users};
+use crate::Data;
+
+/// Corrections to user identities that admins have made, so they survive quizzes being re-imported.
+///
+/// `id_aliases` maps a mistyped or old college ID to the real one, and `email_aliases` maps an email
+/// to the college ID it belongs to. `separate_ids` are IDs split off from someone else that shouldn't
+/// be merged with other IDs that share their email.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Identities {
+    pub id_aliases: HashMap<u64, u64>,
+    pub email_aliases: HashMap<String, u64>,
+    pub separate_ids: Vec<u64>,
+}
+
+/// Body of a request to split someone out of a merged user
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SplitRequest {
+    pub college_id: u64,
+    pub name: String,
+    pub college_email: String,
+}
+
+/// The user after a merge, split or re-key, and how many of each record were moved to them
+#[derive(Default, Debug, Serialize)]
+pub struct IdentityChange {
+    pub user: User,
+    pub checkouts: usize,
+    pub storage: usize,
+    pub queue_entries: usize,
+    pub print_logs: usize,
+    pub sign_offs: usize,
+    pub quiz_attempts: usize,
+    pub billing_records: usize,
+    pub incidents: usize,
+    pub lost_items: usize,
+}
+
+/// A user that might be a duplicate or have a mistyped ID, and why
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct SuspiciousIdentity {
+    pub college_id: u64,
+    pub name: String,
+    pub college_email: String,
+    pub reasons: Vec<String>,
+}
+
+impl Identities {
+    /// Follows ID aliases to the real college ID
+    pub fn resolve_id(&self, college_id: u64) -> u64 {
+        let mut id = college_id;
+
+        // Aliases can chain after several merges, but never loop
+        for _ in 0..=self.id_aliases.len() {
+            match self.id_aliases.get(&id) {
+                Some(next) => id = *next,
+                None => break,
+            }
+        }
+
+        id
+    }
+
+    /// A quiz response with the college ID corrected. An email alias wins over the ID that was typed.
+    pub fn resolve_response(&self, response: &Response) -> Response {
+        let mut response = response.clone();
+
+        response.college_id = match self.email_aliases.get(&response.college_email.to_lowercase()) {
+            Some(id) => self.resolve_id(*id),
+            None => self.resolve_id(response.college_id),
+        };
+
+        response
+    }
+
+    pub fn is_separate(&self, college_id: &u64) -> bool {
+        self.separate_ids.contains(college_id)
+    }
+
+    pub fn add_email_alias(&mut self, email: &str, college_id: u64, users: &Users) -> Result<(), String> {
+        let email = email.trim().to_lowercase();
+
+        if email.is_empty() {
+            return Err("Email can't be empty".to_string());
+        }
+
+        if !users.exists(&college_id) {
+            return Err(format!("No user found with ID {}", college_id));
+        }
+
+        info!("Aliasing {} to {}", email, college_id);
+
+        self.email_aliases.insert(email, college_id);
+
+        Ok(())
+    }
+
+    /// Points `from` and everything that pointed to it at `to`
+    fn set_id_alias(&mut self, from: u64, to: u64) {
+        // Undo an earlier alias the other way instead of making a loop
+        if self.id_aliases.get(&to) == Some(&from) {
+            self.id_aliases.remove(&to);
+        }
+
+        self.id_aliases.insert(from, to);
+
+        for target in self.id_aliases.values_mut().chain(self.email_aliases.values_mut()) {
+            if *target == from {
+                *target = to;
+            }
+        }
+
+        self.separate_ids.retain(|x| x != &from);
+    }
+
+    /// Users that might be duplicates or have mistyped IDs
+    pub fn get_suspicious(&self, users: &Users) -> Vec<SuspiciousIdentity> {
+        let all_users = users.get_users();
+        let mut suspicious: Vec<SuspiciousIdentity> = Vec::new();
+
+        for user in all_users.iter() {
+            let id = user.get_id();
+            let mut reasons = Vec::new();
+
+            if id >= 100_000_000 || id <= 10_000_000 {
+                reasons.push("ID is not 8 digits".to_string());
+            }
+
+            if let Some(real_id) = self.id_aliases.get(&id) {
+                reasons.push(format!("ID is an alias of {}", real_id));
+            }
+
+            if user.get_email().trim().is_empty() {
+                reasons.push("No email".to_string());
+            }
+
+            if !self.is_separate(&id) {
+                let same_email = get_matching_ids(&all_users, user, |x| {
+                    !x.get_email().trim().is_empty() && x.get_email().eq_ignore_ascii_case(&user.get_email())
+                });
+
+                if !same_email.is_empty() {
+                    reasons.push(format!("Same email as {}", same_email.join(", ")));
+                }
+
+                let same_name = get_matching_ids(&all_users, user, |x| {
+                    !x.get_name().trim().is_empty() && x.get_name().trim().eq_ignore_ascii_case(user.get_name().trim())
+                });
+
+                if !same_name.is_empty() {
+                    reasons.push(format!("Same name as {}", same_name.join(", ")));
+                }
+            }
+
+            if !reasons.is_empty() {
+                suspicious.push(SuspiciousIdentity {
+                    college_id: id,
+                    name: user.get_name(),
+                    college_email: user.get_email(),
+                    reasons,
+                });
+            }
+        }
+
+        suspicious.sort_by_key(|x| x.college_id);
+
+        suspicious
+    }
+}
+
+/// Merges the user at `from` into the user at `into`, moving their quizzes and records.
+/// `from` doesn't have to be a user yet, so an ID that's known to be mistyped can be aliased ahead of time.
+pub fn merge_users(data: &mut Data, from: u64, into: u64) -> Result<IdentityChange, String> {
+    if from == into {
+        return Err("Can't merge a user into themselves".to_string());
+    }
+
+    let mut user = data
+        .users
+        .get_user_by_id(&into)
+        .ok_or(format!("No user found with ID {}", into))?;
+
+    if let Some(other) = data.users.remove_user(&from) {
+        user.update_soft_from(&other);
+
+        // Bans carry over, otherwise keep the higher access
+        if other.get_auth_level() == AuthLevel::Banned
+            || (user.get_auth_level() != AuthLevel::Banned && other.get_auth_level() > user.get_auth_level())
+        {
+            user.set_auth_level(other.get_auth_level());
+        }
+
+        if !other.get_email().is_empty() && !other.get_email().eq_ignore_ascii_case(&user.get_email()) {
+            data.identities
+                .email_aliases
+                .insert(other.get_email().to_lowercase(), into);
+        }
+    }
+
+    info!("Merging {} into {}", from, into);
+
+    data.users.add_set_user(user.clone());
+    data.identities.set_id_alias(from, into);
+
+    Ok(move_records(data, from, user))
+}
+
+/// Moves a user to a new college ID, eg when their ID was mistyped on every quiz
+pub fn rekey_user(data: &mut Data, from: u64, to: u64) -> Result<IdentityChange, String> {
+    if to == 0 {
+        return Err("New ID can't be 0".to_string());
+    }
+
+    if data.users.exists(&to) {
+        return Err(format!("{} is already a user, merge them instead", to));
+    }
+
+    let mut user = data
+        .users
+        .remove_user(&from)
+        .ok_or(format!("No user found with ID {}", from))?;
+
+    info!("Moving {} to {}", from, to);
+
+    user.set_id(to);
+
+    data.users.add_set_user(user.clone());
+    data.identities.set_id_alias(from, to);
+
+    Ok(move_records(data, from, user))
+}
+
+/// Separates an ID or email that was wrongly merged into someone else, creating a user for it if needed.
+/// Quizzes and records stay with the user they were merged into, since there's no telling whose they were.
+pub fn split_user(data: &mut Data, request: SplitRequest) -> Result<User, String> {
+    if request.college_id == 0 {
+        return Err("ID can't be 0".to_string());
+    }
+
+    let email = request.college_email.trim().to_lowercase();
+
+    data.identities.id_aliases.remove(&request.college_id);
+
+    if !email.is_empty() {
+        data.identities.email_aliases.remove(&email);
+    }
+
+    if !data.identities.is_separate(&request.college_id) {
+        data.identities.separate_ids.push(request.college_id);
+    }
+
+    info!("Splitting {} off as its own user", request.college_id);
+
+    let user = match data.users.get_user_by_id(&request.college_id) {
+        Some(user) => user,
+        None => {
+            let user = User::from_response(&Response {
+                name: request.name.trim().to_string(),
+                college_id: request.college_id,
+                college_email: email,
+                ..Default::default()
+            });
+
+            data.users.add_set_user(user.clone());
+
+            user
+        }
+    };
+
+    Ok(user)
+}
+
+fn move_records(data: &mut Data, from: u64, user: User) -> IdentityChange {
+    let to = user.get_id();
+
+    data.roles.rekey_user(&from, &to);
+    data.suspensions.rekey_user(&from, &to);
+
+    IdentityChange {
+        checkouts: data.checkout_log.rekey_user(&from, &to),
+        storage: data.student_storage.rekey_user(&from, &to),
+        queue_entries: data.printers.rekey_user(&from, &to),
+        print_logs: data.printers.rekey_print_log(&from, &to),
+        sign_offs: data.sign_offs.rekey_user(&from, &to),
+        quiz_attempts: data.quiz_banks.rekey_user(&from, &to),
+        billing_records: data.billing.rekey_user(&from, &to),
+        incidents: data.incidents.rekey_user(&from, &to),
+        lost_items: data.lost_and_found.rekey_user(&from, &to),
+        user,
+    }
+}
+
+fn get_matching_ids(users: &Vec<User>, user: &User, matches: impl Fn(&User) -> bool) -> Vec<String> {
+    users
+        .iter()
+        .filter(|x| x.get_id() != user.get_id() && matches(x))
+        .map(|x| x.get_id().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_users;
+    use crate::machines::printers::PrintLogEntry;
+    use crate::management::billing::BillingCredit;
+    use crate::management::incidents::Incident;
+    use crate::management::lost_and_found::LostItem;
+    use crate::people::quiz_bank::QuizAttempt;
+    use crate::people::quizzes::Response;
+    use crate::people::users::User;
+    use crate::Data;
+
+    fn user(college_id: u64, email: &str) -> User {
+        User::from_response(&Response {
+            name: "Test".to_string(),
+            college_id,
+            college_email: email.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn merge_moves_every_record() {
+        let mut data = Data::default();
+        data.users.add_set_user(user(12345678, "test@example.edu"));
+        data.users.add_set_user(user(1234567, "typo@example.edu"));
+
+        data.quiz_banks.attempts.push(QuizAttempt {
+            college_id: 1234567,
+            ..Default::default()
+        });
+        data.billing.credits.push(BillingCredit {
+            college_id: 1234567,
+            amount_cents: 500,
+            ..Default::default()
+        });
+        data.incidents.reports.push(Incident {
+            college_id: Some(1234567),
+            strike: true,
+            ..Default::default()
+        });
+        data.lost_and_found.items.push(LostItem {
+            claimed_by: Some(1234567),
+            ..Default::default()
+        });
+        data.printers.add_log(PrintLogEntry {
+            college_id: 1234567,
+            ..Default::default()
+        });
+
+        let change = merge_users(&mut data, 1234567, 12345678).unwrap();
+
+        assert_eq!(change.quiz_attempts, 1);
+        assert_eq!(change.billing_records, 1);
+        assert_eq!(change.incidents, 1);
+        assert_eq!(change.print_logs, 1);
+        assert!(!data.users.exists(&1234567));
+        assert_eq!(data.identities.resolve_id(1234567), 12345678);
+        assert_eq!(data.identities.email_aliases.get("typo@example.edu"), Some(&12345678));
+
+        assert_eq!(data.quiz_banks.get_attempts_for_id(&12345678).len(), 1);
+        assert_eq!(data.billing.get_balance(12345678), -500);
+        assert_eq!(data.incidents.get_strikes(&12345678), 1);
+        assert_eq!(data.lost_and_found.items[0].claimed_by, Some(12345678));
+        assert_eq!(data.printers.get_print_log_for_id(&12345678).len(), 1);
+    }
+}
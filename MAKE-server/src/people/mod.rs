@@ -1,5 +1,12 @@
+pub mod identities;
 pub mod permissions;
+pub mod privacy;
+pub mod quiz_bank;
+pub mod quiz_registry;
 pub mod quizzes;
+pub mod roster;
 pub mod schedule;
+pub mod sign_offs;
+pub mod suspensions;
 pub mod usage;
 pub mod users;
\ No newline at end of file
@@ -1,6 +1,15 @@
+use std::collections::{BTreeSet, HashMap};
+
+use log::info;
+use rand::Rng;
+use uuid::Uuid;
+
 use crate::*;
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+/// The role with every permission, which can't be changed
+pub const ADMIN_ROLE: &str = "admin";
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum SwipeGroup {
     #[default] Makerspace,
     Studio,
@@ -15,4 +24,574 @@ pub struct AccessRule {
     pub auth_level: AuthLevel,
     pub timestamp_start: u64,
     pub timestamp_end: u64,
-}
\ No newline at end of file
+}
+
+/// Something a user or API key can be allowed to do, named like `checkout.create`.
+/// Roles bundle permissions together, and routes check for the one they need.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Permission {
+    #[serde(rename = "checkout.view")]
+    CheckoutView,
+    #[serde(rename = "checkout.create")]
+    CheckoutCreate,
+    #[serde(rename = "checkout.extend")]
+    CheckoutExtend,
+    #[serde(rename = "checkout.check_in")]
+    CheckoutCheckIn,
+    #[serde(rename = "users.view")]
+    UsersView,
+    #[serde(rename = "users.add")]
+    UsersAdd,
+    #[serde(rename = "users.set_auth_level")]
+    UsersSetAuthLevel,
+    #[serde(rename = "users.ban")]
+    UsersBan,
+    #[serde(rename = "users.set_quizzes")]
+    UsersSetQuizzes,
+    #[serde(rename = "users.manage_identities")]
+    UsersManageIdentities,
+    #[serde(rename = "users.import_roster")]
+    UsersImportRoster,
+    #[serde(rename = "storage.view")]
+    StorageView,
+    #[serde(rename = "storage.assign")]
+    StorageAssign,
+    #[serde(rename = "storage.manage")]
+    StorageManage,
+    #[serde(rename = "lost_and_found.manage")]
+    LostAndFoundManage,
+    #[serde(rename = "inventory.view")]
+    InventoryView,
+    #[serde(rename = "inventory.edit")]
+    InventoryEdit,
+    #[serde(rename = "inventory.audit")]
+    InventoryAudit,
+    #[serde(rename = "inventory.restock")]
+    InventoryRestock,
+    #[serde(rename = "inventory.manage")]
+    InventoryManage,
+    #[serde(rename = "labels.print")]
+    LabelsPrint,
+    #[serde(rename = "usage.log")]
+    UsageLog,
+    #[serde(rename = "billing.view")]
+    BillingView,
+    #[serde(rename = "billing.log_usage")]
+    BillingLogUsage,
+    #[serde(rename = "billing.manage")]
+    BillingManage,
+    #[serde(rename = "budget.manage")]
+    BudgetManage,
+    #[serde(rename = "printers.view")]
+    PrintersView,
+    #[serde(rename = "printers.manage")]
+    PrintersManage,
+    #[serde(rename = "maintenance.view")]
+    MaintenanceView,
+    #[serde(rename = "maintenance.log")]
+    MaintenanceLog,
+    #[serde(rename = "maintenance.plan")]
+    MaintenancePlan,
+    #[serde(rename = "machines.interlock")]
+    MachinesInterlock,
+    #[serde(rename = "incidents.report")]
+    IncidentsReport,
+    #[serde(rename = "incidents.manage")]
+    IncidentsManage,
+    #[serde(rename = "quizzes.view_attempts")]
+    QuizzesViewAttempts,
+    #[serde(rename = "quizzes.manage")]
+    QuizzesManage,
+    #[serde(rename = "sign_offs.view")]
+    SignOffsView,
+    #[serde(rename = "sign_offs.add")]
+    SignOffsAdd,
+    #[serde(rename = "sign_offs.manage")]
+    SignOffsManage,
+    #[serde(rename = "privacy.request_deletion")]
+    PrivacyRequestDeletion,
+    #[serde(rename = "privacy.manage")]
+    PrivacyManage,
+    #[serde(rename = "audit.view")]
+    AuditView,
+    #[serde(rename = "schedule.view")]
+    ScheduleView,
+    #[serde(rename = "roles.manage")]
+    RolesManage,
+}
+
+impl Permission {
+    pub fn all() -> Vec<Permission> {
+        vec![
+            Permission::CheckoutView,
+            Permission::CheckoutCreate,
+            Permission::CheckoutExtend,
+            Permission::CheckoutCheckIn,
+            Permission::UsersView,
+            Permission::UsersAdd,
+            Permission::UsersSetAuthLevel,
+            Permission::UsersBan,
+            Permission::UsersSetQuizzes,
+            Permission::UsersManageIdentities,
+            Permission::UsersImportRoster,
+            Permission::StorageView,
+            Permission::StorageAssign,
+            Permission::StorageManage,
+            Permission::LostAndFoundManage,
+            Permission::InventoryView,
+            Permission::InventoryEdit,
+            Permission::InventoryAudit,
+            Permission::InventoryRestock,
+            Permission::InventoryManage,
+            Permission::LabelsPrint,
+            Permission::UsageLog,
+            Permission::BillingView,
+            Permission::BillingLogUsage,
+            Permission::BillingManage,
+            Permission::BudgetManage,
+            Permission::PrintersView,
+            Permission::PrintersManage,
+            Permission::MaintenanceView,
+            Permission::MaintenanceLog,
+            Permission::MaintenancePlan,
+            Permission::MachinesInterlock,
+            Permission::IncidentsReport,
+            Permission::IncidentsManage,
+            Permission::QuizzesViewAttempts,
+            Permission::QuizzesManage,
+            Permission::SignOffsView,
+            Permission::SignOffsAdd,
+            Permission::SignOffsManage,
+            Permission::PrivacyRequestDeletion,
+            Permission::PrivacyManage,
+            Permission::AuditView,
+            Permission::ScheduleView,
+            Permission::RolesManage,
+        ]
+    }
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = serde_json::to_value(self).unwrap_or_default();
+        write!(f, "{}", name.as_str().unwrap_or_default())
+    }
+}
+
+/// A named bundle of permissions, eg "steward" or "storage_coordinator"
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Role {
+    pub id: String,
+    pub display_name: String,
+    pub permissions: BTreeSet<Permission>,
+}
+
+impl Role {
+    fn new(id: &str, display_name: &str, permissions: Vec<Permission>) -> Self {
+        Role {
+            id: id.to_string(),
+            display_name: display_name.to_string(),
+            permissions: permissions.into_iter().collect(),
+        }
+    }
+}
+
+/// Everyone's permissions, from the role their auth level gives them, any roles assigned to
+/// them on top of that, and the role of the API key the request came in with.
+///
+/// `roles` only holds the roles admins have added or changed, the built-in ones are in
+/// `default_roles`. The admin role can't be changed so there's always a way back in.
+/// `tokens` are how users prove who they are, only their hashes are kept.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Roles {
+    roles: Vec<Role>,
+    assignments: HashMap<u64, Vec<String>>,
+    tokens: Vec<UserToken>,
+}
+
+/// A token an admin issued to someone, sent in `X-User-Token` to act as them
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct UserToken {
+    pub id: String,
+    pub college_id: u64,
+    // SHA-256 of the token, hex encoded
+    pub hash: String,
+    pub timestamp_issued: u64,
+}
+
+/// A newly issued token. This is the only time the token itself is shown.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct IssuedToken {
+    pub id: String,
+    pub college_id: u64,
+    pub token: String,
+}
+
+/// The roles every server starts with. The key roles match what each API key could do
+/// before there were permissions, and stewards get everything the kiosks can do.
+pub fn default_roles() -> Vec<Role> {
+    let checkout = vec![
+        Permission::CheckoutView,
+        Permission::CheckoutCreate,
+        Permission::CheckoutExtend,
+        Permission::CheckoutCheckIn,
+        Permission::UsersView,
+        Permission::UsersAdd,
+        Permission::LostAndFoundManage,
+        Permission::InventoryView,
+        Permission::InventoryEdit,
+        Permission::InventoryAudit,
+        Permission::LabelsPrint,
+        Permission::BillingView,
+        Permission::BillingLogUsage,
+        Permission::PrintersView,
+        Permission::MaintenanceView,
+        Permission::MaintenanceLog,
+        Permission::MachinesInterlock,
+        Permission::IncidentsReport,
+        Permission::QuizzesViewAttempts,
+        Permission::SignOffsView,
+        Permission::PrivacyRequestDeletion,
+    ];
+
+    let student_storage = vec![Permission::StorageView, Permission::StorageAssign];
+    let printers = vec![Permission::PrintersView, Permission::PrintersManage, Permission::BillingLogUsage];
+
+    // Signing someone off needs a steward signed in with their token, the kiosk key isn't enough
+    let steward = [
+        checkout.clone(),
+        student_storage.clone(),
+        printers.clone(),
+        vec![Permission::SignOffsAdd],
+    ]
+    .concat();
+
+    vec![
+        Role::new(ADMIN_ROLE, "Admin", Permission::all()),
+        Role::new("steward", "Steward", steward),
+        Role::new("user", "User", vec![Permission::PrivacyRequestDeletion]),
+        Role::new("checkout_key", "Checkout API key", checkout),
+        Role::new("student_storage_key", "Student storage API key", student_storage),
+        Role::new("printers_key", "Printers API key", printers),
+    ]
+}
+
+impl Roles {
+    /// Every role, with the built-in ones first
+    pub fn get_roles(&self) -> Vec<Role> {
+        let mut roles: Vec<Role> = default_roles()
+            .into_iter()
+            .map(|x| self.roles.iter().find(|r| r.id == x.id).cloned().unwrap_or(x))
+            .collect();
+
+        for role in self.roles.iter() {
+            if !roles.iter().any(|x| x.id == role.id) {
+                roles.push(role.clone());
+            }
+        }
+
+        roles
+    }
+
+    pub fn get_role(&self, id: &str) -> Option<Role> {
+        self.get_roles().into_iter().find(|x| x.id == id)
+    }
+
+    /// Adds or replaces a role. Changing a built-in role overrides its defaults.
+    pub fn set_role(&mut self, mut role: Role) -> Result<Role, String> {
+        role.id = role.id.trim().to_lowercase();
+
+        if role.id.is_empty() {
+            return Err("Role must have an ID".to_string());
+        }
+
+        if role.id == ADMIN_ROLE {
+            return Err("The admin role can't be changed".to_string());
+        }
+
+        if role.display_name.trim().is_empty() {
+            role.display_name = role.id.clone();
+        }
+
+        let pos = self.roles.iter().position(|x| x.id == role.id);
+
+        if let Some(pos) = pos {
+            self.roles[pos] = role.clone();
+        } else {
+            info!("Adding role {}", role.id);
+            self.roles.push(role.clone());
+        }
+
+        Ok(role)
+    }
+
+    /// Removes a role. Built-in roles go back to their defaults instead.
+    pub fn delete_role(&mut self, id: &str) -> Result<Role, String> {
+        let is_default = default_roles().iter().any(|x| x.id == id);
+
+        if !is_default && self.assignments.values().any(|x| x.iter().any(|r| r == id)) {
+            return Err(format!("{} is still assigned to users, unassign it first", id));
+        }
+
+        let pos = self.roles.iter().position(|x| x.id == id);
+
+        if pos.is_none() && is_default {
+            return Err(format!("{} already has its default permissions", id));
+        }
+
+        let pos = pos.ok_or(format!("No role found with id {}", id))?;
+
+        info!("Removing role {}", id);
+
+        Ok(self.roles.remove(pos))
+    }
+
+    pub fn get_assigned_roles(&self, college_id: &u64) -> Vec<String> {
+        self.assignments.get(college_id).cloned().unwrap_or_default()
+    }
+
+    pub fn assign_role(&mut self, college_id: u64, role: &str, users: &Users) -> Result<Vec<String>, String> {
+        if !users.exists(&college_id) {
+            return Err(format!("No user found with ID {}", college_id));
+        }
+
+        if self.get_role(role).is_none() {
+            return Err(format!("No role found with id {}", role));
+        }
+
+        let assigned = self.assignments.entry(college_id).or_default();
+
+        if assigned.iter().any(|x| x == role) {
+            return Err(format!("{} already has the {} role", college_id, role));
+        }
+
+        assigned.push(role.to_string());
+
+        Ok(assigned.clone())
+    }
+
+    pub fn unassign_role(&mut self, college_id: u64, role: &str) -> Result<Vec<String>, String> {
+        let assigned = self.assignments.entry(college_id).or_default();
+
+        let pos = assigned
+            .iter()
+            .position(|x| x == role)
+            .ok_or(format!("{} doesn't have the {} role", college_id, role))?;
+
+        assigned.remove(pos);
+        let remaining = assigned.clone();
+
+        if remaining.is_empty() {
+            self.assignments.remove(&college_id);
+        }
+
+        Ok(remaining)
+    }
+
+    /// Issues a new token for a user. Their earlier tokens keep working until they're revoked.
+    pub fn issue_token(&mut self, college_id: u64, users: &Users) -> Result<IssuedToken, String> {
+        if !users.exists(&college_id) {
+            return Err(format!("No user found with ID {}", college_id));
+        }
+
+        let bytes: [u8; 32] = rand::thread_rng().gen();
+        let token = to_hex(&bytes);
+
+        let issued = IssuedToken {
+            id: Uuid::new_v4().to_string(),
+            college_id,
+            token: token.clone(),
+        };
+
+        info!("Issuing a user token for {}", college_id);
+
+        self.tokens.push(UserToken {
+            id: issued.id.clone(),
+            college_id,
+            hash: hash_token(&token),
+            timestamp_issued: now(),
+        });
+
+        Ok(issued)
+    }
+
+    /// Revokes every token a user has, returning how many there were
+    pub fn revoke_tokens(&mut self, college_id: &u64) -> usize {
+        let before = self.tokens.len();
+        self.tokens.retain(|x| &x.college_id != college_id);
+
+        before - self.tokens.len()
+    }
+
+    /// Who a token belongs to, if it's one that was issued and not revoked
+    pub fn get_token_user(&self, token: &str) -> Option<u64> {
+        if token.is_empty() {
+            return None;
+        }
+
+        let hash = hash_token(token);
+
+        self.tokens.iter().find(|x| x.hash == hash).map(|x| x.college_id)
+    }
+
+    /// Moves someone's roles and tokens to a new college ID, for merges and re-keys
+    pub fn rekey_user(&mut self, from: &u64, to: &u64) {
+        if let Some(roles) = self.assignments.remove(from) {
+            let assigned = self.assignments.entry(*to).or_default();
+
+            for role in roles {
+                if !assigned.contains(&role) {
+                    assigned.push(role);
+                }
+            }
+        }
+
+        for token in self.tokens.iter_mut().filter(|x| &x.college_id == from) {
+            token.college_id = *to;
+        }
+    }
+
+    pub fn remove_user(&mut self, college_id: &u64) {
+        self.assignments.remove(college_id);
+        self.revoke_tokens(college_id);
+    }
+
+    /// The role each auth level gets by default. Banned users get nothing, not even assigned roles.
+    pub fn get_level_role(level: &AuthLevel) -> Option<&'static str> {
+        match level {
+            AuthLevel::Banned => None,
+            AuthLevel::User => Some("user"),
+            AuthLevel::Steward => Some("steward"),
+            AuthLevel::Admin | AuthLevel::Faculty | AuthLevel::System => Some(ADMIN_ROLE),
+        }
+    }
+
+    pub fn get_user_permissions(&self, user: &User) -> BTreeSet<Permission> {
+        let level_role = Roles::get_level_role(&user.get_auth_level());
+
+        if level_role.is_none() {
+            return BTreeSet::new();
+        }
+
+        let mut role_ids = self.get_assigned_roles(&user.get_id());
+        role_ids.push(level_role.unwrap().to_string());
+
+        role_ids
+            .iter()
+            .filter_map(|x| self.get_role(x))
+            .flat_map(|x| x.permissions)
+            .collect()
+    }
+
+    /// Permissions of an API key, by the name `ApiKeys::get_key_name` gives it
+    pub fn get_key_permissions(&self, key_name: &str) -> BTreeSet<Permission> {
+        let role = match key_name {
+            "admin" => ADMIN_ROLE.to_string(),
+            "none" => return BTreeSet::new(),
+            name => format!("{}_key", name),
+        };
+
+        self.get_role(&role).map(|x| x.permissions).unwrap_or_default()
+    }
+
+    /// The request needs a valid API key, and then either the key's role or the roles of the
+    /// user signed in with `X-User-Token` have to allow it. A claimed `X-Acting-User` adds nothing.
+    pub fn actor_has_permission(&self, actor: &Actor, users: &Users, permission: &Permission) -> bool {
+        if actor.key == "none" {
+            return false;
+        }
+
+        if self.get_key_permissions(&actor.key).contains(permission) {
+            return true;
+        }
+
+        actor
+            .verified_id()
+            .and_then(|x| users.get_user_by_id(&x))
+            .map_or(false, |x| self.get_user_permissions(&x).contains(permission))
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    to_hex(&openssl::sha::sha256(token.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+/// Whether a request is allowed to do something, eg
+/// `if has_permission(&req, &api_key, Permission::CheckoutCreate).await { ... }`
+pub async fn has_permission(req: &HttpRequest, api_key: &str, permission: Permission) -> bool {
+    let actor = Actor::from_request(req, api_key).await;
+    let data = MEMORY_DATABASE.lock().await;
+
+    data.roles.actor_has_permission(&actor, &data.users, &permission)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Permission, Roles};
+    use crate::management::audit_log::Actor;
+    use crate::people::quizzes::Response;
+    use crate::people::users::{AuthLevel, User, Users};
+
+    fn users() -> Users {
+        let mut users = Users::default();
+        let mut steward = User::from_response(&Response {
+            name: "Steward".to_string(),
+            college_id: 12345678,
+            college_email: "steward@example.edu".to_string(),
+            ..Default::default()
+        });
+        steward.set_auth_level(AuthLevel::Steward);
+        users.add_set_user(steward);
+
+        users
+    }
+
+    fn actor(key: &str, college_id: Option<u64>, verified: bool) -> Actor {
+        Actor {
+            key: key.to_string(),
+            college_id,
+            verified,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn signed_in_users_add_their_roles_to_the_key() {
+        let roles = Roles::default();
+        let users = users();
+        let sign_off = Permission::SignOffsAdd;
+
+        assert!(roles.actor_has_permission(&actor("checkout", None, false), &users, &Permission::CheckoutCreate));
+        assert!(!roles.actor_has_permission(&actor("checkout", None, false), &users, &sign_off));
+        // Claiming to be a steward isn't enough
+        assert!(!roles.actor_has_permission(&actor("checkout", Some(12345678), false), &users, &sign_off));
+        assert!(roles.actor_has_permission(&actor("checkout", Some(12345678), true), &users, &sign_off));
+        assert!(!roles.actor_has_permission(&actor("none", Some(12345678), true), &users, &sign_off));
+    }
+
+    #[test]
+    fn tokens_identify_users_until_revoked() {
+        let mut roles = Roles::default();
+        let users = users();
+
+        assert!(roles.issue_token(1, &users).is_err());
+
+        let issued = roles.issue_token(12345678, &users).unwrap();
+        assert_eq!(roles.get_token_user(&issued.token), Some(12345678));
+        assert_eq!(roles.get_token_user("not a token"), None);
+        assert_eq!(roles.get_token_user(""), None);
+
+        roles.rekey_user(&12345678, &87654321);
+        assert_eq!(roles.get_token_user(&issued.token), Some(87654321));
+
+        assert_eq!(roles.revoke_tokens(&87654321), 1);
+        assert_eq!(roles.get_token_user(&issued.token), None);
+    }
+}
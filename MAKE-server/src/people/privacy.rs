@@ -0,0 +1,751 @@
+use std::collections::BTreeMap;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::inventory::checkout::CheckoutLogEntry;
+use crate::inventory::restock::RestockNotice;
+use crate::machines::printers::{PrintLogEntry, PrintQueueEntry};
+use crate::management::billing::{BillingCredit, MaterialUsageRecord};
+use crate::management::incidents::Incident;
+use crate::management::lost_and_found::LostItem;
+use crate::management::student_storage::{OrphanedAssignment, Slot, WaitlistEntry};
+use crate::now;
+use crate::people::quiz_bank::QuizAttempt;
+use crate::people::quizzes::{Quiz, QuizName};
+use crate::people::sign_offs::SignOff;
+use crate::people::suspensions::Suspension;
+use crate::people::users::{User, Users};
+use crate::Data;
+
+/// Records that were about someone who's been anonymized point to this ID instead, so counts still add up
+pub const ANONYMOUS_ID: u64 = 0;
+
+const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+/// Requests to delete people's data, and the IDs and emails that have been anonymized.
+///
+/// Quiz responses are re-fetched from the sheets every loop, so responses from anonymized IDs and emails
+/// are dropped as they come in until the rows are deleted from the sheets. `retention_years` anonymizes
+/// history older than that many years once a day, if set.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Privacy {
+    pub deletion_requests: Vec<DeletionRequest>,
+    pub anonymized_ids: Vec<u64>,
+    pub anonymized_emails: Vec<String>,
+    pub retention_years: Option<u64>,
+    pub applied_retention: bool,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum DeletionStatus {
+    #[default]
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DeletionRequest {
+    pub id: String,
+    pub college_id: u64,
+    pub reason: String,
+    pub requested_by: String,
+    pub status: DeletionStatus,
+    pub records_anonymized: usize,
+    pub timestamp_requested: u64,
+    pub timestamp_resolved: Option<u64>,
+}
+
+/// A quiz response from the sheets, with the quiz it was for
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct ExportedResponse {
+    pub quiz: QuizName,
+    pub timestamp: String,
+    pub name: String,
+    pub college_email: String,
+    pub passed: bool,
+}
+
+/// Everything stored about a user, for answering data requests
+#[derive(Default, Debug, Serialize, Clone)]
+pub struct UserDataExport {
+    pub college_id: u64,
+    pub timestamp_exported: u64,
+    pub user: Option<User>,
+    pub id_aliases: Vec<u64>,
+    pub email_aliases: Vec<String>,
+    pub quiz_responses: Vec<ExportedResponse>,
+    pub quiz_attempts: Vec<QuizAttempt>,
+    pub sign_offs: Vec<SignOff>,
+    pub checkouts: Vec<CheckoutLogEntry>,
+    pub storage_slots: Vec<Slot>,
+    pub storage_orphaned: Vec<OrphanedAssignment>,
+    pub storage_waitlist: Vec<WaitlistEntry>,
+    pub print_log: Vec<PrintLogEntry>,
+    pub print_queue: Vec<PrintQueueEntry>,
+    pub material_usage: Vec<MaterialUsageRecord>,
+    pub billing_credits: Vec<BillingCredit>,
+    pub incidents: Vec<Incident>,
+    pub lost_items: Vec<LostItem>,
+    pub restock_requests: Vec<RestockNotice>,
+    pub deletion_requests: Vec<DeletionRequest>,
+    pub suspensions: Vec<Suspension>,
+}
+
+impl UserDataExport {
+    /// The export as one CSV per kind of record, keyed by file name.
+    /// Nested fields, eg the items in a checkout, are written as JSON.
+    pub fn to_csv_bundle(&self) -> Result<BTreeMap<String, String>, String> {
+        let mut bundle = BTreeMap::new();
+
+        bundle.insert("user.csv".to_string(), records_to_csv(&self.user.iter().collect::<Vec<_>>())?);
+        bundle.insert("quiz_responses.csv".to_string(), records_to_csv(&self.quiz_responses)?);
+        bundle.insert("quiz_attempts.csv".to_string(), records_to_csv(&self.quiz_attempts)?);
+        bundle.insert("sign_offs.csv".to_string(), records_to_csv(&self.sign_offs)?);
+        bundle.insert("checkouts.csv".to_string(), records_to_csv(&self.checkouts)?);
+        bundle.insert("storage_slots.csv".to_string(), records_to_csv(&self.storage_slots)?);
+        bundle.insert("storage_orphaned.csv".to_string(), records_to_csv(&self.storage_orphaned)?);
+        bundle.insert("storage_waitlist.csv".to_string(), records_to_csv(&self.storage_waitlist)?);
+        bundle.insert("print_log.csv".to_string(), records_to_csv(&self.print_log)?);
+        bundle.insert("print_queue.csv".to_string(), records_to_csv(&self.print_queue)?);
+        bundle.insert("material_usage.csv".to_string(), records_to_csv(&self.material_usage)?);
+        bundle.insert("billing_credits.csv".to_string(), records_to_csv(&self.billing_credits)?);
+        bundle.insert("incidents.csv".to_string(), records_to_csv(&self.incidents)?);
+        bundle.insert("lost_items.csv".to_string(), records_to_csv(&self.lost_items)?);
+        bundle.insert("restock_requests.csv".to_string(), records_to_csv(&self.restock_requests)?);
+        bundle.insert("deletion_requests.csv".to_string(), records_to_csv(&self.deletion_requests)?);
+        bundle.insert("suspensions.csv".to_string(), records_to_csv(&self.suspensions)?);
+
+        Ok(bundle)
+    }
+}
+
+impl Privacy {
+    pub fn is_anonymized(&self, college_id: &u64) -> bool {
+        self.anonymized_ids.contains(college_id)
+    }
+
+    /// Drops quiz responses from anonymized IDs and emails, since the sheets still have them
+    pub fn scrub_quizzes(&self, quizzes: &mut Vec<Quiz>) {
+        for quiz in quizzes.iter_mut() {
+            quiz.responses.retain(|x| {
+                !self.anonymized_ids.contains(&x.college_id)
+                    && !self.anonymized_emails.contains(&x.college_email.trim().to_lowercase())
+            });
+        }
+    }
+
+    pub fn request_deletion(&mut self, mut request: DeletionRequest, users: &Users) -> Result<DeletionRequest, String> {
+        if !users.exists(&request.college_id) {
+            return Err(format!("No user found with ID {}", request.college_id));
+        }
+
+        let open = self
+            .deletion_requests
+            .iter()
+            .any(|x| x.college_id == request.college_id && x.status == DeletionStatus::Pending);
+
+        if open {
+            return Err(format!("{} already has a deletion request waiting", request.college_id));
+        }
+
+        request.id = Uuid::new_v4().to_string();
+        request.status = DeletionStatus::Pending;
+        request.records_anonymized = 0;
+        request.timestamp_requested = now();
+        request.timestamp_resolved = None;
+
+        info!("Deletion requested for {}", request.college_id);
+
+        self.deletion_requests.push(request.clone());
+
+        Ok(request)
+    }
+
+    pub fn get_pending(&self) -> Vec<DeletionRequest> {
+        self.deletion_requests
+            .iter()
+            .filter(|x| x.status == DeletionStatus::Pending)
+            .cloned()
+            .collect()
+    }
+
+    fn get_pending_mut(&mut self, id: &str) -> Result<&mut DeletionRequest, String> {
+        self.deletion_requests
+            .iter_mut()
+            .find(|x| x.id == id && x.status == DeletionStatus::Pending)
+            .ok_or(format!("No pending deletion request found with id {}", id))
+    }
+
+    pub fn reject_deletion(&mut self, id: &str) -> Result<DeletionRequest, String> {
+        let request = self.get_pending_mut(id)?;
+
+        info!("Rejecting deletion request for {}", request.college_id);
+
+        request.status = DeletionStatus::Rejected;
+        request.timestamp_resolved = Some(now());
+
+        Ok(request.clone())
+    }
+
+    pub fn set_retention_years(&mut self, years: u64) {
+        // 0 turns retention off
+        self.retention_years = if years == 0 { None } else { Some(years) };
+        self.applied_retention = false;
+    }
+
+    /// Anything from before this is anonymized by the retention rule
+    pub fn get_retention_cutoff(&self) -> Option<u64> {
+        self.retention_years
+            .map(|years| now().saturating_sub(years * SECONDS_PER_YEAR))
+    }
+}
+
+/// Collects everything tied to a college ID, including IDs and emails that were merged into it
+pub fn export_user_data(data: &Data, college_id: u64) -> Result<UserDataExport, String> {
+    let user = data.users.get_user_by_id(&college_id);
+
+    let id_aliases: Vec<u64> = data
+        .identities
+        .id_aliases
+        .iter()
+        .filter(|(_, to)| **to == college_id)
+        .map(|(from, _)| *from)
+        .collect();
+
+    let mut emails: Vec<String> = data
+        .identities
+        .email_aliases
+        .iter()
+        .filter(|(_, to)| **to == college_id)
+        .map(|(email, _)| email.clone())
+        .collect();
+
+    let email_aliases = emails.clone();
+
+    if let Some(user) = &user {
+        emails.push(user.get_email().trim().to_lowercase());
+    }
+
+    emails.retain(|x| !x.is_empty());
+
+    let matches_id = |id: &u64| *id == college_id || id_aliases.contains(id);
+    let matches_email = |email: &str| emails.contains(&email.trim().to_lowercase());
+
+    let quiz_responses: Vec<ExportedResponse> = data
+        .quizzes
+        .iter()
+        .flat_map(|quiz| {
+            quiz.responses
+                .iter()
+                .filter(|x| matches_id(&x.college_id) || matches_email(&x.college_email))
+                .map(|x| ExportedResponse {
+                    quiz: quiz.name.clone(),
+                    timestamp: x.timestamp.clone(),
+                    name: x.name.clone(),
+                    college_email: x.college_email.clone(),
+                    passed: x.passed,
+                })
+                .collect::<Vec<ExportedResponse>>()
+        })
+        .collect();
+
+    if user.is_none() && quiz_responses.is_empty() {
+        return Err(format!("No user found with ID {}", college_id));
+    }
+
+    let checkouts: Vec<CheckoutLogEntry> = data
+        .checkout_log
+        .currently_checked_out
+        .iter()
+        .chain(data.checkout_log.checkout_history.iter())
+        .filter(|x| x.college_id == college_id)
+        .cloned()
+        .collect();
+
+    let storage_slots: Vec<Slot> = data
+        .student_storage
+        .slots
+        .iter()
+        .filter(|x| {
+            x.occupied_details.iter().chain(x.previous_details.iter()).any(|d| d.college_id == college_id)
+        })
+        .map(|x| {
+            // The other of the two may be someone else
+            let mut slot = x.clone();
+            slot.occupied_details = slot.occupied_details.filter(|d| d.college_id == college_id);
+            slot.previous_details = slot.previous_details.filter(|d| d.college_id == college_id);
+            slot
+        })
+        .collect();
+
+    Ok(UserDataExport {
+        college_id,
+        timestamp_exported: now(),
+        user,
+        quiz_responses,
+        quiz_attempts: data
+            .quiz_banks
+            .attempts
+            .iter()
+            .filter(|x| x.college_id == college_id)
+            .cloned()
+            .collect(),
+        sign_offs: data
+            .sign_offs
+            .records
+            .iter()
+            .filter(|x| x.college_id == college_id || x.steward_id == college_id)
+            .cloned()
+            .collect(),
+        checkouts,
+        storage_slots,
+        storage_orphaned: data
+            .student_storage
+            .orphaned
+            .iter()
+            .filter(|x| x.occupied_details.college_id == college_id)
+            .cloned()
+            .collect(),
+        storage_waitlist: data
+            .student_storage
+            .waitlist
+            .iter()
+            .filter(|x| x.college_id == college_id)
+            .cloned()
+            .collect(),
+        print_log: data.printers.get_print_log_for_id(&college_id),
+        print_queue: data.printers.get_queue_entries_for_id(&college_id),
+        material_usage: data
+            .billing
+            .usage
+            .iter()
+            .filter(|x| x.college_id == college_id)
+            .cloned()
+            .collect(),
+        billing_credits: data
+            .billing
+            .credits
+            .iter()
+            .filter(|x| x.college_id == college_id)
+            .cloned()
+            .collect(),
+        incidents: data
+            .incidents
+            .reports
+            .iter()
+            .filter(|x| x.college_id == Some(college_id))
+            .cloned()
+            .collect(),
+        lost_items: data
+            .lost_and_found
+            .items
+            .iter()
+            .filter(|x| x.college_id == Some(college_id) || x.claimed_by == Some(college_id))
+            .cloned()
+            .collect(),
+        restock_requests: data
+            .inventory
+            .needs_restock
+            .iter()
+            .filter(|x| matches_email(&x.email) || x.also_requested_by.iter().any(|e| matches_email(e)))
+            .cloned()
+            .collect(),
+        deletion_requests: data
+            .privacy
+            .deletion_requests
+            .iter()
+            .filter(|x| x.college_id == college_id)
+            .cloned()
+            .collect(),
+        suspensions: data.suspensions.get_for_id(&college_id),
+        id_aliases,
+        email_aliases,
+    })
+}
+
+/// Approves a deletion request, anonymizing the user.
+/// Refused while they still have something checked out, a storage slot or an outstanding balance.
+pub fn approve_deletion(data: &mut Data, id: &str) -> Result<DeletionRequest, String> {
+    let college_id = data.privacy.get_pending_mut(id)?.college_id;
+
+    let records_anonymized = anonymize_user(data, college_id)?;
+
+    let request = data.privacy.get_pending_mut(id)?;
+
+    request.status = DeletionStatus::Approved;
+    request.records_anonymized = records_anonymized;
+    request.timestamp_resolved = Some(now());
+
+    Ok(request.clone())
+}
+
+/// Removes a user and strips their ID, name and email from everything else, keeping the records for stats.
+/// Returns how many records were changed.
+pub fn anonymize_user(data: &mut Data, college_id: u64) -> Result<usize, String> {
+    if college_id == ANONYMOUS_ID {
+        return Err("ID can't be 0".to_string());
+    }
+
+    // Records can still be under IDs that were merged into this one
+    let mut ids = vec![college_id];
+    ids.extend(
+        data.identities
+            .id_aliases
+            .iter()
+            .filter(|(_, to)| **to == college_id)
+            .map(|(from, _)| *from),
+    );
+
+    let has_checkouts = data
+        .checkout_log
+        .currently_checked_out
+        .iter()
+        .any(|x| ids.contains(&x.college_id));
+
+    if has_checkouts {
+        return Err("User still has items checked out".to_string());
+    }
+
+    let has_slot = data
+        .student_storage
+        .slots
+        .iter()
+        .any(|x| x.occupied_details.as_ref().map_or(false, |d| ids.contains(&d.college_id)));
+
+    if has_slot {
+        return Err("User still has a storage slot".to_string());
+    }
+
+    if ids.iter().any(|x| data.billing.get_balance(*x) != 0) {
+        return Err("User still has an outstanding balance".to_string());
+    }
+
+    info!("Anonymizing {}", college_id);
+
+    let mut emails = Vec::new();
+
+    if let Some(user) = data.users.remove_user(&college_id) {
+        emails.push(user.get_email().trim().to_lowercase());
+    }
+
+    // Aliases point at the user, so they'd bring their quiz responses back
+    data.identities
+        .id_aliases
+        .retain(|from, to| *to != college_id && *from != college_id);
+
+    data.identities.email_aliases.retain(|email, to| {
+        if *to == college_id {
+            emails.push(email.clone());
+            false
+        } else {
+            true
+        }
+    });
+
+    data.identities.separate_ids.retain(|x| x != &college_id);
+    data.roles.remove_user(&college_id);
+    emails.retain(|x| !x.is_empty());
+
+    let mut changed = 0;
+
+    for quiz in data.quizzes.iter_mut() {
+        let before = quiz.responses.len();
+        quiz.responses
+            .retain(|x| !ids.contains(&x.college_id) && !emails.contains(&x.college_email.trim().to_lowercase()));
+        changed += before - quiz.responses.len();
+    }
+
+    let before = data.student_storage.waitlist.len();
+    data.student_storage.waitlist.retain(|x| !ids.contains(&x.college_id));
+    changed += before - data.student_storage.waitlist.len();
+
+    for sign_off in data.sign_offs.records.iter_mut() {
+        if ids.contains(&sign_off.steward_id) {
+            sign_off.steward_name = String::new();
+        }
+    }
+
+    for suspension in data.suspensions.suspensions.iter_mut().filter(|x| ids.contains(&x.college_id)) {
+        suspension.reason = String::new();
+    }
+
+    for id in ids.iter() {
+        changed += data.printers.anonymize_user(id, ANONYMOUS_ID);
+        changed += data.checkout_log.rekey_user(id, &ANONYMOUS_ID);
+        changed += data.student_storage.rekey_user(id, &ANONYMOUS_ID);
+        changed += data.sign_offs.rekey_user(id, &ANONYMOUS_ID);
+        changed += data.suspensions.rekey_user(id, &ANONYMOUS_ID);
+        changed += data.quiz_banks.rekey_user(id, &ANONYMOUS_ID);
+    }
+
+    changed += anonymize_billing(data, |x| ids.contains(&x), |_| true);
+
+    for incident in data.incidents.reports.iter_mut().filter(|x| x.college_id.map_or(false, |x| ids.contains(&x))) {
+        incident.college_id = None;
+        changed += 1;
+    }
+
+    for item in data.lost_and_found.items.iter_mut() {
+        let is_user = |x: &u64| ids.contains(x);
+
+        if item.college_id.map_or(false, |x| is_user(&x)) || item.claimed_by.map_or(false, |x| is_user(&x)) {
+            item.college_id = item.college_id.filter(|x| !is_user(x));
+            item.claimed_by = item.claimed_by.filter(|x| !is_user(x));
+            changed += 1;
+        }
+    }
+
+    for notice in data.inventory.needs_restock.iter_mut() {
+        let before = notice.also_requested_by.len();
+        notice
+            .also_requested_by
+            .retain(|x| !emails.contains(&x.trim().to_lowercase()));
+        changed += before - notice.also_requested_by.len();
+
+        if emails.contains(&notice.email.trim().to_lowercase()) {
+            notice.email = String::new();
+            changed += 1;
+        }
+    }
+
+    changed += data.audit_log.anonymize_user(&ids, &emails, ANONYMOUS_ID);
+
+    for id in ids {
+        if !data.privacy.anonymized_ids.contains(&id) {
+            data.privacy.anonymized_ids.push(id);
+        }
+    }
+
+    for email in emails {
+        if !data.privacy.anonymized_emails.contains(&email) {
+            data.privacy.anonymized_emails.push(email);
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Anonymizes history older than `cutoff`: finished checkouts, storage, print logs, hosted quiz attempts,
+/// settled billing and resolved incidents and lost items. Current users and their certifications are kept.
+/// Returns how many records were changed.
+pub fn anonymize_before(data: &mut Data, cutoff: u64) -> usize {
+    let mut changed = 0;
+
+    for entry in data.checkout_log.checkout_history.iter_mut() {
+        let timestamp = entry.timestamp_checked_in.unwrap_or(entry.timestamp_checked_out);
+
+        if entry.college_id != ANONYMOUS_ID && timestamp < cutoff {
+            entry.college_id = ANONYMOUS_ID;
+            changed += 1;
+        }
+    }
+
+    for slot in data.student_storage.slots.iter_mut() {
+        if let Some(details) = slot.previous_details.as_mut() {
+            if details.college_id != ANONYMOUS_ID && details.timestamp_end < cutoff {
+                details.college_id = ANONYMOUS_ID;
+                changed += 1;
+            }
+        }
+    }
+
+    changed += data.printers.anonymize_before(cutoff, ANONYMOUS_ID);
+
+    // Attempts imported from the sheets would just be imported again
+    for attempt in data.quiz_banks.attempts.iter_mut() {
+        if attempt.college_id != ANONYMOUS_ID && attempt.imported_from.is_none() && attempt.timestamp_started < cutoff {
+            attempt.college_id = ANONYMOUS_ID;
+            changed += 1;
+        }
+    }
+
+    // Only settled accounts, so old charges aren't written off
+    let balances = data.billing.get_balances();
+    let settled = |college_id: u64| {
+        balances
+            .iter()
+            .find(|x| x.college_id == college_id)
+            .map_or(true, |x| x.balance_cents == 0)
+    };
+
+    changed += anonymize_billing(data, |x| x != ANONYMOUS_ID && settled(x), |x| x < cutoff);
+
+    for incident in data.incidents.reports.iter_mut() {
+        if incident.college_id.is_some() && incident.timestamp_resolved.map_or(false, |x| x < cutoff) {
+            incident.college_id = None;
+            changed += 1;
+        }
+    }
+
+    for item in data.lost_and_found.items.iter_mut() {
+        let has_owner = item.college_id.is_some() || item.claimed_by.is_some();
+
+        if has_owner && item.timestamp_resolved.map_or(false, |x| x < cutoff) {
+            item.college_id = None;
+            item.claimed_by = None;
+            changed += 1;
+        }
+    }
+
+    if changed > 0 {
+        info!("Anonymized {} records past the retention period", changed);
+    }
+
+    changed
+}
+
+/// Applies the retention rule, if there is one
+pub fn apply_retention(data: &mut Data) -> usize {
+    data.privacy.applied_retention = true;
+
+    match data.privacy.get_retention_cutoff() {
+        Some(cutoff) => anonymize_before(data, cutoff),
+        None => 0,
+    }
+}
+
+fn anonymize_billing(data: &mut Data, id_matches: impl Fn(u64) -> bool, time_matches: impl Fn(u64) -> bool) -> usize {
+    let mut changed = 0;
+
+    for record in data.billing.usage.iter_mut() {
+        if id_matches(record.college_id) && time_matches(record.timestamp) {
+            record.college_id = ANONYMOUS_ID;
+            record.notes = String::new();
+            changed += 1;
+        }
+    }
+
+    for credit in data.billing.credits.iter_mut() {
+        if id_matches(credit.college_id) && time_matches(credit.timestamp) {
+            credit.college_id = ANONYMOUS_ID;
+            credit.notes = String::new();
+            changed += 1;
+        }
+    }
+
+    changed
+}
+
+/// Writes any serializable records as CSV, with a column for every field any of them has
+fn records_to_csv<T: Serialize>(records: &[T]) -> Result<String, String> {
+    let mut rows = Vec::new();
+
+    for record in records {
+        match serde_json::to_value(record).map_err(|e| e.to_string())? {
+            serde_json::Value::Object(row) => rows.push(row),
+            other => return Err(format!("Can't write {} as a CSV row", other)),
+        }
+    }
+
+    let mut headers: Vec<String> = Vec::new();
+
+    for row in rows.iter() {
+        for key in row.keys() {
+            if !headers.contains(key) {
+                headers.push(key.clone());
+            }
+        }
+    }
+
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+
+    if !headers.is_empty() {
+        wtr.write_record(&headers).map_err(|e| e.to_string())?;
+    }
+
+    for row in rows.iter() {
+        wtr.write_record(headers.iter().map(|header| match row.get(header) {
+            None | Some(serde_json::Value::Null) => String::new(),
+            Some(serde_json::Value::String(x)) => x.clone(),
+            Some(other) => other.to_string(),
+        }))
+        .map_err(|e| e.to_string())?;
+    }
+
+    let bytes = wtr.into_inner().map_err(|e| e.to_string())?;
+
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{anonymize_user, ANONYMOUS_ID};
+    use crate::inventory::checkout::CheckoutLogEntry;
+    use crate::inventory::restock::RestockNotice;
+    use crate::management::incidents::Incident;
+    use crate::management::lost_and_found::LostItem;
+    use crate::people::quizzes::{Quiz, Response};
+    use crate::people::users::User;
+    use crate::Data;
+
+    fn response(college_id: u64, email: &str) -> Response {
+        Response {
+            name: "Test".to_string(),
+            college_id,
+            college_email: email.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn checkout(college_id: u64) -> CheckoutLogEntry {
+        CheckoutLogEntry {
+            college_id,
+            checkout_uuid: "abc".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn anonymizing_keeps_records_without_the_user() {
+        let mut data = Data::default();
+        data.users
+            .add_set_user(User::from_response(&response(12345678, "test@example.edu")));
+        data.identities.id_aliases.insert(1234567, 12345678);
+
+        data.quizzes.push(Quiz {
+            responses: vec![response(1234567, "typo@example.edu"), response(7654321, "other@example.edu")],
+            ..Default::default()
+        });
+        data.checkout_log.checkout_history.push(checkout(12345678));
+        data.checkout_log.checkout_history.push(checkout(1234567));
+        data.incidents.reports.push(Incident {
+            college_id: Some(12345678),
+            ..Default::default()
+        });
+        data.lost_and_found.items.push(LostItem {
+            claimed_by: Some(12345678),
+            ..Default::default()
+        });
+        data.inventory.needs_restock.push(RestockNotice {
+            email: "Test@example.edu".to_string(),
+            ..Default::default()
+        });
+
+        data.checkout_log.currently_checked_out.push(checkout(12345678));
+        assert!(anonymize_user(&mut data, 12345678).is_err());
+        data.checkout_log.currently_checked_out.clear();
+
+        anonymize_user(&mut data, 12345678).unwrap();
+
+        assert!(data.users.get_user_by_id(&12345678).is_none());
+        assert!(data.identities.id_aliases.is_empty());
+
+        // The alias's response goes, everyone else's stays
+        assert_eq!(data.quizzes[0].responses.len(), 1);
+        assert_eq!(data.quizzes[0].responses[0].college_id, 7654321);
+
+        // Including what's still under the alias
+        assert!(data.checkout_log.checkout_history.iter().all(|x| x.college_id == ANONYMOUS_ID));
+        assert_eq!(data.incidents.reports[0].college_id, None);
+        assert_eq!(data.lost_and_found.items[0].claimed_by, None);
+        assert!(data.inventory.needs_restock[0].email.is_empty());
+
+        assert!(data.privacy.anonymized_ids.contains(&1234567));
+        assert!(data.privacy.anonymized_emails.contains(&"test@example.edu".to_string()));
+    }
+}
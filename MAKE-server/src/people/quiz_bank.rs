@@ -0,0 +1,619 @@
+use std::collections::HashSet;
+
+use log::info;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::now;
+use crate::people::quiz_registry::QuizRegistry;
+use crate::people::quizzes::{Quiz, QuizName, Response};
+use crate::people::users::{User, Users};
+
+// How long someone has to submit an attempt once they've started it
+const ATTEMPT_TIME_LIMIT: u64 = 60 * 60;
+// Unsubmitted attempts one ID can have going at once
+const MAX_OPEN_ATTEMPTS: usize = 3;
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum QuestionKind {
+    // One correct choice
+    #[default]
+    MultipleChoice,
+    // Every correct choice has to be picked, and nothing else
+    MultiSelect,
+    // One correct choice, asked about the question's image
+    Image,
+}
+
+/// Quizzes hosted by the server, and every attempt at them
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct QuizBanks {
+    pub banks: Vec<QuestionBank>,
+    pub attempts: Vec<QuizAttempt>,
+}
+
+/// The questions for one quiz, and how attempts at it are made and graded
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct QuestionBank {
+    pub quiz: QuizName,
+    pub questions: Vec<Question>,
+    // Questions drawn at random for each attempt, or every question if 0
+    pub questions_per_attempt: usize,
+    // Fraction of questions that have to be right to pass, eg 0.8
+    pub pass_threshold: f64,
+}
+
+impl Default for QuestionBank {
+    fn default() -> Self {
+        QuestionBank {
+            quiz: QuizName::default(),
+            questions: Vec::new(),
+            questions_per_attempt: 0,
+            // The sheet quizzes needed full marks
+            pass_threshold: 1.0,
+        }
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Question {
+    pub id: String,
+    pub kind: QuestionKind,
+    pub prompt: String,
+    // URL of an image shown with the question
+    pub image: Option<String>,
+    pub choices: Vec<String>,
+    // Indices into `choices`
+    pub correct: Vec<usize>,
+}
+
+/// One go at a quiz. Questions and their choices are shuffled for each attempt.
+/// Attempts imported from the sheets have no questions, only the result.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct QuizAttempt {
+    pub id: String,
+    pub quiz: QuizName,
+    pub college_id: u64,
+    pub questions: Vec<AttemptQuestion>,
+    // The bank's threshold when the attempt started
+    pub pass_threshold: f64,
+    pub answers: Vec<Vec<usize>>,
+    pub score: Option<f64>,
+    pub passed: bool,
+    pub timestamp_started: u64,
+    pub timestamp_submitted: Option<u64>,
+    // Timestamp of the sheet response this was imported from
+    pub imported_from: Option<String>,
+}
+
+/// A question as it was asked in an attempt, copied from the bank so that
+/// editing or re-uploading the bank doesn't change how the attempt is graded.
+/// `choice_order[i]` is the index in `choices` of the i-th choice shown.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AttemptQuestion {
+    pub question_id: String,
+    pub prompt: String,
+    pub choices: Vec<String>,
+    // Indices into `choices`
+    pub correct: Vec<usize>,
+    pub choice_order: Vec<usize>,
+}
+
+/// A question as shown to the person taking the quiz, without the answers
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct QuestionView {
+    pub question_id: String,
+    pub kind: QuestionKind,
+    pub prompt: String,
+    pub image: Option<String>,
+    pub choices: Vec<String>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct AttemptView {
+    pub id: String,
+    pub quiz: QuizName,
+    pub questions: Vec<QuestionView>,
+    pub timestamp_expires: u64,
+}
+
+/// Body of a request to start an attempt.
+/// Only needed for people who haven't taken a quiz before, so they can be added as users.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct QuizTaker {
+    pub name: String,
+    pub college_email: String,
+}
+
+/// Body of a request to submit an attempt.
+/// `answers[i]` are the indices of the choices picked for the i-th question, as shown.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AttemptSubmission {
+    pub answers: Vec<Vec<usize>>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct AttemptResult {
+    pub correct: usize,
+    pub total: usize,
+    pub score: f64,
+    pub passed: bool,
+}
+
+impl QuizBanks {
+    pub fn get_bank(&self, quiz: &QuizName) -> Option<QuestionBank> {
+        self.banks.iter().find(|x| &x.quiz == quiz).cloned()
+    }
+
+    /// Quizzes the server hosts itself, which no longer need fetching from the sheets
+    pub fn get_native_quizzes(&self) -> Vec<QuizName> {
+        self.banks
+            .iter()
+            .filter(|x| !x.questions.is_empty())
+            .map(|x| x.quiz.clone())
+            .collect()
+    }
+
+    /// Adds or replaces the question bank for a quiz
+    pub fn set_bank(&mut self, mut bank: QuestionBank) -> Result<QuestionBank, String> {
+        if !(0.0..=1.0).contains(&bank.pass_threshold) {
+            return Err("Pass threshold must be between 0 and 1".to_string());
+        }
+
+        for (i, question) in bank.questions.iter_mut().enumerate() {
+            if question.prompt.trim().is_empty() {
+                return Err(format!("Question {} has no prompt", i + 1));
+            }
+
+            if question.choices.len() < 2 {
+                return Err(format!("Question {} needs at least 2 choices", i + 1));
+            }
+
+            if question.correct.iter().any(|x| *x >= question.choices.len()) {
+                return Err(format!("Question {} has a correct answer that isn't a choice", i + 1));
+            }
+
+            let needs_one = question.kind != QuestionKind::MultiSelect;
+
+            if question.correct.is_empty() || (needs_one && question.correct.len() > 1) {
+                return Err(format!(
+                    "Question {} needs {} correct answer",
+                    i + 1,
+                    if needs_one { "exactly one" } else { "at least one" }
+                ));
+            }
+
+            if question.kind == QuestionKind::Image && question.image.is_none() {
+                return Err(format!("Question {} has no image", i + 1));
+            }
+
+            if question.id.is_empty() {
+                question.id = Uuid::new_v4().to_string();
+            }
+        }
+
+        info!("Setting {} questions for the {} quiz", bank.questions.len(), bank.quiz);
+
+        self.banks.retain(|x| x.quiz != bank.quiz);
+        self.banks.push(bank.clone());
+
+        Ok(bank)
+    }
+
+    /// Starts an attempt, drawing and shuffling questions from the quiz's bank.
+    /// New users are added, as the sheets used to do for anyone who filled in a quiz.
+    pub fn start_attempt(
+        &mut self,
+        quiz: &QuizName,
+        college_id: u64,
+        taker: QuizTaker,
+        can_add_user: bool,
+        users: &mut Users,
+        registry: &QuizRegistry,
+    ) -> Result<AttemptView, String> {
+        if college_id == 0 {
+            return Err("Invalid ID number".to_string());
+        }
+
+        self.prune_expired_attempts();
+
+        let open = self
+            .attempts
+            .iter()
+            .filter(|x| x.college_id == college_id && x.timestamp_submitted.is_none())
+            .count();
+
+        if open >= MAX_OPEN_ATTEMPTS {
+            return Err("Too many attempts in progress, please finish one or wait an hour".to_string());
+        }
+
+        let bank = self
+            .get_bank(quiz)
+            .filter(|x| !x.questions.is_empty())
+            .ok_or(format!("The {} quiz has no questions", quiz))?;
+
+        // Use the ID as the bank has it, rather than however it was typed
+        let quiz = &bank.quiz.clone();
+
+        if let Some(user) = users.get_user_by_id(&college_id) {
+            let missing = registry.get_missing_prerequisites(quiz, &user);
+
+            if !missing.is_empty() {
+                return Err(format!(
+                    "The {} quiz needs {} passed first",
+                    quiz,
+                    missing.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")
+                ));
+            }
+        } else if !registry.get_all_prerequisites(quiz).is_empty() {
+            return Err(format!("The {} quiz has prerequisites, please take those first", quiz));
+        } else if !can_add_user {
+            return Err("User not found, please ask a steward to add you before taking a quiz".to_string());
+        } else if taker.name.trim().is_empty() || taker.college_email.trim().is_empty() {
+            return Err("User not found, a name and email are needed to take a quiz".to_string());
+        } else {
+            info!("Adding user {} to take the {} quiz", college_id, quiz);
+
+            users.add_set_user(User::from_response(&Response {
+                name: taker.name.trim().to_string(),
+                college_id,
+                college_email: taker.college_email.trim().to_string(),
+                ..Default::default()
+            }));
+        }
+
+        let mut rng = rand::thread_rng();
+
+        let count = match bank.questions_per_attempt {
+            0 => bank.questions.len(),
+            n => n.min(bank.questions.len()),
+        };
+
+        let mut questions: Vec<&Question> = bank.questions.choose_multiple(&mut rng, count).collect();
+        questions.shuffle(&mut rng);
+
+        let mut attempt = QuizAttempt {
+            id: Uuid::new_v4().to_string(),
+            quiz: quiz.clone(),
+            college_id,
+            pass_threshold: bank.pass_threshold,
+            timestamp_started: now(),
+            ..Default::default()
+        };
+
+        let mut view = AttemptView {
+            id: attempt.id.clone(),
+            quiz: quiz.clone(),
+            questions: Vec::new(),
+            timestamp_expires: attempt.timestamp_started + ATTEMPT_TIME_LIMIT,
+        };
+
+        for question in questions {
+            let mut choice_order: Vec<usize> = (0..question.choices.len()).collect();
+            choice_order.shuffle(&mut rng);
+
+            view.questions.push(QuestionView {
+                question_id: question.id.clone(),
+                kind: question.kind.clone(),
+                prompt: question.prompt.clone(),
+                image: question.image.clone(),
+                choices: choice_order.iter().map(|x| question.choices[*x].clone()).collect(),
+            });
+
+            attempt.questions.push(AttemptQuestion {
+                question_id: question.id.clone(),
+                prompt: question.prompt.clone(),
+                choices: question.choices.clone(),
+                correct: question.correct.clone(),
+                choice_order,
+            });
+        }
+
+        info!("{} started an attempt at the {} quiz", college_id, quiz);
+
+        self.attempts.push(attempt);
+
+        Ok(view)
+    }
+
+    /// Removes attempts that ran out of time without being submitted.
+    /// Returns how many were removed.
+    pub fn prune_expired_attempts(&mut self) -> usize {
+        let cutoff = now().saturating_sub(ATTEMPT_TIME_LIMIT);
+        let before = self.attempts.len();
+
+        self.attempts.retain(|x| {
+            x.timestamp_submitted.is_some() || x.imported_from.is_some() || x.timestamp_started >= cutoff
+        });
+
+        before - self.attempts.len()
+    }
+
+    /// Grades an attempt against the questions it was asked, marking the quiz passed for the user if they passed
+    pub fn submit_attempt(
+        &mut self,
+        id: &str,
+        submission: AttemptSubmission,
+        users: &mut Users,
+    ) -> Result<AttemptResult, String> {
+        let attempt = self
+            .attempts
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or(format!("No attempt found with id {}", id))?;
+
+        if attempt.timestamp_submitted.is_some() {
+            return Err("Attempt has already been submitted".to_string());
+        }
+
+        if now() > attempt.timestamp_started + ATTEMPT_TIME_LIMIT {
+            return Err("Attempt has expired, please start again".to_string());
+        }
+
+        if submission.answers.len() != attempt.questions.len() {
+            return Err(format!(
+                "Expected answers to {} questions, got {}",
+                attempt.questions.len(),
+                submission.answers.len()
+            ));
+        }
+
+        // Every question has a correct answer, so attempts started before answers were kept can't be graded
+        if attempt.questions.iter().any(|x| x.correct.is_empty()) {
+            return Err("Attempt can't be graded, please start again".to_string());
+        }
+
+        let mut correct = 0;
+
+        for (asked, answer) in attempt.questions.iter().zip(submission.answers.iter()) {
+            let picked: HashSet<usize> = answer
+                .iter()
+                .filter_map(|x| asked.choice_order.get(*x).cloned())
+                .collect();
+            let expected: HashSet<usize> = asked.correct.iter().cloned().collect();
+
+            if picked == expected {
+                correct += 1;
+            }
+        }
+
+        let total = attempt.questions.len();
+        let score = if total == 0 { 1.0 } else { correct as f64 / total as f64 };
+        let passed = score >= attempt.pass_threshold;
+
+        attempt.answers = submission.answers;
+        attempt.score = Some(score);
+        attempt.passed = passed;
+        attempt.timestamp_submitted = Some(now());
+
+        info!(
+            "{} scored {}/{} on the {} quiz",
+            attempt.college_id, correct, total, attempt.quiz
+        );
+
+        if passed {
+            // Passing again renews the certification
+            if let Some(mut user) = users.get_user_by_id(&attempt.college_id) {
+                user.set_quiz_passed(&attempt.quiz, true);
+                users.add_set_user(user);
+            }
+        }
+
+        Ok(AttemptResult {
+            correct,
+            total,
+            score,
+            passed,
+        })
+    }
+
+    pub fn rekey_user(&mut self, from: &u64, to: &u64) -> usize {
+        let mut moved = 0;
+
+        for attempt in self.attempts.iter_mut().filter(|x| &x.college_id == from) {
+            attempt.college_id = *to;
+            moved += 1;
+        }
+
+        moved
+    }
+
+    pub fn get_attempts_for_id(&self, college_id: &u64) -> Vec<QuizAttempt> {
+        self.attempts
+            .iter()
+            .filter(|x| &x.college_id == college_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Records responses from the sheets as attempts, so the history goes back
+    /// before the server hosted quizzes. Responses already imported are skipped.
+    pub fn import_responses(&mut self, quizzes: &Vec<Quiz>) -> usize {
+        let imported: HashSet<(QuizName, u64, String)> = self
+            .attempts
+            .iter()
+            .filter_map(|x| {
+                x.imported_from
+                    .as_ref()
+                    .map(|from| (x.quiz.clone(), x.college_id, from.clone()))
+            })
+            .collect();
+
+        let mut count = 0;
+
+        for quiz in quizzes {
+            for response in quiz.get_responses() {
+                let key = (quiz.get_name().clone(), response.college_id, response.timestamp.clone());
+
+                if response.college_id == 0 || imported.contains(&key) {
+                    continue;
+                }
+
+                let timestamp = response.get_timestamp().unwrap_or(0);
+
+                self.attempts.push(QuizAttempt {
+                    id: Uuid::new_v4().to_string(),
+                    quiz: key.0,
+                    college_id: key.1,
+                    passed: response.passed,
+                    timestamp_started: timestamp,
+                    timestamp_submitted: Some(timestamp),
+                    imported_from: Some(key.2),
+                    ..Default::default()
+                });
+
+                count += 1;
+            }
+        }
+
+        if count > 0 {
+            info!("Imported {} quiz responses from the sheets", count);
+        }
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttemptSubmission, Question, QuestionBank, QuizAttempt, QuizBanks, QuizTaker, MAX_OPEN_ATTEMPTS};
+    use crate::people::quiz_registry::QuizRegistry;
+    use crate::people::quizzes::{QuizName, Response};
+    use crate::people::users::{User, Users};
+
+    fn bank(correct: usize) -> QuestionBank {
+        let question = Question {
+            prompt: "Where do safety glasses go?".to_string(),
+            choices: vec!["On your eyes".to_string(), "In your pocket".to_string()],
+            correct: vec![correct],
+            ..Default::default()
+        };
+
+        QuestionBank {
+            quiz: QuizName::new("Laser"),
+            questions: vec![question.clone(), question],
+            ..Default::default()
+        }
+    }
+
+    /// Answers as shown, picking the choice at `bank_index` for every question
+    fn answers(banks: &QuizBanks, id: &str, bank_index: usize) -> AttemptSubmission {
+        let attempt = banks.attempts.iter().find(|x| x.id == id).unwrap();
+
+        AttemptSubmission {
+            answers: attempt
+                .questions
+                .iter()
+                .map(|x| vec![x.choice_order.iter().position(|c| *c == bank_index).unwrap()])
+                .collect(),
+        }
+    }
+
+    fn start(banks: &mut QuizBanks, users: &mut Users) -> String {
+        banks
+            .start_attempt(
+                &QuizName::new("Laser"),
+                1234,
+                QuizTaker::default(),
+                false,
+                users,
+                &QuizRegistry::default(),
+            )
+            .unwrap()
+            .id
+    }
+
+    #[test]
+    fn attempts_are_graded_against_the_questions_asked() {
+        let mut banks = QuizBanks::default();
+        let mut users = Users::default();
+        users.add_set_user(User::from_response(&Response {
+            name: "Test".to_string(),
+            college_id: 1234,
+            college_email: "test@example.edu".to_string(),
+            ..Default::default()
+        }));
+
+        banks.set_bank(bank(0)).unwrap();
+        let id = start(&mut banks, &mut users);
+
+        // Re-uploading gives every question a new ID and flips the answer
+        banks.set_bank(bank(1)).unwrap();
+
+        let wrong = answers(&banks, &id, 1);
+        let result = banks.submit_attempt(&id, wrong, &mut users).unwrap();
+        assert_eq!(result.correct, 0);
+        assert!(!result.passed);
+
+        let id = start(&mut banks, &mut users);
+        banks.banks.clear();
+
+        let right = answers(&banks, &id, 1);
+        let result = banks.submit_attempt(&id, right, &mut users).unwrap();
+        assert_eq!(result.correct, 2);
+        assert!(result.passed);
+    }
+
+    #[test]
+    fn open_attempts_are_capped_and_expired_ones_pruned() {
+        let mut banks = QuizBanks::default();
+        let mut users = Users::default();
+        users.add_set_user(User::from_response(&Response {
+            name: "Test".to_string(),
+            college_id: 1234,
+            college_email: "test@example.edu".to_string(),
+            ..Default::default()
+        }));
+
+        banks.set_bank(bank(0)).unwrap();
+
+        for _ in 0..MAX_OPEN_ATTEMPTS {
+            start(&mut banks, &mut users);
+        }
+
+        let taker = QuizTaker::default();
+        let result = banks.start_attempt(&QuizName::new("Laser"), 1234, taker, false, &mut users, &QuizRegistry::default());
+        assert!(result.is_err());
+
+        // Once they've run out of time they stop counting, and are removed
+        for attempt in banks.attempts.iter_mut() {
+            attempt.timestamp_started = 0;
+        }
+        banks.attempts.push(QuizAttempt {
+            college_id: 1234,
+            timestamp_submitted: Some(1),
+            ..Default::default()
+        });
+
+        start(&mut banks, &mut users);
+        assert_eq!(banks.attempts.len(), 2);
+    }
+
+    #[test]
+    fn new_users_need_permission_to_be_added() {
+        let mut banks = QuizBanks::default();
+        let mut users = Users::default();
+        banks.set_bank(bank(0)).unwrap();
+
+        let taker = QuizTaker {
+            name: "Test".to_string(),
+            college_email: "test@example.edu".to_string(),
+        };
+        let laser = QuizName::new("Laser");
+        let registry = QuizRegistry::default();
+
+        assert!(banks.start_attempt(&laser, 1234, taker.clone(), false, &mut users, &registry).is_err());
+        assert!(!users.exists(&1234));
+
+        assert!(banks.start_attempt(&laser, 1234, taker, true, &mut users, &registry).is_ok());
+        assert!(users.exists(&1234));
+    }
+}
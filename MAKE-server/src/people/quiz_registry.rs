@@ -0,0 +1,354 @@
+use std::io::Read;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::management::emails::send_individual_email;
+use crate::people::permissions::SwipeGroup;
+use crate::people::quizzes::{Quiz, QuizName};
+use crate::people::users::{User, Users};
+use crate::{now, EMAIL_TEMPLATES, MEMORY_DATABASE};
+
+const QUIZ_CONFIG: &str = "quizzes.toml";
+const DAY: u64 = 24 * 60 * 60;
+const DEFAULT_REMINDER_DAYS: u64 = 14;
+const DEFAULT_QUIZ_LINK: &str = "https://make.hmc.edu/?p=quiz-info";
+
+/// Where a quiz's responses come from
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum QuizSource {
+    // The gid of the Google Sheet the form's responses go to
+    Sheet(String),
+    // Hosted by the server, see `QuizBanks`
+    Native,
+}
+
+impl Default for QuizSource {
+    fn default() -> Self {
+        QuizSource::Native
+    }
+}
+
+/// Every quiz the makerspace has, from quizzes.toml and the admin API
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct QuizRegistry {
+    pub quizzes: Vec<QuizDefinition>,
+}
+
+/// A quiz, what has to be passed before it, and what passing it gives access to.
+/// Quizzes with `valid_days` have to be retaken that long after they were passed,
+/// and users are emailed `reminder_days` (default 14) before that with `link`.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct QuizDefinition {
+    pub id: QuizName,
+    pub display_name: String,
+    pub source: QuizSource,
+    pub prerequisites: Vec<QuizName>,
+    pub swipe_groups: Vec<SwipeGroup>,
+    pub machines: Vec<String>,
+    pub valid_days: Option<u64>,
+    pub reminder_days: Option<u64>,
+    pub link: Option<String>,
+}
+
+/// A certification that expires soon, for reminder emails
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct ExpiringCertification {
+    pub college_id: u64,
+    pub quiz: QuizDefinition,
+    pub timestamp_expires: u64,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct QuizConfig {
+    quizzes: Vec<QuizDefinition>,
+}
+
+impl QuizRegistry {
+    /// Adds or resets the quizzes listed in quizzes.toml, leaving quizzes added by the API alone
+    pub fn load_config(&mut self) {
+        let file = std::fs::File::open(QUIZ_CONFIG);
+
+        if file.is_err() {
+            warn!("No {} found, keeping the saved quizzes", QUIZ_CONFIG);
+            return;
+        }
+
+        let mut contents = String::new();
+        file.unwrap().read_to_string(&mut contents).unwrap();
+        let config: QuizConfig = toml::from_str(&contents).unwrap();
+
+        for quiz in config.quizzes {
+            let result = self.set_quiz(quiz.clone());
+
+            if result.is_err() {
+                warn!("Skipping quiz {} in {}: {}", quiz.id, QUIZ_CONFIG, result.unwrap_err());
+            }
+        }
+
+        info!("{} quizzes in the registry", self.quizzes.len());
+    }
+
+    pub fn get_quiz(&self, id: &QuizName) -> Option<QuizDefinition> {
+        self.quizzes.iter().find(|x| &x.id == id).cloned()
+    }
+
+    pub fn exists(&self, id: &QuizName) -> bool {
+        self.quizzes.iter().any(|x| &x.id == id)
+    }
+
+    /// Adds or replaces a quiz, checking its prerequisites exist and don't loop back to it
+    pub fn set_quiz(&mut self, mut quiz: QuizDefinition) -> Result<QuizDefinition, String> {
+        if quiz.id.as_str().is_empty() {
+            return Err("Quiz must have an ID".to_string());
+        }
+
+        if quiz.valid_days == Some(0) {
+            return Err("A quiz can't expire the day it's passed, leave valid_days out instead".to_string());
+        }
+
+        if quiz.display_name.trim().is_empty() {
+            quiz.display_name = quiz.id.to_string();
+        }
+
+        for prerequisite in quiz.prerequisites.iter() {
+            if prerequisite == &quiz.id {
+                return Err(format!("{} can't be its own prerequisite", quiz.id));
+            }
+
+            if !self.exists(prerequisite) {
+                return Err(format!("No quiz found with id {}", prerequisite));
+            }
+
+            if self.get_all_prerequisites(prerequisite).contains(&quiz.id) {
+                return Err(format!("{} already needs {}", prerequisite, quiz.id));
+            }
+        }
+
+        let pos = self.quizzes.iter().position(|x| x.id == quiz.id);
+
+        if let Some(pos) = pos {
+            self.quizzes[pos] = quiz.clone();
+        } else {
+            info!("Adding quiz {}", quiz.id);
+            self.quizzes.push(quiz.clone());
+        }
+
+        Ok(quiz)
+    }
+
+    /// Removes a quiz. Users keep it in their passed quizzes, it just stops meaning anything.
+    pub fn delete_quiz(&mut self, id: &QuizName) -> Result<QuizDefinition, String> {
+        if let Some(dependent) = self.quizzes.iter().find(|x| x.prerequisites.contains(id)) {
+            return Err(format!("{} needs {}, remove it from there first", dependent.id, id));
+        }
+
+        let pos = self
+            .quizzes
+            .iter()
+            .position(|x| &x.id == id)
+            .ok_or(format!("No quiz found with id {}", id))?;
+
+        info!("Removing quiz {}", id);
+
+        Ok(self.quizzes.remove(pos))
+    }
+
+    /// Every quiz that has to be passed before this one, including their prerequisites
+    pub fn get_all_prerequisites(&self, id: &QuizName) -> Vec<QuizName> {
+        let mut found: Vec<QuizName> = Vec::new();
+        let mut to_check = vec![id.clone()];
+
+        while let Some(next) = to_check.pop() {
+            if let Some(quiz) = self.get_quiz(&next) {
+                for prerequisite in quiz.prerequisites {
+                    if !found.contains(&prerequisite) {
+                        found.push(prerequisite.clone());
+                        to_check.push(prerequisite);
+                    }
+                }
+            }
+        }
+
+        found
+    }
+
+    /// When a user's certification for a quiz runs out, if the quiz expires
+    pub fn get_expiry(&self, id: &QuizName, user: &User) -> Option<u64> {
+        let valid_days = self.get_quiz(id)?.valid_days?;
+        let certification = user.get_certification(id)?;
+
+        Some(certification.timestamp_passed + valid_days * DAY)
+    }
+
+    /// The user's passed quizzes, leaving out any that have expired.
+    /// Use this rather than `User::get_passed_quizzes` to decide what someone can use.
+    pub fn get_valid_quizzes(&self, user: &User) -> Vec<QuizName> {
+        let now = now();
+
+        user.get_passed_quizzes()
+            .into_iter()
+            .filter(|x| self.get_expiry(x, user).map_or(true, |expiry| expiry > now))
+            .collect()
+    }
+
+    /// Passed quizzes that have expired
+    pub fn get_expired_quizzes(&self, user: &User) -> Vec<QuizName> {
+        let valid = self.get_valid_quizzes(user);
+
+        user.get_passed_quizzes()
+            .into_iter()
+            .filter(|x| !valid.contains(x))
+            .collect()
+    }
+
+    /// Every user with expired quizzes taken out of their passed quizzes, for the CIS swipe system
+    pub fn remove_expired(&self, users: &Users) -> Users {
+        let mut valid_users = Users::default();
+
+        for mut user in users.get_users() {
+            user.set_passed_quizzes(self.get_valid_quizzes(&user));
+            valid_users.add_set_user(user);
+        }
+
+        valid_users
+    }
+
+    /// Certifications inside their reminder window that haven't been reminded about yet
+    pub fn get_expiring(&self, users: &Users) -> Vec<ExpiringCertification> {
+        let now = now();
+        let mut expiring = Vec::new();
+
+        for user in users.get_users() {
+            for id in self.get_valid_quizzes(&user) {
+                let expiry = self.get_expiry(&id, &user);
+                let certification = user.get_certification(&id);
+
+                if expiry.is_none() || certification.is_none() || certification.unwrap().reminder_sent {
+                    continue;
+                }
+
+                let quiz = self.get_quiz(&id).unwrap();
+                let reminder_days = quiz.reminder_days.unwrap_or(DEFAULT_REMINDER_DAYS);
+
+                if expiry.unwrap() <= now + reminder_days * DAY {
+                    expiring.push(ExpiringCertification {
+                        college_id: user.get_id(),
+                        quiz,
+                        timestamp_expires: expiry.unwrap(),
+                    });
+                }
+            }
+        }
+
+        expiring
+    }
+
+    /// Prerequisites of a quiz the user hasn't passed yet, or whose pass has expired
+    pub fn get_missing_prerequisites(&self, id: &QuizName, user: &User) -> Vec<QuizName> {
+        let passed = self.get_valid_quizzes(user);
+
+        self.get_all_prerequisites(id)
+            .into_iter()
+            .filter(|x| !passed.contains(x))
+            .collect()
+    }
+
+    /// Quizzes whose responses are fetched from Google Sheets
+    pub fn get_sheet_quizzes(&self) -> Vec<Quiz> {
+        self.quizzes
+            .iter()
+            .filter_map(|x| match &x.source {
+                QuizSource::Sheet(gid) => Some(Quiz::new(gid, x.id.clone())),
+                QuizSource::Native => None,
+            })
+            .collect()
+    }
+
+    /// Quizzes a user needs to use a machine
+    pub fn get_quizzes_for_machine(&self, machine: &str) -> Vec<QuizName> {
+        self.quizzes
+            .iter()
+            .filter(|x| x.machines.iter().any(|m| m.eq_ignore_ascii_case(machine)))
+            .map(|x| x.id.clone())
+            .collect()
+    }
+
+    /// Swipe groups a user gets from the quizzes they've passed and haven't expired
+    pub fn get_swipe_groups(&self, user: &User) -> Vec<SwipeGroup> {
+        let passed = self.get_valid_quizzes(user);
+        let mut groups: Vec<SwipeGroup> = Vec::new();
+
+        for quiz in self.quizzes.iter().filter(|x| passed.contains(&x.id)) {
+            for group in quiz.swipe_groups.iter() {
+                if !groups.contains(group) {
+                    groups.push(group.clone());
+                }
+            }
+        }
+
+        groups
+    }
+}
+
+/// Emails everyone whose certifications are about to expire a link to retake the quiz
+pub async fn send_certification_reminders() {
+    let data = MEMORY_DATABASE.lock().await;
+    let expiring = data.quiz_registry.get_expiring(&data.users);
+    let users = data.users.clone();
+    drop(data);
+
+    for certification in expiring.iter() {
+        let user = users.get_user_by_id(&certification.college_id).unwrap();
+
+        if user.get_email().is_empty() {
+            continue;
+        }
+
+        let expiry_date = chrono::NaiveDateTime::from_timestamp_opt(certification.timestamp_expires as i64, 0)
+            .map(|x| x.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+
+        let link = certification
+            .quiz
+            .link
+            .clone()
+            .unwrap_or(DEFAULT_QUIZ_LINK.to_string());
+
+        info!(
+            "Reminding {} their {} certification expires {}",
+            user.get_id(),
+            certification.quiz.id,
+            expiry_date
+        );
+
+        let result = send_individual_email(
+            user.get_email(),
+            None,
+            format!("Your {} certification expires soon", certification.quiz.display_name),
+            EMAIL_TEMPLATES.lock().await.get_certification_expiring(
+                &user.get_name(),
+                &certification.quiz.display_name,
+                &expiry_date,
+                &link,
+            ),
+        )
+        .await;
+
+        if result.is_err() {
+            warn!("Failed to send certification reminder to {}", user.get_email());
+            continue;
+        }
+
+        // Mark it here rather than before sending so a failed email is tried again later
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        if let Some(mut user) = data.users.get_user_by_id(&certification.college_id) {
+            user.set_reminder_sent(&certification.quiz.id);
+            data.users.add_set_user(user);
+        }
+    }
+}
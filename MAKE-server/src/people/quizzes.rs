@@ -1,32 +1,78 @@
-use std::time::SystemTime;
-
+use chrono::{Local, TimeZone};
 use log::info;
 use serde::{Deserialize, Serialize};
 
+use crate::now;
+
 const BASE_QUIZ_URL: &str = "https://docs.google.com/spreadsheets/d/e/2PACX-1vRyOdR5ZzocTVLi02rPVQPVwoGyuPrGmULHznFB66pDnqsWrCWVTi5JM5KCbBn8oMVLa-vwIS3RvK6z/pub?gid=";
 const POST_QUIZ_URL: &str = "&single=true&output=csv";
 
-const GENERAL: &str = "66546920";
-const LASER3D: &str = "1524924728";
-const SPRAY_PAINT: &str = "1841312496";
-const COMPOSITE: &str = "913890505";
-const WELDING: &str = "482685426";
-const STUDIO: &str = "2079405017";
-const WATERJET: &str = "2100779718";
-
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd, Eq, Ord)]
-pub enum QuizName {
-    General,
-    Laser3D,
-    SprayPaint,
-    Composite,
-    Welding,
-    Studio,
-    Waterjet
+/// The ID of a quiz in the registry, eg "Welding".
+///
+/// Quizzes used to be a fixed enum, which serialized as the variant name, so IDs
+/// serialize as a plain string and saved `passed_quizzes` load unchanged.
+/// IDs compare ignoring case, spaces and punctuation, so "Spray Paint" matches "SprayPaint".
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(transparent)]
+pub struct QuizName(String);
+
+impl QuizName {
+    pub fn new(id: &str) -> Self {
+        QuizName(id.trim().to_string())
+    }
+
+    /// The quiz everyone has to pass before using the makerspace
+    pub fn general() -> Self {
+        QuizName::new("General")
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn key(&self) -> String {
+        self.0
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    }
 }
 
 impl Default for QuizName {
-    fn default() -> Self { QuizName::General }
+    fn default() -> Self { QuizName::general() }
+}
+
+impl std::fmt::Display for QuizName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl PartialEq for QuizName {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for QuizName {}
+
+impl std::hash::Hash for QuizName {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+impl PartialOrd for QuizName {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QuizName {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
 }
 
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
@@ -49,12 +95,9 @@ impl Quiz {
 
     pub async fn update(&mut self) -> Result<(), reqwest::Error> {
         // Get time as unix timestamp
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
+        let now = now();
 
-        let response = reqwest::get(format!("{}{}{}", BASE_QUIZ_URL, self.id, POST_QUIZ_URL))
+        let response = reqwest::get(get_quiz_url(&self.id))
             .await;
 
         if let Ok(response) = response {
@@ -68,10 +111,12 @@ impl Quiz {
 
             for result in rdr.into_records() {
                 if let Ok(result) = result {
-                    // Create new item
+                    // Create new item, skipping rows with missing columns
                     let response = Response::new_from_line(result.iter().map(|x| x.to_string()).collect());
 
-                    responses.push(response);
+                    if let Some(response) = response {
+                        responses.push(response);
+                    }
                 }
             }
 
@@ -79,7 +124,7 @@ impl Quiz {
             self.last_updated = now;
             self.responses = responses;
 
-            info!("Updated quiz {:20?} with {} passing / {} responses", self.name, self.responses.iter().filter(|x| x.passed).count(), self.responses.len());
+            info!("Updated quiz {:20} with {} passing / {} responses", self.name, self.responses.iter().filter(|x| x.passed).count(), self.responses.len());
             Ok(())
         } else {
             Err(response.unwrap_err())
@@ -109,14 +154,18 @@ pub struct Response {
 }
 
 impl Response {
-    pub fn new_from_line(line: Vec<String>) -> Self {
-        Response {
+    pub fn new_from_line(line: Vec<String>) -> Option<Self> {
+        if line.len() < 5 {
+            return None;
+        }
+
+        Some(Response {
             timestamp: line[0].clone(),
             passed: Response::determine_if_passed(line[1].clone()),
             name: line[2].trim().to_string(),
             college_id: Response::parse_college_id(line[3].clone()),
             college_email: line[4].trim().to_string(),
-        }
+        })
     }
 
     pub fn parse_college_id(id_str: String) -> u64 {
@@ -137,23 +186,28 @@ impl Response {
     pub fn determine_if_passed(score_str: String) -> bool {
         let score = score_str.split('/').collect::<Vec<&str>>();
 
+        // Malformed scores, eg an empty cell, never pass
+        if score.len() != 2 || score[0].trim().is_empty() {
+            return false;
+        }
+
         // Check if both sides equal each other, eg '100 / 100'
         score[0].trim() == score[1].trim()
     }
+
+    /// Parses the timestamp the sheet gives, eg "9/1/2023 14:22:01"
+    /// The sheet records local time, which is the same timezone the server runs in
+    pub fn get_timestamp(&self) -> Option<u64> {
+        let local = chrono::NaiveDateTime::parse_from_str(self.timestamp.trim(), "%m/%d/%Y %H:%M:%S").ok()?;
+
+        // Times repeated when the clocks go back take the first, times skipped when they go forward don't exist
+        Local.from_local_datetime(&local)
+            .earliest()
+            .map(|x| x.timestamp() as u64)
+    }
 }
 
-pub fn _get_quiz_url(quiz_id: &str) -> String {
+/// URL of the CSV export of a quiz's response sheet
+pub fn get_quiz_url(quiz_id: &str) -> String {
     format!("{}{}{}", BASE_QUIZ_URL, quiz_id, POST_QUIZ_URL)
 }
-
-pub fn get_all_quizzes() -> Vec<Quiz> {
-    vec![
-        Quiz::new(GENERAL, QuizName::General),
-        Quiz::new(LASER3D, QuizName::Laser3D),
-        Quiz::new(SPRAY_PAINT, QuizName::SprayPaint),
-        Quiz::new(COMPOSITE, QuizName::Composite),
-        Quiz::new(WELDING, QuizName::Welding),
-        Quiz::new(STUDIO, QuizName::Studio),
-        Quiz::new(WATERJET, QuizName::Waterjet),
-    ]
-}
\ No newline at end of file
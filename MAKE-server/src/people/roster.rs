@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::inventory::ImportReport;
+use crate::people::identities::Identities;
+use crate::people::privacy::Privacy;
+use crate::people::quizzes::Response;
+use crate::people::usage::College;
+use crate::people::users::{Affiliation, User, Users};
+
+/// A roster column, matched from the header the registrar's export uses
+#[derive(Debug, PartialEq, Clone)]
+pub enum RosterColumn {
+    Id,
+    Name,
+    FirstName,
+    LastName,
+    Email,
+    College,
+    Affiliation,
+    GraduationDate,
+}
+
+impl RosterColumn {
+    /// Matches a header case-insensitively, ignoring spaces and punctuation
+    pub fn from_header(header: &str) -> Option<Self> {
+        let header: String = header
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        match header.as_str() {
+            "id" | "collegeid" | "studentid" | "idnumber" | "cxid" => Some(RosterColumn::Id),
+            "name" | "fullname" => Some(RosterColumn::Name),
+            "firstname" | "first" | "preferredname" => Some(RosterColumn::FirstName),
+            "lastname" | "last" | "surname" => Some(RosterColumn::LastName),
+            "email" | "collegeemail" | "emailaddress" => Some(RosterColumn::Email),
+            "college" | "school" | "homecollege" | "campus" => Some(RosterColumn::College),
+            "affiliation" | "type" | "role" | "status" => Some(RosterColumn::Affiliation),
+            "graduationdate" | "graduation" | "gradyear" | "classyear" | "class" => Some(RosterColumn::GraduationDate),
+            _ => None,
+        }
+    }
+}
+
+/// Body of a JSON roster import, one object per person keyed by the same headers as the CSV
+pub type RosterJson = Vec<HashMap<String, serde_json::Value>>;
+
+/// Filters for the users API, eg `?affiliation=Student&college=HarveyMudd&graduated=false`
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct UserFilter {
+    pub affiliation: Option<Affiliation>,
+    pub college: Option<College>,
+    pub graduated: Option<bool>,
+}
+
+impl UserFilter {
+    pub fn matches(&self, user: &User) -> bool {
+        self.affiliation.as_ref().map_or(true, |x| x == &user.get_affiliation())
+            && self.college.as_ref().map_or(true, |x| x == &user.get_college())
+            && self.graduated.map_or(true, |x| x == user.has_graduated())
+    }
+
+    pub fn apply(&self, users: &Users) -> Users {
+        let mut filtered = Users::default();
+
+        for user in users.get_users().into_iter().filter(|x| self.matches(x)) {
+            filtered.add_set_user(user);
+        }
+
+        filtered
+    }
+}
+
+/// Imports a roster CSV with a header row
+pub fn import_roster_csv(
+    users: &mut Users,
+    identities: &Identities,
+    privacy: &Privacy,
+    csv_text: &str,
+) -> Result<ImportReport, String> {
+    let mut rdr = csv::Reader::from_reader(csv_text.as_bytes());
+
+    let columns: Vec<Option<RosterColumn>> = rdr
+        .headers()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(RosterColumn::from_header)
+        .collect();
+
+    if !columns.contains(&Some(RosterColumn::Id)) {
+        return Err("Roster has no ID column".to_string());
+    }
+
+    let mut rows = Vec::new();
+
+    for record in rdr.records() {
+        match record {
+            Ok(record) => rows.push(Ok(columns
+                .iter()
+                .cloned()
+                .zip(record.iter().map(|x| x.to_string()))
+                .collect())),
+            Err(e) => rows.push(Err(e.to_string())),
+        }
+    }
+
+    // Row numbers match the spreadsheet, after the header row
+    Ok(import_rows(users, identities, privacy, rows, 2))
+}
+
+/// Imports a roster from JSON objects, numbers and strings are both accepted
+pub fn import_roster_json(
+    users: &mut Users,
+    identities: &Identities,
+    privacy: &Privacy,
+    roster: RosterJson,
+) -> Result<ImportReport, String> {
+    let rows = roster
+        .into_iter()
+        .map(|person| {
+            Ok(person
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        serde_json::Value::String(x) => x,
+                        serde_json::Value::Null => String::new(),
+                        other => other.to_string(),
+                    };
+
+                    (RosterColumn::from_header(&key), value)
+                })
+                .collect())
+        })
+        .collect();
+
+    Ok(import_rows(users, identities, privacy, rows, 1))
+}
+
+fn import_rows(
+    users: &mut Users,
+    identities: &Identities,
+    privacy: &Privacy,
+    rows: Vec<Result<Vec<(Option<RosterColumn>, String)>, String>>,
+    first_row: usize,
+) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    for (i, row) in rows.into_iter().enumerate() {
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                report.skipped.push(format!("Row {}: {}", i + first_row, e));
+                continue;
+            }
+        };
+
+        let get = |column: RosterColumn| {
+            row.iter()
+                .find(|(x, _)| x.as_ref() == Some(&column))
+                .map(|(_, value)| value.trim().to_string())
+                .filter(|x| !x.is_empty())
+        };
+
+        let id = get(RosterColumn::Id).map(Response::parse_college_id).unwrap_or(0);
+
+        if id == 0 {
+            report.skipped.push(format!("Row {}: no ID", i + first_row));
+            continue;
+        }
+
+        // Corrections from merges and re-keys apply to the roster too
+        let id = identities.resolve_id(id);
+
+        // Deleted users stay deleted, even though the registrar still lists them
+        if privacy.is_anonymized(&id) {
+            report.skipped.push(format!("Row {}: user's data was deleted", i + first_row));
+            continue;
+        }
+
+        let college = get(RosterColumn::College).map(|x| (College::parse(&x), x));
+        let affiliation = get(RosterColumn::Affiliation).map(|x| (Affiliation::parse(&x), x));
+        let graduation_date = get(RosterColumn::GraduationDate).map(|x| (parse_graduation_date(&x), x));
+
+        if let Some((None, value)) = &college {
+            report.skipped.push(format!("Row {}: unknown college {}", i + first_row, value));
+            continue;
+        }
+
+        if let Some((None, value)) = &affiliation {
+            report.skipped.push(format!("Row {}: unknown affiliation {}", i + first_row, value));
+            continue;
+        }
+
+        if let Some((None, value)) = &graduation_date {
+            report.skipped.push(format!("Row {}: unknown graduation date {}", i + first_row, value));
+            continue;
+        }
+
+        let name = get(RosterColumn::Name).or(match (get(RosterColumn::FirstName), get(RosterColumn::LastName)) {
+            (Some(first), Some(last)) => Some(format!("{} {}", first, last)),
+            (first, last) => first.or(last),
+        });
+
+        let existing = users.get_user_by_id(&id);
+        let is_new = existing.is_none();
+
+        let mut user = existing.unwrap_or(User::from_response(&Response {
+            college_id: id,
+            ..Default::default()
+        }));
+
+        // The roster is the source of truth for names and emails, but blanks don't erase anything
+        if let Some(name) = name {
+            user.set_name(&name);
+        }
+
+        if let Some(email) = get(RosterColumn::Email) {
+            user.set_email(&email);
+        }
+
+        if let Some((Some(college), _)) = college {
+            user.set_college(college);
+        }
+
+        if let Some((Some(affiliation), _)) = affiliation {
+            user.set_affiliation(affiliation);
+        }
+
+        if let Some((Some(graduation_date), _)) = graduation_date {
+            user.set_graduation_date(Some(graduation_date));
+        }
+
+        if is_new {
+            report.created.push(id.to_string());
+        } else {
+            report.updated.push(id.to_string());
+        }
+
+        users.add_set_user(user);
+    }
+
+    info!(
+        "Imported roster: {} created, {} updated, {} skipped",
+        report.created.len(),
+        report.updated.len(),
+        report.skipped.len()
+    );
+
+    report
+}
+
+/// Parses a graduation date, eg "2026-05-16" or "5/16/2026".
+/// A class year on its own, eg "2026", is taken as the end of May that year.
+pub fn parse_graduation_date(date: &str) -> Option<u64> {
+    let date = date.trim();
+
+    let parsed = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .or(chrono::NaiveDate::parse_from_str(date, "%m/%d/%Y"))
+        .ok()
+        .or(date
+            .parse::<i32>()
+            .ok()
+            .filter(|x| (1900..3000).contains(x))
+            .and_then(|x| chrono::NaiveDate::from_ymd_opt(x, 5, 31)));
+
+    parsed
+        .and_then(|x| x.and_hms_opt(0, 0, 0))
+        .map(|x| x.timestamp() as u64)
+}
@@ -0,0 +1,256 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::inventory::inventory::Inventory;
+use crate::now;
+use crate::people::quiz_registry::QuizRegistry;
+use crate::people::quizzes::QuizName;
+use crate::people::users::{AuthLevel, User, Users};
+
+/// Skills that need a hands-on demo from a steward, and who's been signed off on them
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SignOffs {
+    pub skills: Vec<Skill>,
+    pub records: Vec<SignOff>,
+}
+
+/// Something a steward has to sign a user off on, eg "Lathe".
+/// `prerequisites` are the quizzes a user must have passed before they can be signed off,
+/// and `machines` and `items` (inventory item IDs) are what the sign-off is needed for.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Skill {
+    pub id: String,
+    pub display_name: String,
+    pub prerequisites: Vec<QuizName>,
+    pub machines: Vec<String>,
+    pub items: Vec<String>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SignOff {
+    pub id: String,
+    pub college_id: u64,
+    pub skill: String,
+    pub steward_id: u64,
+    pub steward_name: String,
+    pub notes: String,
+    pub timestamp: u64,
+    pub revoked: bool,
+}
+
+/// Body of a sign-off request from the management kiosk.
+/// The steward is whoever is signed in, never someone named in the body.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SignOffRequest {
+    pub college_id: u64,
+    pub skill: String,
+    pub notes: String,
+}
+
+impl SignOffs {
+    pub fn get_skill(&self, id: &str) -> Option<Skill> {
+        self.skills.iter().find(|x| x.id.eq_ignore_ascii_case(id)).cloned()
+    }
+
+    /// Adds or replaces a skill, checking its prerequisites and items exist
+    pub fn set_skill(&mut self, mut skill: Skill, registry: &QuizRegistry, inventory: &Inventory) -> Result<Skill, String> {
+        skill.id = skill.id.trim().to_string();
+
+        if skill.id.is_empty() {
+            return Err("Skill must have an ID".to_string());
+        }
+
+        if skill.display_name.trim().is_empty() {
+            skill.display_name = skill.id.clone();
+        }
+
+        if let Some(quiz) = skill.prerequisites.iter().find(|x| !registry.exists(x)) {
+            return Err(format!("No quiz found with id {}", quiz));
+        }
+
+        if let Some(item) = skill.items.iter().find(|x| inventory.get_item_by_id(x).is_none()) {
+            return Err(format!("No item found with id {}", item));
+        }
+
+        let pos = self.skills.iter().position(|x| x.id.eq_ignore_ascii_case(&skill.id));
+
+        if let Some(pos) = pos {
+            self.skills[pos] = skill.clone();
+        } else {
+            info!("Adding skill {}", skill.id);
+            self.skills.push(skill.clone());
+        }
+
+        Ok(skill)
+    }
+
+    /// Removes a skill. Sign-offs for it are kept, they just stop being needed.
+    pub fn delete_skill(&mut self, id: &str) -> Result<Skill, String> {
+        let pos = self
+            .skills
+            .iter()
+            .position(|x| x.id.eq_ignore_ascii_case(id))
+            .ok_or(format!("No skill found with id {}", id))?;
+
+        info!("Removing skill {}", id);
+
+        Ok(self.skills.remove(pos))
+    }
+
+    /// Records a steward signing a user off on a skill.
+    /// The steward must be a steward or above, and the user must have passed the skill's quizzes.
+    pub fn sign_off(
+        &mut self,
+        request: SignOffRequest,
+        steward_id: Option<u64>,
+        users: &Users,
+        registry: &QuizRegistry,
+    ) -> Result<SignOff, String> {
+        let skill = self
+            .get_skill(&request.skill)
+            .ok_or(format!("No skill found with id {}", request.skill))?;
+
+        let steward_id = steward_id.ok_or("Sign-offs have to come from a steward signed in with their user token")?;
+
+        let steward = users
+            .get_user_by_id(&steward_id)
+            .ok_or("Steward not found".to_string())?;
+
+        if steward.get_auth_level() < AuthLevel::Steward {
+            return Err(format!("{} is not a steward", steward.get_name()));
+        }
+
+        let user = users
+            .get_user_by_id(&request.college_id)
+            .ok_or("User not found".to_string())?;
+
+        if user.get_id() == steward.get_id() {
+            return Err("Stewards can't sign themselves off".to_string());
+        }
+
+        let passed = registry.get_valid_quizzes(&user);
+        let missing: Vec<String> = skill
+            .prerequisites
+            .iter()
+            .filter(|x| !passed.contains(x))
+            .map(|x| x.to_string())
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(format!(
+                "{} needs to pass {} before being signed off",
+                user.get_name(),
+                missing.join(", ")
+            ));
+        }
+
+        let sign_off = SignOff {
+            id: Uuid::new_v4().to_string(),
+            college_id: user.get_id(),
+            skill: skill.id.clone(),
+            steward_id: steward.get_id(),
+            steward_name: steward.get_name(),
+            notes: request.notes,
+            timestamp: now(),
+            revoked: false,
+        };
+
+        info!("{} signed off {} on {}", steward.get_name(), user.get_id(), skill.id);
+
+        self.records.push(sign_off.clone());
+
+        Ok(sign_off)
+    }
+
+    /// Takes back a sign-off, eg if a steward sees someone using the machine unsafely
+    pub fn revoke(&mut self, id: &str) -> Result<SignOff, String> {
+        let sign_off = self
+            .records
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or(format!("No sign-off found with id {}", id))?;
+
+        info!("Revoking sign-off of {} on {}", sign_off.college_id, sign_off.skill);
+
+        sign_off.revoked = true;
+
+        Ok(sign_off.clone())
+    }
+
+    /// Moves sign-offs given to or by one college ID to another, for fixing identities
+    pub fn rekey_user(&mut self, from: &u64, to: &u64) -> usize {
+        let mut moved = 0;
+
+        for record in self.records.iter_mut() {
+            if &record.college_id == from {
+                record.college_id = *to;
+                moved += 1;
+            }
+
+            if &record.steward_id == from {
+                record.steward_id = *to;
+            }
+        }
+
+        moved
+    }
+
+    pub fn get_sign_offs_for_id(&self, college_id: &u64) -> Vec<SignOff> {
+        self.records
+            .iter()
+            .filter(|x| &x.college_id == college_id)
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_signed_off(&self, college_id: &u64, skill: &str) -> bool {
+        self.records
+            .iter()
+            .any(|x| &x.college_id == college_id && x.skill.eq_ignore_ascii_case(skill) && !x.revoked)
+    }
+
+    /// Skills a user still needs a sign-off on to use a machine
+    pub fn get_missing_for_machine(&self, machine: &str, user: &User) -> Vec<String> {
+        self.skills
+            .iter()
+            .filter(|x| x.machines.iter().any(|m| m.eq_ignore_ascii_case(machine)))
+            .filter(|x| !self.is_signed_off(&user.get_id(), &x.id))
+            .map(|x| x.display_name.clone())
+            .collect()
+    }
+
+    /// Checks the user has been signed off on everything the named items need, for checkouts.
+    /// Gives an error listing what's missing for each item.
+    pub fn check_items(&self, names: &Vec<String>, inventory: &Inventory, user: &User) -> Result<(), String> {
+        let missing: Vec<String> = names
+            .iter()
+            .filter_map(|name| inventory.get_item_by_name(name))
+            .filter_map(|item| {
+                let missing: Vec<String> = self
+                    .skills
+                    .iter()
+                    .filter(|x| x.items.contains(&item.id))
+                    .filter(|x| !self.is_signed_off(&user.get_id(), &x.id))
+                    .map(|x| x.display_name.clone())
+                    .collect();
+
+                if missing.is_empty() {
+                    None
+                } else {
+                    Some(format!("{} needs {}", item.name, missing.join(", ")))
+                }
+            })
+            .collect();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Missing sign-offs: {}", missing.join("; ")))
+        }
+    }
+}
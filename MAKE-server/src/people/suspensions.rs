@@ -0,0 +1,333 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::management::emails::send_individual_email;
+use crate::people::users::{User, Users};
+use crate::{now, EMAIL_TEMPLATES, MEMORY_DATABASE};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// What a suspension keeps someone from
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum SuspensionScope {
+    #[default]
+    All,
+    // Item checkouts, reservations and student storage
+    Checkouts,
+    // Machine interlocks and the print queue
+    Machines,
+}
+
+impl SuspensionScope {
+    pub fn covers(&self, access: &Access) -> bool {
+        match self {
+            SuspensionScope::All => true,
+            SuspensionScope::Checkouts => matches!(access, Access::Checkouts | Access::Storage),
+            SuspensionScope::Machines => access == &Access::Machines,
+        }
+    }
+
+    pub fn describe(&self) -> &str {
+        match self {
+            SuspensionScope::All => "the makerspace",
+            SuspensionScope::Checkouts => "checkouts and storage",
+            SuspensionScope::Machines => "machines",
+        }
+    }
+}
+
+/// What a user is trying to use, for `Suspensions::check_access`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Access {
+    // Getting in the door, ie swipe access
+    Space,
+    Checkouts,
+    Storage,
+    Machines,
+}
+
+/// A temporary ban. Suspensions without `timestamp_expires` last until an admin lifts them.
+/// Nothing is ever removed, so they double as the user's history.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Suspension {
+    pub id: String,
+    pub college_id: u64,
+    pub scope: SuspensionScope,
+    pub reason: String,
+    pub issued_by: u64,
+    pub timestamp_issued: u64,
+    pub timestamp_expires: Option<u64>,
+    pub timestamp_lifted: Option<u64>,
+    pub lifted_by: Option<u64>,
+    pub notified_ended: bool,
+}
+
+/// Body of a request to suspend someone, `days` is left out for a suspension that lasts until it's lifted
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SuspensionRequest {
+    pub college_id: u64,
+    pub scope: SuspensionScope,
+    pub reason: String,
+    pub days: Option<u64>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Suspensions {
+    pub suspensions: Vec<Suspension>,
+}
+
+impl Suspension {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.timestamp_lifted.is_none() && self.timestamp_expires.map_or(true, |x| now < x)
+    }
+
+    /// Why someone can't do something, eg "User is suspended from machines until 2026-10-20 18:00 UTC: ..."
+    pub fn describe(&self) -> String {
+        let until = match self.timestamp_expires {
+            Some(expires) => format!(" until {}", format_time(expires)),
+            None => String::new(),
+        };
+
+        format!("User is suspended from {}{}: {}", self.scope.describe(), until, self.reason)
+    }
+
+    /// Emails the user that they've been suspended, and why
+    pub async fn notify(&self, user: &User) {
+        if user.get_email().is_empty() {
+            return;
+        }
+
+        let until = self
+            .timestamp_expires
+            .map(format_time)
+            .unwrap_or("an admin lifts it".to_string());
+
+        let result = send_individual_email(
+            user.get_email(),
+            None,
+            "MAKE Suspension".to_string(),
+            EMAIL_TEMPLATES.lock().await.get_suspension(
+                &user.get_name(),
+                self.scope.describe(),
+                &self.reason,
+                &until,
+            ),
+        )
+        .await;
+
+        if result.is_err() {
+            warn!("Failed to send suspension email to {}", user.get_email());
+        }
+    }
+
+    /// Emails the user that their suspension is over, returning whether it was sent
+    pub async fn notify_ended(&self, user: &User) -> bool {
+        if user.get_email().is_empty() {
+            return true;
+        }
+
+        let result = send_individual_email(
+            user.get_email(),
+            None,
+            "MAKE Suspension Ended".to_string(),
+            EMAIL_TEMPLATES
+                .lock()
+                .await
+                .get_suspension_ended(&user.get_name(), self.scope.describe()),
+        )
+        .await;
+
+        result.is_ok()
+    }
+}
+
+impl Suspensions {
+    pub fn suspend(
+        &mut self,
+        request: SuspensionRequest,
+        issued_by: Option<u64>,
+        users: &Users,
+    ) -> Result<Suspension, String> {
+        if !users.exists(&request.college_id) {
+            return Err(format!("No user found with ID {}", request.college_id));
+        }
+
+        if request.reason.trim().is_empty() {
+            return Err("Suspensions need a reason".to_string());
+        }
+
+        if request.days == Some(0) {
+            return Err("A suspension has to last at least a day, leave days out to suspend until lifted".to_string());
+        }
+
+        let issued_by = issued_by.ok_or("Suspensions have to come from an admin signed in with their user token")?;
+
+        let suspension = Suspension {
+            id: Uuid::new_v4().to_string(),
+            college_id: request.college_id,
+            scope: request.scope,
+            reason: request.reason.trim().to_string(),
+            issued_by,
+            timestamp_issued: now(),
+            timestamp_expires: request.days.map(|x| now() + x * DAY),
+            ..Default::default()
+        };
+
+        info!("Suspending {} from {}", suspension.college_id, suspension.scope.describe());
+
+        self.suspensions.push(suspension.clone());
+
+        Ok(suspension)
+    }
+
+    /// Suspends someone from everything for reaching the strike limit, until an admin lifts it.
+    /// `issued_by` is whoever filed or updated the incident, or 0 if they weren't signed in.
+    pub fn suspend_for_strikes(&mut self, college_id: u64, strikes: u64, issued_by: u64) -> Suspension {
+        let suspension = Suspension {
+            id: Uuid::new_v4().to_string(),
+            college_id,
+            scope: SuspensionScope::All,
+            reason: format!("Reached the limit of {} incident strikes", strikes),
+            issued_by,
+            timestamp_issued: now(),
+            ..Default::default()
+        };
+
+        info!("Suspending {} after {} strikes", college_id, strikes);
+
+        self.suspensions.push(suspension.clone());
+
+        suspension
+    }
+
+    /// Ends a suspension early. It's kept, with who lifted it and when.
+    pub fn lift(&mut self, id: &str, lifted_by: Option<u64>) -> Result<Suspension, String> {
+        let suspension = self
+            .suspensions
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or(format!("No suspension found with id {}", id))?;
+
+        if !suspension.is_active(now()) {
+            return Err("Suspension has already ended".to_string());
+        }
+
+        info!("Lifting suspension of {}", suspension.college_id);
+
+        suspension.timestamp_lifted = Some(now());
+        suspension.lifted_by = lifted_by;
+
+        Ok(suspension.clone())
+    }
+
+    pub fn get_suspension(&self, id: &str) -> Option<Suspension> {
+        self.suspensions.iter().find(|x| x.id == id).cloned()
+    }
+
+    /// Every suspension, newest first
+    pub fn get_all(&self) -> Vec<Suspension> {
+        self.suspensions.iter().rev().cloned().collect()
+    }
+
+    pub fn get_for_id(&self, college_id: &u64) -> Vec<Suspension> {
+        self.suspensions
+            .iter()
+            .rev()
+            .filter(|x| &x.college_id == college_id)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_active_for_id(&self, college_id: &u64) -> Vec<Suspension> {
+        let now = now();
+
+        self.suspensions
+            .iter()
+            .filter(|x| &x.college_id == college_id && x.is_active(now))
+            .cloned()
+            .collect()
+    }
+
+    /// The one check for whether someone can use part of the makerspace right now.
+    /// Banned and graduated users can't use anything, and suspensions cover their scope.
+    pub fn check_access(&self, user: &User, access: &Access) -> Result<(), String> {
+        user.check_active()?;
+
+        let suspension = self
+            .get_active_for_id(&user.get_id())
+            .into_iter()
+            .find(|x| x.scope.covers(access));
+
+        if let Some(suspension) = suspension {
+            return Err(suspension.describe());
+        }
+
+        Ok(())
+    }
+
+    /// Moves suspensions to a new college ID, for merges, re-keys and anonymization
+    pub fn rekey_user(&mut self, from: &u64, to: &u64) -> usize {
+        let mut moved = 0;
+
+        for suspension in self.suspensions.iter_mut() {
+            if &suspension.college_id == from {
+                suspension.college_id = *to;
+                moved += 1;
+            }
+
+            if &suspension.issued_by == from {
+                suspension.issued_by = *to;
+            }
+
+            if suspension.lifted_by.as_ref() == Some(from) {
+                suspension.lifted_by = Some(*to);
+            }
+        }
+
+        moved
+    }
+}
+
+/// Lets people know when their suspensions run out
+pub async fn send_suspension_endings() {
+    let data = MEMORY_DATABASE.lock().await;
+    let now = now();
+    let ended: Vec<Suspension> = data
+        .suspensions
+        .suspensions
+        .iter()
+        .filter(|x| !x.notified_ended && !x.is_active(now))
+        .cloned()
+        .collect();
+    let users = data.users.clone();
+    drop(data);
+
+    for suspension in ended.iter() {
+        // Anyone who's gone doesn't need telling
+        let sent = match users.get_user_by_id(&suspension.college_id) {
+            Some(user) => suspension.notify_ended(&user).await,
+            None => true,
+        };
+
+        if !sent {
+            warn!("Failed to send suspension ended email to {}", suspension.college_id);
+            continue;
+        }
+
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        if let Some(x) = data.suspensions.suspensions.iter_mut().find(|x| x.id == suspension.id) {
+            x.notified_ended = true;
+        }
+    }
+}
+
+fn format_time(timestamp: u64) -> String {
+    chrono::NaiveDateTime::from_timestamp_opt(timestamp as i64, 0)
+        .map(|x| format!("{} UTC", x.format("%Y-%m-%d %H:%M")))
+        .unwrap_or_default()
+}
@@ -1,6 +1,6 @@
 use crate::*;
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum College {
     #[default]
     NA,
@@ -13,6 +13,29 @@ pub enum College {
     ClaremontGraduateUniversity,
 }
 
+impl College {
+    /// Matches a college's name or abbreviation, eg "Harvey Mudd College" or "HMC"
+    pub fn parse(college: &str) -> Option<Self> {
+        let college: String = college
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        match college.as_str() {
+            "hmc" | "harveymudd" | "harveymuddcollege" | "mudd" => Some(College::HarveyMudd),
+            "cmc" | "claremontmckenna" | "claremontmckennacollege" => Some(College::ClaremontMcKenna),
+            "scr" | "scripps" | "scrippscollege" => Some(College::Scripps),
+            "pz" | "pitzer" | "pitzercollege" => Some(College::Pitzer),
+            "po" | "pom" | "pomona" | "pomonacollege" => Some(College::Pomona),
+            "kgi" | "keck" | "keckgraduateinstitute" => Some(College::KeckGraduateInstitute),
+            "cgu" | "claremontgraduateuniversity" => Some(College::ClaremontGraduateUniversity),
+            "na" | "" => Some(College::NA),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 pub struct ButtonRecordLog {
     pub log: Vec<ButtonRecord>,
@@ -1,12 +1,14 @@
 use std::collections::HashMap;
-use std::time::SystemTime;
 use log::warn;
 use serde::{Deserialize, Serialize};
 
 use crate::inventory::checkout::*;
 
 use crate::machines::printers::PrintQueueEntry;
+use crate::now;
+use crate::people::identities::Identities;
 use crate::people::quizzes::*;
+use crate::people::usage::College;
 
 #[derive(Default, Debug, Deserialize, Serialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum AuthLevel {
@@ -18,6 +20,20 @@ pub enum AuthLevel {
     System,
 }
 
+impl AuthLevel {
+    pub fn parse(level: &str) -> Option<Self> {
+        match level.trim().to_lowercase().as_str() {
+            "banned" => Some(AuthLevel::Banned),
+            "user" => Some(AuthLevel::User),
+            "steward" => Some(AuthLevel::Steward),
+            "admin" => Some(AuthLevel::Admin),
+            "faculty" => Some(AuthLevel::Faculty),
+            "system" => Some(AuthLevel::System),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Default, Deserialize, Serialize, Clone)]
 #[serde(default)]
 pub struct Users {
@@ -41,6 +57,10 @@ impl Users {
         self.users.get(id_number).cloned()
     }
 
+    pub fn remove_user(&mut self, id_number: &u64) -> Option<User> {
+        self.users.remove(id_number)
+    }
+
     pub fn len(&self) -> usize {
         self.users.len()
     }
@@ -75,15 +95,82 @@ impl Users {
 
         None
     }
+
+    /// Gives certifications from before pass dates were kept a date of now,
+    /// so they start their validity period instead of expiring straight away
+    pub fn fill_missing_pass_dates(&mut self) {
+        let now = now();
+
+        for user in self.users.values_mut() {
+            for quiz in user.passed_quizzes.iter() {
+                user.certifications
+                    .entry(quiz.clone())
+                    .or_insert(Certification {
+                        timestamp_passed: now,
+                        reminder_sent: false,
+                    });
+            }
+        }
+    }
+
+    pub fn get_users(&self) -> Vec<User> {
+        self.users.values().cloned().collect()
+    }
+
+    pub fn get_users_with_auth_level(&self, auth_level: &AuthLevel) -> Vec<User> {
+        self.users
+            .values()
+            .filter(|x| &x.auth_level == auth_level)
+            .cloned()
+            .collect()
+    }
 }
 
-#[derive(Default, Deserialize, Serialize, Clone)]
+#[derive(Default, Debug, Deserialize, Serialize, Clone)]
 pub struct User {
     name: String,
     college_id: u64,
     college_email: String,
     passed_quizzes: Vec<QuizName>,
     auth_level: AuthLevel,
+    #[serde(default)]
+    certifications: HashMap<QuizName, Certification>,
+    #[serde(default)]
+    college: College,
+    #[serde(default)]
+    affiliation: Affiliation,
+    #[serde(default)]
+    graduation_date: Option<u64>,
+}
+
+/// How someone is connected to the colleges, from the roster
+#[derive(Default, Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum Affiliation {
+    #[default]
+    Unknown,
+    Student,
+    Faculty,
+    Staff,
+}
+
+impl Affiliation {
+    pub fn parse(affiliation: &str) -> Option<Self> {
+        match affiliation.trim().to_lowercase().as_str() {
+            "student" | "students" | "undergraduate" | "graduate" => Some(Affiliation::Student),
+            "faculty" | "professor" => Some(Affiliation::Faculty),
+            "staff" | "employee" => Some(Affiliation::Staff),
+            "unknown" | "" => Some(Affiliation::Unknown),
+            _ => None,
+        }
+    }
+}
+
+/// When a quiz in `passed_quizzes` was last passed, for quizzes that expire
+#[derive(Default, Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct Certification {
+    pub timestamp_passed: u64,
+    pub reminder_sent: bool,
 }
 
 impl User {
@@ -94,19 +181,65 @@ impl User {
             college_email: response.college_email.clone(),
             passed_quizzes: vec![],
             auth_level: AuthLevel::User,
+            certifications: HashMap::new(),
+            college: College::NA,
+            affiliation: Affiliation::Unknown,
+            graduation_date: None,
         }
     }
 
-    pub fn log_quiz(&mut self, quiz_name: QuizName, passed: bool) {
+    pub fn log_quiz(&mut self, quiz_name: QuizName, passed: bool, timestamp: Option<u64>) {
         if passed {
+            if let Some(timestamp) = timestamp {
+                self.record_pass_date(&quiz_name, timestamp);
+            }
+
             self.passed_quizzes.push(quiz_name)
         };
     }
 
+    /// Keeps the latest pass date, so retaking a quiz renews it
+    fn record_pass_date(&mut self, quiz_name: &QuizName, timestamp: u64) {
+        let current = self.certifications.get(quiz_name);
+
+        if current.is_none() || current.unwrap().timestamp_passed < timestamp {
+            self.certifications.insert(
+                quiz_name.clone(),
+                Certification {
+                    timestamp_passed: timestamp,
+                    reminder_sent: false,
+                },
+            );
+        }
+    }
+
+    pub fn set_passed_quizzes(&mut self, quizzes: Vec<QuizName>) {
+        self.passed_quizzes = quizzes;
+    }
+
+    pub fn get_certification(&self, quiz_name: &QuizName) -> Option<Certification> {
+        self.certifications.get(quiz_name).cloned()
+    }
+
+    pub fn get_certifications(&self) -> HashMap<QuizName, Certification> {
+        self.certifications.clone()
+    }
+
+    pub fn set_reminder_sent(&mut self, quiz_name: &QuizName) {
+        if let Some(certification) = self.certifications.get_mut(quiz_name) {
+            certification.reminder_sent = true;
+        }
+    }
+
     pub fn get_id(&self) -> u64 {
         self.college_id
     }
 
+    /// Changes the user's college ID. Only `Identities` should call this, as it also moves their records.
+    pub fn set_id(&mut self, college_id: u64) {
+        self.college_id = college_id;
+    }
+
     pub fn get_email(&self) -> String {
         self.college_email.to_string()
     }
@@ -115,6 +248,56 @@ impl User {
         self.name.to_string()
     }
 
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.trim().to_string();
+    }
+
+    pub fn set_email(&mut self, email: &str) {
+        self.college_email = email.trim().to_string();
+    }
+
+    pub fn get_college(&self) -> College {
+        self.college.clone()
+    }
+
+    pub fn set_college(&mut self, college: College) {
+        self.college = college;
+    }
+
+    pub fn get_affiliation(&self) -> Affiliation {
+        self.affiliation.clone()
+    }
+
+    pub fn set_affiliation(&mut self, affiliation: Affiliation) {
+        self.affiliation = affiliation;
+    }
+
+    pub fn get_graduation_date(&self) -> Option<u64> {
+        self.graduation_date
+    }
+
+    pub fn set_graduation_date(&mut self, graduation_date: Option<u64>) {
+        self.graduation_date = graduation_date;
+    }
+
+    /// Students lose access once their graduation date from the roster has passed
+    pub fn has_graduated(&self) -> bool {
+        self.affiliation == Affiliation::Student && self.graduation_date.map_or(false, |x| x < now())
+    }
+
+    /// Whether the user can use the makerspace at all, ie isn't banned and hasn't graduated
+    pub fn check_active(&self) -> Result<(), String> {
+        if self.auth_level == AuthLevel::Banned {
+            return Err("User is banned".to_string());
+        }
+
+        if self.has_graduated() {
+            return Err("User has graduated".to_string());
+        }
+
+        Ok(())
+    }
+
     pub fn get_passed_quizzes(&self) -> Vec<QuizName> {
         self.passed_quizzes.clone()
     }
@@ -150,6 +333,28 @@ impl User {
             .cloned()
             .collect();
 
+        // Keep the latest pass date of each quiz
+        for (quiz_name, certification) in other.certifications.iter() {
+            let current = self.certifications.get(quiz_name);
+
+            if current.is_none() || current.unwrap().timestamp_passed < certification.timestamp_passed {
+                self.certifications.insert(quiz_name.clone(), certification.clone());
+            }
+        }
+
+        // Keep roster details, filling in any that are missing
+        if self.college == College::NA {
+            self.college = other.college.clone();
+        }
+
+        if self.affiliation == Affiliation::Unknown {
+            self.affiliation = other.affiliation.clone();
+        }
+
+        if self.graduation_date.is_none() {
+            self.graduation_date = other.graduation_date;
+        }
+
         // Use longest name
         if self.name.len() < other.name.len() {
             self.name = other.name.clone();
@@ -170,9 +375,14 @@ impl User {
 
     pub fn set_quiz_passed(&mut self, quiz_name: &QuizName, passed: bool) {
         if passed {
-            self.passed_quizzes.push(quiz_name.clone());
+            self.record_pass_date(quiz_name, now());
+
+            if !self.passed_quizzes.contains(quiz_name) {
+                self.passed_quizzes.push(quiz_name.clone());
+            }
         } else {
             self.passed_quizzes.retain(|x| x != quiz_name);
+            self.certifications.remove(quiz_name);
         }
     }
 
@@ -181,17 +391,14 @@ impl User {
             uuid: uuid::Uuid::new_v4().to_string(),
             college_id: self.college_id,
             email: self.college_email.clone(),
-            timestamp_submitted: SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_secs(),
+            timestamp_submitted: now(),
             timestamp_notified: None,
             timestamp_accepted: None,
         }
     }
 }
 
-pub fn create_users_from_quizzes(quizzes: &Vec<Quiz>) -> Users {
+pub fn create_users_from_quizzes(quizzes: &Vec<Quiz>, identities: &Identities) -> Users {
     let mut users = Users::default();
 
     // Keep track of emails and the relevent ID numbers
@@ -203,7 +410,15 @@ pub fn create_users_from_quizzes(quizzes: &Vec<Quiz>) -> Users {
 
     for quiz in quizzes {
         for response in quiz.get_responses() {
-            let email = response.college_email.to_lowercase();
+            // Apply the corrections admins have made, so fixed identities don't come back
+            let response = &identities.resolve_response(response);
+
+            // IDs split off from their email are kept out of the email's group
+            let email = if identities.is_separate(&response.college_id) {
+                format!("{}#{}", response.college_email.to_lowercase(), response.college_id)
+            } else {
+                response.college_email.to_lowercase()
+            };
 
             if !email_key.contains_key(&email) {
                 email_key.insert(email.clone(), vec![response.college_id]);
@@ -217,7 +432,7 @@ pub fn create_users_from_quizzes(quizzes: &Vec<Quiz>) -> Users {
                 user = users.get_user(&user).unwrap().clone();
             }
 
-            user.log_quiz(quiz.get_name().clone(), response.passed);
+            user.log_quiz(quiz.get_name().clone(), response.passed, response.get_timestamp());
 
             users.add_set_user(user);
         }
@@ -260,4 +475,4 @@ pub fn create_users_from_quizzes(quizzes: &Vec<Quiz>) -> Users {
 
 
     final_users
-}
\ No newline at end of file
+}
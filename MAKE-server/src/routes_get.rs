@@ -1,4 +1,5 @@
 use crate::*;
+use std::collections::HashMap;
 use ::serde::{Deserialize, Serialize};
 use actix_web::{error::*, dev::{ServiceResponse}};
 use serde_json::json;
@@ -9,6 +10,8 @@ struct UserInfo {
     college_id: u64,
     college_email: String,
     passed_quizzes: Vec<QuizName>,
+    expired_quizzes: Vec<QuizName>,
+    certifications: HashMap<QuizName, Certification>,
     pending_checkouts: Vec<CheckoutLogEntry>,
     all_checkouts: Vec<CheckoutLogEntry>,
     auth_level: AuthLevel,
@@ -17,6 +20,7 @@ struct UserInfo {
 impl UserInfo {
     fn from_user_and_checkouts(
         user: &User,
+        registry: &QuizRegistry,
         pending_checkouts: Vec<CheckoutLogEntry>,
         all_checkouts: Vec<CheckoutLogEntry>,
     ) -> Self {
@@ -25,6 +29,8 @@ impl UserInfo {
             college_id: user.get_id(),
             college_email: user.get_email(),
             passed_quizzes: user.get_passed_quizzes(),
+            expired_quizzes: registry.get_expired_quizzes(user),
+            certifications: user.get_certifications(),
             pending_checkouts,
             all_checkouts,
             auth_level: user.get_auth_level(),
@@ -114,13 +120,53 @@ pub async fn status() -> Result<HttpResponse, Error> {
 #[get("/api/v1/inventory")]
 pub async fn get_inventory(_path: web::Path<()>) -> Result<HttpResponse, Error> {
     let data = MEMORY_DATABASE.lock().await;
-    let inventory = data.inventory.clone();
+    let mut inventory = data.inventory.clone();
+
+    // Anyone can see this, so leave out who asked for restocks
+    inventory.needs_restock = inventory.needs_restock.iter().map(|x| x.redacted()).collect();
+
     Ok(HttpResponse::Ok().json(inventory))
 }
 
+/// Searches the inventory with typo-tolerant matching.
+/// Takes `q` and the optional filters in `SearchQuery` as query parameters,
+/// eg `/api/v1/inventory/search?q=soldering&is_tool=true&available=true`
+#[get("/api/v1/inventory/search")]
+pub async fn search_inventory(query: web::Query<SearchQuery>) -> Result<HttpResponse, Error> {
+    let data = MEMORY_DATABASE.lock().await;
+    let results = data.inventory.search(&query.into_inner());
+    Ok(HttpResponse::Ok().json(results))
+}
+
+#[get("/api/v1/inventory/export_csv/{api_key}")]
+pub async fn export_inventory_csv(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryView).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let csv = data.inventory.export_csv();
+
+        if csv.is_err() {
+            return Err(ErrorInternalServerError(csv.err().unwrap().to_string()));
+        }
+
+        Ok(HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header((
+                http::header::CONTENT_DISPOSITION,
+                "attachment; filename=\"inventory.csv\"",
+            ))
+            .body(csv.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
 #[get("/api/v1/inventory/restock_requests/{api_key}")]
-pub async fn get_restock_requests(path: web::Path<String>) -> Result<HttpResponse, Error> {
-    if API_KEYS.lock().await.validate_checkout(&path.into_inner()) {
+pub async fn get_restock_requests(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryView).await {
         let data = MEMORY_DATABASE.lock().await;
         let restock_requests = data.inventory.needs_restock.clone();
         Ok(HttpResponse::Ok().json(restock_requests))
@@ -130,8 +176,10 @@ pub async fn get_restock_requests(path: web::Path<String>) -> Result<HttpRespons
 }
 
 #[get("/api/v1/quizzes/{api_key}")]
-pub async fn get_quizzes(path: web::Path<String>) -> Result<HttpResponse, Error> {
-    if API_KEYS.lock().await.validate_admin(&path.into_inner()) {
+pub async fn get_quizzes(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::QuizzesManage).await {
         let data = MEMORY_DATABASE.lock().await;
         let quizzes = data.quizzes.clone();
         Ok(HttpResponse::Ok().json(quizzes))
@@ -140,11 +188,20 @@ pub async fn get_quizzes(path: web::Path<String>) -> Result<HttpResponse, Error>
     }
 }
 
+/// Gets every user, optionally filtered by roster details
+///
+/// eg `/api/v1/users/all/{api_key}?affiliation=Student&college=HarveyMudd&graduated=false`
 #[get("/api/v1/users/all/{api_key}")]
-pub async fn get_users(path: web::Path<String>) -> Result<HttpResponse, Error> {
-    if API_KEYS.lock().await.validate_checkout(&path.into_inner()) {
+pub async fn get_users(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<UserFilter>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersView).await {
         let data = MEMORY_DATABASE.lock().await;
-        let users = data.users.clone();
+        let users = query.apply(&data.users);
         Ok(HttpResponse::Ok().json(users))
     } else {
         Ok(HttpResponse::Unauthorized().finish())
@@ -152,10 +209,21 @@ pub async fn get_users(path: web::Path<String>) -> Result<HttpResponse, Error> {
 }
 
 #[get("/api/v1/users/for_cis/{api_key}")]
-pub async fn get_swipe_access(path: web::Path<String>) -> Result<HttpResponse, Error> {
-    if API_KEYS.lock().await.validate_checkout(&path.into_inner()) {
+pub async fn get_swipe_access(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersView).await {
         let data = MEMORY_DATABASE.lock().await;
-        let users = data.users.clone();
+        // The CIS only looks at passed quizzes, so leave out the expired ones and anyone who can't come in
+        let mut current_users = Users::default();
+
+        for user in data.users.get_users() {
+            if data.suspensions.check_access(&user, &Access::Space).is_ok() {
+                current_users.add_set_user(user);
+            }
+        }
+
+        let users = data.quiz_registry.remove_expired(&current_users);
         Ok(HttpResponse::Ok().json(users))
     } else {
         Ok(HttpResponse::Unauthorized().finish())
@@ -163,9 +231,9 @@ pub async fn get_swipe_access(path: web::Path<String>) -> Result<HttpResponse, E
 }
 
 #[get("/api/v1/checkouts/log/{api_key}")]
-pub async fn get_checkout_log(path: web::Path<String>) -> Result<HttpResponse, Error> {
+pub async fn get_checkout_log(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
     let api_key = path.into_inner();
-    if API_KEYS.lock().await.validate_checkout(&api_key) {
+    if has_permission(&req, &api_key, Permission::CheckoutView).await {
         let data = MEMORY_DATABASE.lock().await;
         let checkout_log = data.checkout_log.clone();
         Ok(HttpResponse::Ok().json(checkout_log))
@@ -190,6 +258,7 @@ pub async fn get_user_info(path: web::Path<u64>) -> Result<HttpResponse, Error>
 
     let user_info = UserInfo::from_user_and_checkouts(
         &user,
+        &data.quiz_registry,
         pending_checkouts.to_vec(),
         all_checkouts.to_vec(),
     );
@@ -213,9 +282,9 @@ pub async fn get_student_storage_for_user(path: web::Path<u64>) -> Result<HttpRe
 }
 
 #[get("/api/v1/student_storage/all/{api_key}")]
-pub async fn get_student_storage_for_all(path: web::Path<String>) -> Result<HttpResponse, Error> {
+pub async fn get_student_storage_for_all(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
     let api_key = path.into_inner();
-    if API_KEYS.lock().await.validate_student_storage(&api_key) {
+    if has_permission(&req, &api_key, Permission::StorageView).await {
         let data = MEMORY_DATABASE.lock().await;
         let student_storage = data.student_storage.clone();
         Ok(HttpResponse::Ok().json(student_storage))
@@ -224,6 +293,62 @@ pub async fn get_student_storage_for_all(path: web::Path<String>) -> Result<Http
     }
 }
 
+#[get("/api/v1/student_storage/migrations/{api_key}")]
+pub async fn get_student_storage_migrations(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+    if has_permission(&req, &api_key, Permission::StorageManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+
+        Ok(HttpResponse::Ok().json(json!({
+            "orphaned": data.student_storage.orphaned,
+            "migration_reports": data.student_storage.migration_reports,
+        })))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/lost_and_found/all/{api_key}")]
+pub async fn get_lost_and_found(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+    if has_permission(&req, &api_key, Permission::LostAndFoundManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let lost_and_found = data.lost_and_found.clone();
+        Ok(HttpResponse::Ok().json(lost_and_found))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/lost_and_found/user/{id_number}")]
+pub async fn get_lost_and_found_for_user(path: web::Path<u64>) -> Result<HttpResponse, Error> {
+    let data = MEMORY_DATABASE.lock().await;
+    let items = data.lost_and_found.get_items_for_id(&path.into_inner());
+
+    Ok(HttpResponse::Ok().json(items))
+}
+
+#[get("/api/v1/lost_and_found/photo/{uuid}/{thumbnail}")]
+pub async fn get_lost_item_photo(path: web::Path<(String, bool)>) -> Result<HttpResponse, Error> {
+    let (uuid, thumbnail) = path.into_inner();
+
+    let item = MEMORY_DATABASE.lock().await.lost_and_found.get_item_by_uuid(&uuid);
+
+    if item.is_none() || !item.unwrap().has_photo {
+        return Err(ErrorNotFound("Photo not found".to_string()));
+    }
+
+    let photo = load_lost_item_photo(&uuid, thumbnail);
+
+    if photo.is_none() {
+        return Err(ErrorNotFound("Photo not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("image/jpeg")
+        .body(photo.unwrap()))
+}
+
 #[get("/api/v1/printers/{id_number}")]
 pub async fn get_printers(path: web::Path<u64>) -> Result<HttpResponse, Error> {
     let id = path.into_inner();
@@ -237,15 +362,10 @@ pub async fn get_printers(path: web::Path<u64>) -> Result<HttpResponse, Error> {
 }
 
 #[get("/api/v1/printers/for_api/{api_key}")]
-pub async fn get_printers_api_key(path: web::Path<String>) -> Result<HttpResponse, Error> {
+pub async fn get_printers_api_key(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
     let api_key = path.into_inner();
 
-    let api_keys = API_KEYS.lock().await;
-
-    if api_keys.validate_printers(&api_key)
-        || api_keys.validate_checkout(&api_key)
-        || api_keys.validate_admin(&api_key)
-    {
+    if has_permission(&req, &api_key, Permission::PrintersView).await {
         let data = MEMORY_DATABASE.lock().await;
         let printers = data.printers.get_printer_statuses();
 
@@ -267,10 +387,9 @@ pub async fn get_schedule() -> Result<HttpResponse, Error> {
 }
 
 #[get("/api/v1/schedule/{api_key}")]
-pub async fn get_schedule_api_key(path: web::Path<String>) -> Result<HttpResponse, Error> {
+pub async fn get_schedule_api_key(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
     let api_key = path.into_inner();
-    if API_KEYS.lock().await.validate_admin(&api_key)
-    {
+    if has_permission(&req, &api_key, Permission::ScheduleView).await {
         let data = MEMORY_DATABASE.lock().await;
         let schedule = data.schedule.clone();
         Ok(HttpResponse::Ok().json(schedule))
@@ -295,4 +414,622 @@ pub async fn get_now_playing() -> Result<HttpResponse, Error> {
     drop(data);
 
     Ok(HttpResponse::Ok().json(now_playing))
-}
\ No newline at end of file
+}
+
+#[get("/api/v1/budget/{api_key}")]
+pub async fn get_budget(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BudgetManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let budget = data.budget.clone();
+        Ok(HttpResponse::Ok().json(budget))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Gets the allocated, spent and remaining budget for each category.
+/// `fiscal_period` is eg FY2026, or current for the current fiscal year.
+#[get("/api/v1/budget/summary/{fiscal_period}/{api_key}")]
+pub async fn get_budget_summary(req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (fiscal_period, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BudgetManage).await {
+        let fiscal_period = if fiscal_period == "current" {
+            get_current_fiscal_period()
+        } else {
+            fiscal_period
+        };
+
+        let data = MEMORY_DATABASE.lock().await;
+        let summary = data.budget.get_summary(&fiscal_period);
+        Ok(HttpResponse::Ok().json(summary))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Exports purchase order line items as a CSV ledger for the finance office.
+///
+/// eg `/api/v1/budget/export_csv/{api_key}?fiscal_period=FY2026`
+#[get("/api/v1/budget/export_csv/{api_key}")]
+pub async fn export_budget_ledger(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<LedgerQuery>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BudgetManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let csv = data
+            .budget
+            .export_ledger_csv(query.fiscal_period.as_deref(), &data.inventory);
+
+        if csv.is_err() {
+            return Err(ErrorInternalServerError(csv.err().unwrap().to_string()));
+        }
+
+        let filename = format!(
+            "attachment; filename=\"ledger_{}.csv\"",
+            query.fiscal_period.clone().unwrap_or("all".to_string())
+        );
+
+        Ok(HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header((http::header::CONTENT_DISPOSITION, filename))
+            .body(csv.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/billing/prices")]
+pub async fn get_material_prices(_path: web::Path<()>) -> Result<HttpResponse, Error> {
+    let data = MEMORY_DATABASE.lock().await;
+    let prices = data.billing.prices.clone();
+    Ok(HttpResponse::Ok().json(prices))
+}
+
+#[get("/api/v1/billing/balance/{id_number}/{api_key}")]
+pub async fn get_billing_balance(req: HttpRequest, path: web::Path<(u64, String)>) -> Result<HttpResponse, Error> {
+    let (id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BillingView).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let balance = UserBalance {
+            college_id: id_number,
+            balance_cents: data.billing.get_balance(id_number),
+        };
+        Ok(HttpResponse::Ok().json(balance))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/billing/balances/{api_key}")]
+pub async fn get_billing_balances(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BillingManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let balances = data.billing.get_balances();
+        Ok(HttpResponse::Ok().json(balances))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Gets a user's material statement for a month, eg 2026-09
+#[get("/api/v1/billing/statement/{id_number}/{month}/{api_key}")]
+pub async fn get_billing_statement(
+    req: HttpRequest,
+    path: web::Path<(u64, String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id_number, month, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BillingView).await {
+        if !is_month(&month) {
+            return Err(ErrorBadRequest(format!("Invalid month {}, expected eg 2026-09", month)));
+        }
+
+        let data = MEMORY_DATABASE.lock().await;
+        let statement = data.billing.get_statement(id_number, &month, &data.users);
+        Ok(HttpResponse::Ok().json(statement))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Exports a summary row for every user's statement for a month, eg 2026-09
+#[get("/api/v1/billing/statements_csv/{month}/{api_key}")]
+pub async fn export_billing_statements(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (month, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BillingManage).await {
+        if !is_month(&month) {
+            return Err(ErrorBadRequest(format!("Invalid month {}, expected eg 2026-09", month)));
+        }
+
+        let data = MEMORY_DATABASE.lock().await;
+        let csv = data.billing.export_statements_csv(&month, &data.users);
+
+        if csv.is_err() {
+            return Err(ErrorInternalServerError(csv.err().unwrap().to_string()));
+        }
+
+        Ok(HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header((
+                http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"statements_{}.csv\"", month),
+            ))
+            .body(csv.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/printers/spools/{api_key}")]
+pub async fn get_spools(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrintersManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let spools = data.printers.get_spools();
+        Ok(HttpResponse::Ok().json(spools))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/inventory/audits/{api_key}")]
+pub async fn get_audits(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryAudit).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let sessions = data.audits.sessions.clone();
+        Ok(HttpResponse::Ok().json(sessions))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Gets the report for an audit. Audits still being counted get a live report.
+#[get("/api/v1/inventory/audit/{id}/report/{api_key}")]
+pub async fn get_audit_report(req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryAudit).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let session = data.audits.get_session(&id);
+
+        if session.is_none() {
+            return Err(ErrorBadRequest(format!("No audit found with id {}", id)));
+        }
+
+        let session = session.unwrap();
+        let report = session
+            .report
+            .clone()
+            .unwrap_or_else(|| session.get_report(&data.inventory));
+
+        Ok(HttpResponse::Ok().json(report))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/inventory/adjustments/{api_key}")]
+pub async fn get_stock_adjustments(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let adjustments = data.audits.adjustments.clone();
+        Ok(HttpResponse::Ok().json(adjustments))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/maintenance/plans/{api_key}")]
+pub async fn get_maintenance_plans(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::MaintenanceView).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let plans = data.maintenance.plans.clone();
+        Ok(HttpResponse::Ok().json(plans))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/maintenance/due/{api_key}")]
+pub async fn get_maintenance_due(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::MaintenanceView).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let due = data.maintenance.get_due(&data.inventory, &data.printers);
+        Ok(HttpResponse::Ok().json(due))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/maintenance/log/{api_key}")]
+pub async fn get_maintenance_log(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::MaintenanceView).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let log = data.maintenance.log.clone();
+        Ok(HttpResponse::Ok().json(log))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/maintenance/out_of_service")]
+pub async fn get_out_of_service(_path: web::Path<()>) -> Result<HttpResponse, Error> {
+    let data = MEMORY_DATABASE.lock().await;
+    let out_of_service = data.maintenance.out_of_service.clone();
+    Ok(HttpResponse::Ok().json(out_of_service))
+}
+
+/// Asked by a machine's interlock when someone swipes in to use it
+#[get("/api/v1/machines/interlock/{machine}/{id_number}/{api_key}")]
+pub async fn get_interlock_approval(
+    req: HttpRequest,
+    path: web::Path<(String, u64, String)>,
+) -> Result<HttpResponse, Error> {
+    let (machine, id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::MachinesInterlock).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let approval = check_interlock(&data, &machine, id_number);
+        Ok(HttpResponse::Ok().json(approval))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/incidents/{api_key}")]
+pub async fn get_incidents(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::IncidentsManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let incidents = data.incidents.clone();
+        Ok(HttpResponse::Ok().json(incidents))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[derive(Serialize)]
+pub struct UserIncidents {
+    pub strikes: u64,
+    pub incidents: Vec<Incident>,
+}
+
+#[get("/api/v1/incidents/user/{id_number}/{api_key}")]
+pub async fn get_incidents_for_user(req: HttpRequest, path: web::Path<(u64, String)>) -> Result<HttpResponse, Error> {
+    let (id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::IncidentsManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let user_incidents = UserIncidents {
+            strikes: data.incidents.get_strikes(&id_number),
+            incidents: data.incidents.get_incidents_for_id(&id_number),
+        };
+        Ok(HttpResponse::Ok().json(user_incidents))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Gets the question banks, including the answers
+#[get("/api/v1/quizzes/banks/{api_key}")]
+pub async fn get_question_banks(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::QuizzesManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let banks = data.quiz_banks.banks.clone();
+        Ok(HttpResponse::Ok().json(banks))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/quizzes/attempts/{id_number}/{api_key}")]
+pub async fn get_quiz_attempts(req: HttpRequest, path: web::Path<(u64, String)>) -> Result<HttpResponse, Error> {
+    let (id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::QuizzesViewAttempts).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let attempts = data.quiz_banks.get_attempts_for_id(&id_number);
+        Ok(HttpResponse::Ok().json(attempts))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/quizzes/registry")]
+pub async fn get_quiz_registry(_path: web::Path<()>) -> Result<HttpResponse, Error> {
+    let data = MEMORY_DATABASE.lock().await;
+    let quizzes = data.quiz_registry.quizzes.clone();
+    Ok(HttpResponse::Ok().json(quizzes))
+}
+
+/// Gets the swipe groups a user's quizzes give them access to
+#[get("/api/v1/users/swipe_groups/{id_number}/{api_key}")]
+pub async fn get_user_swipe_groups(req: HttpRequest, path: web::Path<(u64, String)>) -> Result<HttpResponse, Error> {
+    let (id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersView).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let user = data.users.get_user_by_id(&id_number);
+
+        if user.is_none() {
+            return Err(ErrorBadRequest("User not found".to_string()));
+        }
+
+        let user = user.unwrap();
+
+        if data.suspensions.check_access(&user, &Access::Space).is_err() {
+            return Ok(HttpResponse::Ok().json(Vec::<SwipeGroup>::new()));
+        }
+
+        let groups = data.quiz_registry.get_swipe_groups(&user);
+        Ok(HttpResponse::Ok().json(groups))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/sign_offs/{api_key}")]
+pub async fn get_sign_offs(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::SignOffsManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let sign_offs = data.sign_offs.records.clone();
+        Ok(HttpResponse::Ok().json(sign_offs))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/sign_offs/skills")]
+pub async fn get_skills(_path: web::Path<()>) -> Result<HttpResponse, Error> {
+    let data = MEMORY_DATABASE.lock().await;
+    let skills = data.sign_offs.skills.clone();
+    Ok(HttpResponse::Ok().json(skills))
+}
+
+#[get("/api/v1/sign_offs/user/{id_number}/{api_key}")]
+pub async fn get_sign_offs_for_user(req: HttpRequest, path: web::Path<(u64, String)>) -> Result<HttpResponse, Error> {
+    let (id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::SignOffsView).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let sign_offs = data.sign_offs.get_sign_offs_for_id(&id_number);
+        Ok(HttpResponse::Ok().json(sign_offs))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/users/identities/{api_key}")]
+pub async fn get_identities(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersManageIdentities).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let identities = data.identities.clone();
+        Ok(HttpResponse::Ok().json(identities))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Lists users that might be duplicates or have mistyped IDs, for an admin to merge or re-key
+#[get("/api/v1/users/identities/suspicious/{api_key}")]
+pub async fn get_suspicious_identities(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersManageIdentities).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let suspicious = data.identities.get_suspicious(&data.users);
+        Ok(HttpResponse::Ok().json(suspicious))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Everything stored about a user, for answering a data request
+#[get("/api/v1/privacy/export/{id_number}/{api_key}")]
+pub async fn export_user_data_route(req: HttpRequest, path: web::Path<(u64, String)>) -> Result<HttpResponse, Error> {
+    let (id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrivacyManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let result = export_user_data(&data, id_number);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        Ok(HttpResponse::Ok().json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// The same export as one CSV per kind of record, as an object of file names to CSV text
+#[get("/api/v1/privacy/export_csv/{id_number}/{api_key}")]
+pub async fn export_user_data_csv(req: HttpRequest, path: web::Path<(u64, String)>) -> Result<HttpResponse, Error> {
+    let (id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrivacyManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let result = export_user_data(&data, id_number);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let bundle = result.unwrap().to_csv_bundle();
+
+        if bundle.is_err() {
+            return Err(ErrorInternalServerError(bundle.unwrap_err()));
+        }
+
+        Ok(HttpResponse::Ok()
+            .insert_header((
+                http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"user_data_{}.json\"", id_number),
+            ))
+            .json(bundle.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/privacy/deletion_requests/{api_key}")]
+pub async fn get_deletion_requests(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrivacyManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let requests = data.privacy.deletion_requests.clone();
+        Ok(HttpResponse::Ok().json(requests))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// The audit log, newest first, eg `/api/v1/audit_log/{api_key}?target=12345678` or `?actor=checkout`
+#[get("/api/v1/audit_log/{api_key}")]
+pub async fn get_audit_log(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<AuditQuery>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::AuditView).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let entries = data.audit_log.query(&query.into_inner());
+        Ok(HttpResponse::Ok().json(entries))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Takes the same filters as the audit log
+#[get("/api/v1/audit_log/export_csv/{api_key}")]
+pub async fn export_audit_log(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<AuditQuery>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::AuditView).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let csv = data.audit_log.export_csv(&query.into_inner());
+
+        if csv.is_err() {
+            return Err(ErrorInternalServerError(csv.err().unwrap().to_string()));
+        }
+
+        Ok(HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header((
+                http::header::CONTENT_DISPOSITION,
+                "attachment; filename=\"audit_log.csv\"",
+            ))
+            .body(csv.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Every role and every permission there is, for the admin page
+#[get("/api/v1/roles/{api_key}")]
+pub async fn get_roles(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::RolesManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+
+        Ok(HttpResponse::Ok().json(json!({
+            "roles": data.roles.get_roles(),
+            "permissions": Permission::all(),
+        })))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// The roles assigned to a user on top of their auth level, and everything they end up being allowed to do
+#[get("/api/v1/roles/user/{id_number}/{api_key}")]
+pub async fn get_user_roles(req: HttpRequest, path: web::Path<(u64, String)>) -> Result<HttpResponse, Error> {
+    let (id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::RolesManage).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let user = data.users.get_user_by_id(&id_number);
+
+        if user.is_none() {
+            return Err(ErrorBadRequest("User not found".to_string()));
+        }
+
+        Ok(HttpResponse::Ok().json(json!({
+            "assigned_roles": data.roles.get_assigned_roles(&id_number),
+            "permissions": data.roles.get_user_permissions(&user.unwrap()),
+        })))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Every suspension, newest first, including ones that have ended
+#[get("/api/v1/suspensions/{api_key}")]
+pub async fn get_suspensions(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersBan).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let suspensions = data.suspensions.get_all();
+        Ok(HttpResponse::Ok().json(suspensions))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[get("/api/v1/suspensions/user/{id_number}/{api_key}")]
+pub async fn get_suspensions_for_user(
+    req: HttpRequest,
+    path: web::Path<(u64, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersBan).await {
+        let data = MEMORY_DATABASE.lock().await;
+        let suspensions = data.suspensions.get_for_id(&id_number);
+        Ok(HttpResponse::Ok().json(suspensions))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
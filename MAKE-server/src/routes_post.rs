@@ -1,6 +1,7 @@
 use crate::*;
 use ::serde::Deserialize;
 use actix_web::error::*;
+use base64::prelude::BASE64_STANDARD;
 
 /*
 =================
@@ -14,12 +15,14 @@ pub struct CheckoutItems {
 }
 #[post("/api/v1/checkouts/add_entry/{id_number}/{sec_length}/{api_key}")]
 pub async fn checkout_items(
+    req: HttpRequest,
     path: web::Path<(u64, u64, String)>,
     body: web::Json<CheckoutItems>,
 ) -> Result<HttpResponse, Error> {
     let (id_number, sec_length, api_key) = path.into_inner();
 
-    if API_KEYS.lock().await.validate_checkout(&api_key) {
+    if has_permission(&req, &api_key, Permission::CheckoutCreate).await {
+        let actor = Actor::from_request(&req, &api_key).await;
         let mut data = MEMORY_DATABASE.lock().await;
 
         let user = data.users.get_user_by_id(&id_number);
@@ -28,22 +31,45 @@ pub async fn checkout_items(
             return Err(ErrorBadRequest("User not found".to_string()));
         }
 
-
         let user = user.unwrap();
 
-        if user.get_auth_level() == AuthLevel::Banned {
-            return Err(ErrorUnauthorized("User is banned".to_string()));
+        let access = data.suspensions.check_access(&user, &Access::Checkouts);
+
+        if access.is_err() {
+            return Err(ErrorUnauthorized(access.unwrap_err()));
         }
 
-        if user.get_passed_quizzes().contains(&QuizName::General) == false {
+        if data.quiz_registry.get_valid_quizzes(&user).contains(&QuizName::general()) == false {
+            if user.get_passed_quizzes().contains(&QuizName::general()) {
+                return Err(ErrorUnauthorized("User's General Quiz has expired".to_string()));
+            }
+
             return Err(ErrorUnauthorized("User has not passed the General Quiz".to_string()));
         }
 
-        data.checkout_log.add_checkout(CheckoutLogEntry::new(
-            user.get_id(),
-            sec_length,
-            body.items.clone(),
-        ));
+        let requirements = data.inventory.check_requirements(&body.items, &user, &data.quiz_registry);
+
+        if requirements.is_err() {
+            return Err(ErrorUnauthorized(requirements.unwrap_err()));
+        }
+
+        let sign_offs = data.sign_offs.check_items(&body.items, &data.inventory, &user);
+
+        if sign_offs.is_err() {
+            return Err(ErrorUnauthorized(sign_offs.unwrap_err()));
+        }
+
+        let in_service = data.maintenance.check_items_in_service(&body.items, &data.inventory);
+
+        if in_service.is_err() {
+            return Err(ErrorBadRequest(in_service.unwrap_err()));
+        }
+
+        let entry = CheckoutLogEntry::new(user.get_id(), sec_length, body.items.clone());
+
+        data.checkout_log.add_checkout(entry.clone());
+        data.audit_log
+            .record(&actor, "checkouts.create", user.get_id(), &None::<()>, &entry);
 
         drop(data);
 
@@ -59,19 +85,26 @@ pub async fn checkout_items(
 
 #[post("/api/v1/checkouts/extend/{uuid}/{sec_length}/{api_key}")]
 pub async fn extend_checkout_by_uuid(
+    req: HttpRequest,
     path: web::Path<(String, u64, String)>,
 ) -> Result<HttpResponse, Error> {
     let (uuid, sec_length, api_key) = path.into_inner();
 
-    if API_KEYS.lock().await.validate_checkout(&api_key) {
+    if has_permission(&req, &api_key, Permission::CheckoutExtend).await {
+        let actor = Actor::from_request(&req, &api_key).await;
         let mut data = MEMORY_DATABASE.lock().await;
 
-        let checkout = data.checkout_log.extend_checkout(uuid, sec_length);
+        let before = data.checkout_log.get_entry(&uuid);
+        let checkout = data.checkout_log.extend_checkout(uuid.clone(), sec_length);
 
         if checkout.is_err() {
             return Err(ErrorBadRequest(checkout.err().unwrap().to_string()));
         }
 
+        let after = data.checkout_log.get_entry(&uuid);
+        data.audit_log
+            .record(&actor, "checkouts.extend", format!("checkout:{}", uuid), &before, &after);
+
         Ok(HttpResponse::Ok()
             .status(http::StatusCode::CREATED)
             .finish())
@@ -82,12 +115,14 @@ pub async fn extend_checkout_by_uuid(
 
 #[post("/api/v1/checkouts/add_reservation/{id_number}/{start_time}/{sec_length}/{api_key}")]
 pub async fn reserve_items(
+    req: HttpRequest,
     path: web::Path<(u64, u64, u64, String)>,
     body: web::Json<CheckoutItems>,
 ) -> Result<HttpResponse, Error> {
     let (id_number, start_time, sec_length, api_key) = path.into_inner();
 
-    if API_KEYS.lock().await.validate_checkout(&api_key) {
+    if has_permission(&req, &api_key, Permission::CheckoutCreate).await {
+        let actor = Actor::from_request(&req, &api_key).await;
         let mut data = MEMORY_DATABASE.lock().await;
 
         let user = data.users.get_user_by_id(&id_number);
@@ -98,16 +133,36 @@ pub async fn reserve_items(
 
         let user = user.unwrap();
 
-        if user.get_auth_level() == AuthLevel::Banned {
-            return Err(ErrorUnauthorized("User is banned".to_string()));
+        let access = data.suspensions.check_access(&user, &Access::Checkouts);
+
+        if access.is_err() {
+            return Err(ErrorUnauthorized(access.unwrap_err()));
+        }
+
+        let requirements = data.inventory.check_requirements(&body.items, &user, &data.quiz_registry);
+
+        if requirements.is_err() {
+            return Err(ErrorUnauthorized(requirements.unwrap_err()));
+        }
+
+        let sign_offs = data.sign_offs.check_items(&body.items, &data.inventory, &user);
+
+        if sign_offs.is_err() {
+            return Err(ErrorUnauthorized(sign_offs.unwrap_err()));
+        }
+
+        let in_service = data.maintenance.check_items_in_service(&body.items, &data.inventory);
+
+        if in_service.is_err() {
+            return Err(ErrorBadRequest(in_service.unwrap_err()));
         }
-        data.checkout_log
-            .add_reservation(CheckoutLogEntry::new_reservation(
-                user.get_id(),
-                start_time,
-                sec_length,
-                body.items.clone(),
-            ));
+
+        let entry =
+            CheckoutLogEntry::new_reservation(user.get_id(), start_time, sec_length, body.items.clone());
+
+        data.checkout_log.add_reservation(entry.clone());
+        data.audit_log
+            .record(&actor, "checkouts.reserve", user.get_id(), &None::<()>, &entry);
 
         Ok(HttpResponse::Ok()
             .status(http::StatusCode::CREATED)
@@ -118,13 +173,25 @@ pub async fn reserve_items(
 }
 
 #[post("/api/v1/checkouts/check_in_entry/{uuid}/{api_key}")]
-pub async fn checkin_items(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+pub async fn checkin_items(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
     let (uuid, api_key) = path.into_inner();
 
-    if API_KEYS.lock().await.validate_checkout(&api_key) {
+    if has_permission(&req, &api_key, Permission::CheckoutCheckIn).await {
+        let actor = Actor::from_request(&req, &api_key).await;
         let mut data = MEMORY_DATABASE.lock().await;
 
-        let result = data.checkout_log.check_in(uuid);
+        let before = data.checkout_log.get_entry(&uuid);
+        let result = data.checkout_log.check_in(uuid.clone());
+
+        if result.is_ok() {
+            let after = data.checkout_log.get_entry(&uuid);
+            let target = format!("checkout:{}", uuid);
+            data.audit_log
+                .record(&actor, "checkouts.check_in", target, &before, &after);
+        }
 
         drop(data);
 
@@ -142,11 +209,27 @@ pub async fn checkin_items(path: web::Path<(String, String)>) -> Result<HttpResp
 
 #[post("/api/v1/auth/set_level/{id_number}/{auth_level}/{api_key}")]
 pub async fn set_auth_level(
+    req: HttpRequest,
     path: web::Path<(u64, AuthLevel, String)>,
 ) -> Result<HttpResponse, Error> {
     let (id_number, auth_level, api_key) = path.into_inner();
 
-    if API_KEYS.lock().await.validate_admin(&api_key) {
+    let is_banned = MEMORY_DATABASE
+        .lock()
+        .await
+        .users
+        .get_user_by_id(&id_number)
+        .map_or(false, |x| x.get_auth_level() == AuthLevel::Banned);
+
+    // Banning and unbanning are their own permission
+    let permission = if auth_level == AuthLevel::Banned || is_banned {
+        Permission::UsersBan
+    } else {
+        Permission::UsersSetAuthLevel
+    };
+
+    if has_permission(&req, &api_key, permission).await {
+        let actor = Actor::from_request(&req, &api_key).await;
         let mut data = MEMORY_DATABASE.lock().await;
 
         let user = data.users.get_user_by_id(&id_number);
@@ -156,10 +239,13 @@ pub async fn set_auth_level(
         }
 
         let mut user = user.unwrap();
+        let before = user.get_auth_level();
 
-        user.set_auth_level(auth_level);
+        user.set_auth_level(auth_level.clone());
 
         data.users.add_set_user(user);
+        data.audit_log
+            .record(&actor, "users.set_auth_level", id_number, &before, &auth_level);
 
         Ok(HttpResponse::Ok()
             .status(http::StatusCode::CREATED)
@@ -171,11 +257,13 @@ pub async fn set_auth_level(
 
 #[post("/api/v1/auth/set_quiz/{id_number}/{quiz_name}/{passed}/{api_key}")]
 pub async fn set_quiz_passed(
+    req: HttpRequest,
     path: web::Path<(u64, QuizName, bool, String)>,
 ) -> Result<HttpResponse, Error> {
     let (id_number, quiz_name, passed, api_key) = path.into_inner();
 
-    if API_KEYS.lock().await.validate_admin(&api_key) {
+    if has_permission(&req, &api_key, Permission::UsersSetQuizzes).await {
+        let actor = Actor::from_request(&req, &api_key).await;
         let mut data = MEMORY_DATABASE.lock().await;
 
         let user = data.users.get_user_by_id(&id_number);
@@ -184,11 +272,20 @@ pub async fn set_quiz_passed(
             return Err(ErrorBadRequest("User not found".to_string()));
         }
 
+        if !data.quiz_registry.exists(&quiz_name) {
+            return Err(ErrorBadRequest(format!("No quiz found with id {}", quiz_name)));
+        }
+
         let mut user = user.unwrap();
+        let before = user.get_passed_quizzes();
 
         user.set_quiz_passed(&quiz_name, passed);
 
+        let after = user.get_passed_quizzes();
+
         data.users.add_set_user(user);
+        data.audit_log
+            .record(&actor, "users.set_quiz_passed", id_number, &before, &after);
 
         Ok(HttpResponse::Ok()
             .status(http::StatusCode::CREATED)
@@ -217,6 +314,26 @@ pub async fn update_printer_status(
         }
     }
 
+    // Order more filament for spools that just ran low
+    for spool in data.printers.take_low_spools() {
+        let notice = data.inventory.add_restock_notice(RestockNotice {
+            name: format!("{} Filament ({})", spool.material, spool.color),
+            current_quantity: format!("{:.0} g left on spool {}", spool.remaining_grams, spool.uuid),
+            requested_quantity: "1 spool".to_string(),
+            notes: "Automatic notice: spool running low".to_string(),
+            authorized: true,
+            ..Default::default()
+        });
+
+        data.audit_log.record(
+            &Actor::system("printer_webhook"),
+            "inventory.add_restock",
+            format!("restock:{}", notice.id),
+            &None::<()>,
+            &notice,
+        );
+    }
+
     Ok(HttpResponse::Ok()
         .status(http::StatusCode::CREATED)
         .finish())
@@ -236,8 +353,10 @@ pub async fn join_printer_queue(path: web::Path<u64>) -> Result<HttpResponse, Er
 
     let user = user.unwrap();
 
-    if user.get_auth_level() == AuthLevel::Banned {
-        return Err(ErrorUnauthorized("User is banned".to_string()));
+    let access = data.suspensions.check_access(&user, &Access::Machines);
+
+    if access.is_err() {
+        return Err(ErrorUnauthorized(access.unwrap_err()));
     }
 
     let result = data.printers.add_user_to_queue(&user);
@@ -278,11 +397,13 @@ pub async fn leave_printer_queue(path: web::Path<u64>) -> Result<HttpResponse, E
 
 #[post("/api/v1/student_storage/add_entry/{id_number}/{slot_id}/{api_key}")]
 pub async fn checkout_student_storage(
+    req: HttpRequest,
     path: web::Path<(u64, String, String)>,
 ) -> Result<HttpResponse, Error> {
     let (id_number, slot_id, api_key) = path.into_inner();
 
-    if API_KEYS.lock().await.validate_student_storage(&api_key) {
+    if has_permission(&req, &api_key, Permission::StorageAssign).await {
+        let actor = Actor::from_request(&req, &api_key).await;
         let mut data = MEMORY_DATABASE.lock().await;
 
         let user = data.users.get_user_by_id(&id_number);
@@ -293,18 +414,25 @@ pub async fn checkout_student_storage(
 
         let user = user.unwrap();
 
-        if user.get_auth_level() == AuthLevel::Banned {
-            return Err(ErrorUnauthorized("User is banned".to_string()));
+        let access = data.suspensions.check_access(&user, &Access::Storage);
+
+        if access.is_err() {
+            return Err(ErrorUnauthorized(access.unwrap_err()));
         }
 
-        let finished = data
+        let before = data.student_storage.get_slot(&slot_id);
+        let result = data
             .student_storage
             .checkout_slot_by_id(&user.get_id(), &slot_id);
 
-        if !finished {
-            return Err(ErrorBadRequest("Slot not found".to_string()));
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
         }
 
+        let after = data.student_storage.get_slot(&slot_id);
+        data.audit_log
+            .record(&actor, "storage.checkout", user.get_id(), &before, &after);
+
         Ok(HttpResponse::Ok()
             .status(http::StatusCode::CREATED)
             .finish())
@@ -329,8 +457,10 @@ pub async fn renew_student_storage_slot(
 
     let user = user.unwrap();
 
-    if user.get_auth_level() == AuthLevel::Banned {
-        return Err(ErrorUnauthorized("User is banned".to_string()));
+    let access = data.suspensions.check_access(&user, &Access::Storage);
+
+    if access.is_err() {
+        return Err(ErrorUnauthorized(access.unwrap_err()));
     }
 
     let result = data.student_storage.renew_by_id(&user.get_id(), &slot_id);
@@ -367,19 +497,64 @@ pub async fn release_student_storage_slot(
         .finish())
 }
 
-#[post("/api/v1/inventory/add_restock_notice/{api_key}")]
-pub async fn add_restock_notice(
-    body: web::Json<RestockNotice>,
-    path: web::Path<String>,
+#[post("/api/v1/student_storage/join_waitlist/{id_number}/{size}")]
+pub async fn join_student_storage_waitlist(
+    path: web::Path<(u64, SlotSize)>,
+) -> Result<HttpResponse, Error> {
+    let (id_number, size) = path.into_inner();
+
+    let mut data = MEMORY_DATABASE.lock().await;
+
+    let user = data.users.get_user_by_id(&id_number);
+
+    if user.is_none() {
+        return Err(ErrorBadRequest("User not found".to_string()));
+    }
+
+    let user = user.unwrap();
+
+    let access = data.suspensions.check_access(&user, &Access::Storage);
+
+    if access.is_err() {
+        return Err(ErrorUnauthorized(access.unwrap_err()));
+    }
+
+    let result = data.student_storage.join_waitlist(&user, size);
+
+    if result.is_err() {
+        return Err(ErrorBadRequest(result.unwrap_err()));
+    }
+
+    Ok(HttpResponse::Ok()
+        .status(http::StatusCode::CREATED)
+        .finish())
+}
+
+/// Takes someone off the waitlist. Needs a key that can assign storage, or the user's own token in `X-User-Token`.
+#[post("/api/v1/student_storage/leave_waitlist/{id_number}/{size}/{api_key}")]
+pub async fn leave_student_storage_waitlist(
+    req: HttpRequest,
+    path: web::Path<(u64, SlotSize, String)>,
 ) -> Result<HttpResponse, Error> {
-    if API_KEYS.lock().await.validate_checkout(&path.into_inner()) {
+    let (id_number, size, api_key) = path.into_inner();
+
+    let actor = Actor::from_request(&req, &api_key).await;
+
+    if actor.verified_id() == Some(id_number) || has_permission(&req, &api_key, Permission::StorageAssign).await {
         let mut data = MEMORY_DATABASE.lock().await;
 
-        let mut notice = body.into_inner();
+        let result = data.student_storage.leave_waitlist(&id_number, size.clone());
 
-        notice.authorized = true;
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "storage.leave_waitlist", id_number, &size, &None::<()>);
+
+        drop(data);
 
-        data.inventory.add_restock_notice(notice);
+        let _ = save_database().await;
 
         Ok(HttpResponse::Ok()
             .status(http::StatusCode::CREATED)
@@ -389,36 +564,259 @@ pub async fn add_restock_notice(
     }
 }
 
-#[post("/api/v1/inventory/add_restock_notice_user")]
-pub async fn add_user_restock_notice(
-    body: web::Json<RestockNotice>,
+#[post("/api/v1/student_storage/transfer/{slot_id}/{new_id_number}/{api_key}")]
+pub async fn transfer_student_storage_slot(
+    req: HttpRequest,
+    path: web::Path<(String, u64, String)>,
 ) -> Result<HttpResponse, Error> {
-    let mut data = MEMORY_DATABASE.lock().await;
+    let (slot_id, new_id_number, api_key) = path.into_inner();
 
-    let mut notice = body.into_inner();
+    if has_permission(&req, &api_key, Permission::StorageManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
 
-    notice.authorized = false;
+        if !data.users.exists(&new_id_number) {
+            return Err(ErrorBadRequest("User not found".to_string()));
+        }
 
-    data.inventory.add_restock_notice(notice);
+        let before = data.student_storage.get_slot(&slot_id);
+        let result = data
+            .student_storage
+            .transfer_slot_by_id(&slot_id, &new_id_number);
 
-    Ok(HttpResponse::Ok()
-        .status(http::StatusCode::CREATED)
-        .finish())
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let after = data.student_storage.get_slot(&slot_id);
+        data.audit_log
+            .record(&actor, "storage.transfer", new_id_number, &before, &after);
+
+        info!("Transferred student storage slot {} to {}", slot_id, new_id_number);
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
 }
 
-#[post("/api/v1/usage/add_button_log/{api_key}")]
-pub async fn add_button_log(
+#[post("/api/v1/student_storage/override/{id_number}/{slot_id}/{api_key}")]
+pub async fn override_student_storage_slot(
+    req: HttpRequest,
+    path: web::Path<(u64, String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id_number, slot_id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::StorageManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        if !data.users.exists(&id_number) {
+            return Err(ErrorBadRequest("User not found".to_string()));
+        }
+
+        let before = data.student_storage.get_slot(&slot_id);
+        let result = data
+            .student_storage
+            .override_slot_by_id(&id_number, &slot_id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let after = data.student_storage.get_slot(&slot_id);
+        data.audit_log
+            .record(&actor, "storage.override", id_number, &before, &after);
+
+        info!("Overrode student storage slot {} for {}", slot_id, id_number);
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/student_storage/reassign_orphan/{old_slot_id}/{new_slot_id}/{api_key}")]
+pub async fn reassign_student_storage_orphan(
+    req: HttpRequest,
+    path: web::Path<(String, String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (old_slot_id, new_slot_id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::StorageManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.student_storage.get_orphan(&old_slot_id);
+        let result = data
+            .student_storage
+            .reassign_orphan(&old_slot_id, &new_slot_id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let after = data.student_storage.get_slot(&new_slot_id);
+        let target = before.as_ref().map(|x| x.occupied_details.college_id).unwrap_or_default();
+        data.audit_log
+            .record(&actor, "storage.reassign_orphan", target, &before, &after);
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/student_storage/dismiss_orphan/{old_slot_id}/{api_key}")]
+pub async fn dismiss_student_storage_orphan(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (old_slot_id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::StorageManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.student_storage.get_orphan(&old_slot_id);
+        let result = data.student_storage.dismiss_orphan(&old_slot_id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let target = before.as_ref().map(|x| x.occupied_details.college_id).unwrap_or_default();
+        data.audit_log
+            .record(&actor, "storage.dismiss_orphan", target, &before, &None::<()>);
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/lost_and_found/add_item/{api_key}")]
+pub async fn add_lost_item(
+    req: HttpRequest,
     path: web::Path<String>,
-    button_record: web::Json<ButtonRecord>,
+    body: web::Json<LostItemRequest>,
 ) -> Result<HttpResponse, Error> {
     let api_key = path.into_inner();
 
-    if API_KEYS.lock().await.validate_admin(&api_key) {
+    if has_permission(&req, &api_key, Permission::LostAndFoundManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let request = body.into_inner();
+
+        let mut item = LostItem::new(&request, request.college_id);
+
+        // Decoding and resizing the photo is slow, so it's done on a blocking thread before taking the database
+        if let Some(photo) = request.photo.clone() {
+            let extension = request.photo_extension.clone().unwrap_or("jpg".to_string());
+            let uuid = item.uuid.clone();
+
+            let result = web::block(move || save_lost_item_photo(&uuid, &photo, &extension))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|x| x);
+
+            if result.is_err() {
+                return Err(ErrorBadRequest(result.unwrap_err()));
+            }
+
+            item.has_photo = true;
+        }
+
         let mut data = MEMORY_DATABASE.lock().await;
 
-        let button_record = button_record.into_inner();
+        // If the item was found in a storage slot, it belongs to whoever had the slot last
+        let college_id = item.college_id.or_else(|| {
+            request.slot_id.as_ref().and_then(|slot_id| {
+                data.student_storage
+                    .slots
+                    .iter()
+                    .find(|slot| slot.get_id() == slot_id)
+                    .and_then(|slot| slot.get_last_owner())
+            })
+        });
 
-        data.button_log.add(button_record);
+        item.college_id = college_id;
+
+        let owner = college_id.and_then(|id| data.users.get_user_by_id(&id));
+
+        data.lost_and_found.add_item(item.clone());
+        data.audit_log
+            .record(&actor, "lost_and_found.add", format!("lost_item:{}", item.uuid), &None::<()>, &item);
+
+        drop(data);
+
+        if let Some(owner) = owner {
+            let email_result = send_individual_email(
+                owner.get_email(),
+                None,
+                "MAKE Lost and Found Notification".to_string(),
+                EMAIL_TEMPLATES.lock().await.get_lost_and_found(
+                    &item.description,
+                    &item.location_found,
+                    &item.get_hold_until_string(),
+                ),
+            )
+            .await;
+
+            if email_result.is_ok() {
+                item.owner_notified = true;
+
+                let mut data = MEMORY_DATABASE.lock().await;
+
+                if let Some(saved) = data
+                    .lost_and_found
+                    .items
+                    .iter_mut()
+                    .find(|x| x.uuid == item.uuid)
+                {
+                    saved.owner_notified = true;
+                }
+            }
+        }
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(item))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/lost_and_found/claim/{uuid}/{id_number}/{api_key}")]
+pub async fn claim_lost_item(
+    req: HttpRequest,
+    path: web::Path<(String, u64, String)>,
+) -> Result<HttpResponse, Error> {
+    let (uuid, id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::LostAndFoundManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.lost_and_found.get_item_by_uuid(&uuid);
+        let result = data.lost_and_found.claim(&uuid, id_number);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let after = data.lost_and_found.get_item_by_uuid(&uuid);
+        data.audit_log
+            .record(&actor, "lost_and_found.claim", id_number, &before, &after);
 
         Ok(HttpResponse::Ok()
             .status(http::StatusCode::CREATED)
@@ -428,38 +826,2397 @@ pub async fn add_button_log(
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct LoomRenderRequest {
-    pub file: String,
-    pub extension: String,
-    pub output_format: String,
-    pub desired_width: u32,
-    pub loom_width: u32,
-    pub invert: bool,
-    pub tabby_width: usize,
+#[post("/api/v1/lost_and_found/dispose/{uuid}/{api_key}")]
+pub async fn dispose_lost_item(req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (uuid, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::LostAndFoundManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.lost_and_found.get_item_by_uuid(&uuid);
+        let result = data.lost_and_found.dispose(&uuid);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let after = data.lost_and_found.get_item_by_uuid(&uuid);
+        data.audit_log
+            .record(&actor, "lost_and_found.dispose", format!("lost_item:{}", uuid), &before, &after);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
 }
 
-#[post("/api/v1/loom/render")]
-pub async fn render_loom(body: web::Json<LoomRenderRequest>) -> Result<HttpResponse, Error> {
-    let request = body.into_inner();
+#[post("/api/v1/inventory/create/{api_key}")]
+pub async fn create_inventory_item(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<InventoryItem>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
 
-    info!(
-        "Received request to render file of {} size",
-        request.file.len()
-    );
+    if has_permission(&req, &api_key, Permission::InventoryEdit).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
 
-    let result = render_loom_request(
-        &request.file,
-        &request.extension,
-        &request.loom_width,
-        &request.desired_width,
-        &request.tabby_width,
-        &request.invert,
-        &request.output_format,
-    );
+        let result = data.inventory.create_item(body.into_inner());
 
-    // Return result
-    Ok(HttpResponse::Ok()
-        .status(http::StatusCode::CREATED)
-        .json(result))
+        if result.is_err() {
+            return Err(ErrorConflict(result.unwrap_err()));
+        }
+
+        let item = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "inventory.create", format!("item:{}", item.id), &None::<()>, item);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/inventory/update/{id}/{api_key}")]
+pub async fn update_inventory_item(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    body: web::Json<InventoryItem>,
+) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryEdit).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.inventory.get_item_by_id(&id);
+        let result = data.inventory.edit_item(&id, body.into_inner());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "inventory.update", format!("item:{}", id), &before, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/inventory/delete/{id}/{api_key}")]
+pub async fn delete_inventory_item(req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryEdit).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.inventory.delete_item(&id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "inventory.delete", format!("item:{}", id), &result.unwrap(), &None::<()>);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/inventory/bulk/{api_key}")]
+pub async fn bulk_edit_inventory(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<InventoryBulkEdit>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.inventory.items.clone();
+        let errors = data.inventory.bulk_edit(body.into_inner());
+        let after = data.inventory.items.clone();
+
+        data.audit_log
+            .record_changes(&actor, "inventory.bulk_edit", "inventory", &before, &after, |x| x.id.clone());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(errors))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Body is the raw CSV text, with a header row
+#[post("/api/v1/inventory/import_csv/{api_key}")]
+pub async fn import_inventory_csv(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: String,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.inventory.items.clone();
+        let result = data.inventory.import_csv(&body);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let after = data.inventory.items.clone();
+        data.audit_log
+            .record_changes(&actor, "inventory.import_csv", "inventory", &before, &after, |x| x.id.clone());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/inventory/import_sheet/{api_key}")]
+pub async fn import_inventory_sheet(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let csv_text = Inventory::fetch_sheet().await;
+
+        if csv_text.is_err() {
+            return Err(ErrorBadGateway(csv_text.err().unwrap().to_string()));
+        }
+
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.inventory.items.clone();
+        let result = data.inventory.import_csv(&csv_text.unwrap());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let after = data.inventory.items.clone();
+        data.audit_log
+            .record_changes(&actor, "inventory.import_sheet", "inventory", &before, &after, |x| x.id.clone());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/inventory/set_sheet_import/{enabled}/{api_key}")]
+pub async fn set_inventory_sheet_import(
+    req: HttpRequest,
+    path: web::Path<(bool, String)>,
+) -> Result<HttpResponse, Error> {
+    let (enabled, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.inventory.sheet_import;
+        data.inventory.sheet_import = enabled;
+        data.audit_log
+            .record(&actor, "inventory.set_sheet_import", "inventory", &before, &enabled);
+
+        info!("Inventory sheet import set to {}", enabled);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Logs material as used, taking it out of stock.
+/// Crossing the item's reorder threshold files a restock notice.
+#[post("/api/v1/inventory/use_material/{api_key}")]
+pub async fn use_inventory_material(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<MaterialUsage>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryEdit).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let usage = body.into_inner();
+        let before = data.inventory.get_item_by_id(&usage.item_id);
+        let result = data.inventory.use_material(&usage);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log.record(
+            &actor,
+            "inventory.use_material",
+            format!("item:{}", usage.item_id),
+            &before,
+            result.as_ref().unwrap(),
+        );
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/inventory/add_restock_notice/{api_key}")]
+pub async fn add_restock_notice(
+    req: HttpRequest,
+    body: web::Json<RestockNotice>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryEdit).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let mut notice = body.into_inner();
+
+        notice.authorized = true;
+
+        let before = data
+            .inventory
+            .needs_restock
+            .iter()
+            .find(|x| x.is_open() && x.name.eq_ignore_ascii_case(&notice.name))
+            .cloned();
+
+        let notice = data.inventory.add_restock_notice(notice);
+
+        data.audit_log
+            .record(&actor, "inventory.add_restock", format!("restock:{}", notice.id), &before, &notice);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(notice))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/inventory/add_restock_notice_user")]
+pub async fn add_user_restock_notice(
+    body: web::Json<RestockNotice>,
+) -> Result<HttpResponse, Error> {
+    let mut data = MEMORY_DATABASE.lock().await;
+
+    // Users only get to say what they need, everything else is up to stewards
+    let notice = body.into_inner().from_user();
+
+    data.inventory.add_restock_notice(notice);
+
+    drop(data);
+
+    let _ = save_database().await;
+
+    Ok(HttpResponse::Ok()
+        .status(http::StatusCode::CREATED)
+        .finish())
+}
+
+/// Moves a restock request through approved, ordered and received (or rejected),
+/// and sets its assignee, vendor link, cost and expected arrival.
+/// Everyone who asked for the item is emailed once it's received.
+#[post("/api/v1/inventory/restock/{id}/{api_key}")]
+pub async fn update_restock_notice(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    body: web::Json<RestockUpdate>,
+) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryRestock).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let previous = data.inventory.get_restock_notice(&id);
+        let result = data.inventory.update_restock_notice(&id, body.into_inner());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "inventory.update_restock", format!("restock:{}", id), &previous, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        let notice = result.unwrap();
+
+        let newly_received = notice.status == RestockStatus::Received
+            && previous.map(|x| x.status) != Some(RestockStatus::Received);
+
+        if newly_received {
+            let body = EMAIL_TEMPLATES
+                .lock()
+                .await
+                .get_restock_fulfilled(&notice.name);
+
+            for email in notice.get_requester_emails() {
+                let _ = send_individual_email(
+                    email,
+                    None,
+                    format!("{} has been restocked", notice.name),
+                    body.clone(),
+                )
+                .await;
+            }
+        }
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(notice))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/usage/add_button_log/{api_key}")]
+pub async fn add_button_log(
+    req: HttpRequest,
+    path: web::Path<String>,
+    button_record: web::Json<ButtonRecord>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsageLog).await {
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let button_record = button_record.into_inner();
+
+        data.button_log.add(button_record);
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct LoomRenderRequest {
+    pub file: String,
+    pub extension: String,
+    pub output_format: String,
+    pub desired_width: u32,
+    pub loom_width: u32,
+    pub invert: bool,
+    pub tabby_width: usize,
+}
+
+#[post("/api/v1/loom/render")]
+pub async fn render_loom(body: web::Json<LoomRenderRequest>) -> Result<HttpResponse, Error> {
+    let request = body.into_inner();
+
+    info!(
+        "Received request to render file of {} size",
+        request.file.len()
+    );
+
+    let result = render_loom_request(
+        &request.file,
+        &request.extension,
+        &request.loom_width,
+        &request.desired_width,
+        &request.tabby_width,
+        &request.invert,
+        &request.output_format,
+    );
+
+    // Return result
+    Ok(HttpResponse::Ok()
+        .status(http::StatusCode::CREATED)
+        .json(result))
+}
+
+/// Renders QR code label sheets.
+/// PNG sheets are returned as a list of base64 strings, one per page,
+/// and PDF sheets are returned as a single PDF file.
+#[post("/api/v1/labels/render/{api_key}")]
+pub async fn render_labels(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<LabelRequest>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::LabelsPrint).await {
+        let request = body.into_inner();
+
+        let check = request.check_skip();
+
+        if check.is_err() {
+            return Err(ErrorBadRequest(check.unwrap_err()));
+        }
+
+        let data = MEMORY_DATABASE.lock().await;
+        let labels = request.collect_labels(&data.inventory, &data.student_storage, &data.printers);
+        drop(data);
+
+        if labels.is_empty() {
+            return Err(ErrorBadRequest("No labels selected".to_string()));
+        }
+
+        info!("Rendering {} labels", labels.len());
+
+        let rendered = web::block(move || render_labels_as(&labels, &request.layout, request.skip, &request.format))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|x| x);
+
+        if rendered.is_err() {
+            return Err(ErrorInternalServerError(rendered.unwrap_err()));
+        }
+
+        match rendered.unwrap() {
+            RenderedLabels::Png(pngs) => {
+                let pngs: Vec<String> = pngs.iter().map(|png| BASE64_STANDARD.encode(png)).collect();
+
+                Ok(HttpResponse::Ok()
+                    .status(http::StatusCode::CREATED)
+                    .json(pngs))
+            }
+            RenderedLabels::Pdf(pdf) => Ok(HttpResponse::Ok()
+                .status(http::StatusCode::CREATED)
+                .content_type("application/pdf")
+                .body(pdf)),
+        }
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/budget/set_allocation/{api_key}")]
+pub async fn set_budget_allocation(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<BudgetAllocation>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BudgetManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let allocation = body.into_inner();
+        let before = data
+            .budget
+            .categories
+            .iter()
+            .find(|x| x.name == allocation.category)
+            .and_then(|x| x.allocations_cents.get(&allocation.fiscal_period).cloned());
+
+        let result = data.budget.set_allocation(allocation.clone());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let target = format!("budget:{}:{}", allocation.category, allocation.fiscal_period);
+        data.audit_log
+            .record(&actor, "budget.set_allocation", target, &before, &allocation.amount_cents);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Records a purchase order. Line items can link to inventory items and restock
+/// requests, and linked requests that haven't been ordered yet are marked ordered.
+#[post("/api/v1/budget/purchase_order/add/{api_key}")]
+pub async fn add_purchase_order(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<PurchaseOrder>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BudgetManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { budget, inventory, users, .. } = &mut *data;
+
+        let mut order = body.into_inner();
+        order.ordered_by = actor.get_name(users);
+
+        let result = budget.add_purchase_order(order, inventory);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let order = result.as_ref().unwrap();
+        let target = format!("purchase_order:{}", order.id);
+        data.audit_log
+            .record(&actor, "budget.add_purchase_order", target, &None::<()>, order);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/budget/purchase_order/delete/{id}/{api_key}")]
+pub async fn delete_purchase_order(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BudgetManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.budget.delete_purchase_order(&id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let target = format!("purchase_order:{}", id);
+        data.audit_log
+            .record(&actor, "budget.delete_purchase_order", target, &result.unwrap(), &None::<()>);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Charges a user for material they used. Called by the kiosks and by the
+/// printer and laser cutter systems. Stock isn't touched, since prices are per
+/// unit of material (eg per g) rather than per inventory item, so anything
+/// counted in the inventory is taken out with `use_inventory_material`.
+#[post("/api/v1/billing/log_usage/{api_key}")]
+pub async fn log_material_usage(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<MaterialUsageRequest>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BillingLogUsage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.billing.log_usage(body.into_inner());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let record = result.unwrap();
+
+        data.audit_log
+            .record(&actor, "billing.log_usage", record.college_id, &None::<()>, &record);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(record))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/billing/set_price/{api_key}")]
+pub async fn set_material_price(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<MaterialPrice>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BillingManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let price = body.into_inner();
+        let before = data.billing.get_price(&price.material);
+        let result = data.billing.set_price(price.clone());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let after = data.billing.get_price(&price.material);
+        let target = format!("material:{}", price.material);
+        data.audit_log
+            .record(&actor, "billing.set_price", target, &before, &after);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Records a payment or waiver against a user's balance
+#[post("/api/v1/billing/add_credit/{api_key}")]
+pub async fn add_billing_credit(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<BillingCredit>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BillingManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let mut credit = body.into_inner();
+        credit.recorded_by = actor.get_name(&data.users);
+
+        let result = data.billing.add_credit(credit);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let credit = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "billing.add_credit", credit.college_id, &None::<()>, credit);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Emails everyone with activity their statement for a month, eg 2026-09.
+/// Statements for the previous month go out automatically.
+#[post("/api/v1/billing/send_statements/{month}/{api_key}")]
+pub async fn send_billing_statements(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (month, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::BillingManage).await {
+        if !is_month(&month) {
+            return Err(ErrorBadRequest(format!("Invalid month {}, expected eg 2026-09", month)));
+        }
+
+        let actor = Actor::from_request(&req, &api_key).await;
+        let before = MEMORY_DATABASE.lock().await.billing.last_statement_month.clone();
+
+        send_statements(&month).await;
+
+        let mut data = MEMORY_DATABASE.lock().await;
+        let after = data.billing.last_statement_month.clone();
+
+        data.audit_log
+            .record(&actor, "billing.send_statements", format!("statements:{}", month), &before, &after);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Adds a filament spool. Spools without a UUID are given one for their label.
+#[post("/api/v1/printers/spools/add/{api_key}")]
+pub async fn add_spool(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<Spool>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrintersManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.printers.add_spool(body.into_inner());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let spool = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "printers.add_spool", format!("spool:{}", spool.uuid), &None::<()>, spool);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Sets how many grams are left on a spool, eg after weighing it
+#[post("/api/v1/printers/spools/set_remaining/{uuid}/{grams}/{api_key}")]
+pub async fn set_spool_remaining(
+    req: HttpRequest,
+    path: web::Path<(String, f64, String)>,
+) -> Result<HttpResponse, Error> {
+    let (uuid, grams, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrintersManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.printers.get_spool(&uuid);
+        let result = data.printers.set_spool_remaining(&uuid, grams);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "printers.set_spool_remaining", format!("spool:{}", uuid), &before, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/printers/spools/delete/{uuid}/{api_key}")]
+pub async fn delete_spool(req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (uuid, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrintersManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.printers.delete_spool(&uuid);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "printers.delete_spool", format!("spool:{}", uuid), &result.unwrap(), &None::<()>);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/printers/load_spool/{printer_id}/{uuid}/{api_key}")]
+pub async fn load_spool(req: HttpRequest, path: web::Path<(String, String, String)>) -> Result<HttpResponse, Error> {
+    let (printer_id, uuid, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrintersManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.printers.get_loaded_spool(&printer_id);
+        let result = data.printers.load_spool(&printer_id, &uuid);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "printers.load_spool", format!("printer:{}", printer_id), &before, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/printers/unload_spool/{printer_id}/{api_key}")]
+pub async fn unload_spool(req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (printer_id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrintersManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.printers.unload_spool(&printer_id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "printers.unload_spool", format!("printer:{}", printer_id), result.as_ref().unwrap(), &None::<()>);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Starts counting the stock in a room, or one area of it
+#[post("/api/v1/inventory/audit/start/{api_key}")]
+pub async fn start_audit(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<AuditStart>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryAudit).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { audits, inventory, users, .. } = &mut *data;
+
+        let result = audits.start(body.into_inner(), actor.get_name(users), inventory);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let session = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "inventory.audit_start", format!("audit:{}", session.id), &None::<()>, session);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/inventory/audit/{id}/scan/{uuid}/{api_key}")]
+pub async fn scan_audit_uuid(
+    req: HttpRequest,
+    path: web::Path<(String, String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id, uuid, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryAudit).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { audits, inventory, .. } = &mut *data;
+
+        let result = audits.scan(&id, &uuid, inventory);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "inventory.audit_scan", format!("audit:{}", id), &None::<()>, &uuid);
+
+        // Scans come in quickly, so leave saving to the update loop
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/inventory/audit/{id}/count/{api_key}")]
+pub async fn enter_audit_count(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    body: web::Json<AuditCountEntry>,
+) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryAudit).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { audits, inventory, .. } = &mut *data;
+
+        let entry = body.into_inner();
+        let before = audits
+            .get_session(&id)
+            .and_then(|x| x.counts.into_iter().find(|c| c.item_id == entry.item_id));
+        let result = audits.count(&id, entry.clone(), inventory);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "inventory.audit_count", format!("audit:{}", id), &before, &entry);
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/inventory/audit/{id}/finish/{api_key}")]
+pub async fn finish_audit(req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryAudit).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { audits, inventory, .. } = &mut *data;
+
+        let result = audits.finish(&id, inventory);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "inventory.audit_finish", format!("audit:{}", id), &None::<()>, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/inventory/audit/{id}/cancel/{api_key}")]
+pub async fn cancel_audit(req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryAudit).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.audits.get_session(&id).map(|x| x.status);
+        let result = data.audits.cancel(&id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let after = data.audits.get_session(&id).map(|x| x.status);
+        data.audit_log
+            .record(&actor, "inventory.audit_cancel", format!("audit:{}", id), &before, &after);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Updates stock to match a finished audit's counts, recording each adjustment
+#[post("/api/v1/inventory/audit/{id}/apply/{api_key}")]
+pub async fn apply_audit(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    body: web::Json<AuditApply>,
+) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::InventoryManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { audits, inventory, users, .. } = &mut *data;
+
+        let before = inventory.items.clone();
+        let result = audits.apply(&id, body.into_inner(), actor.get_name(users), inventory);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let after = data.inventory.items.clone();
+        data.audit_log
+            .record_changes(&actor, "inventory.audit_apply", format!("audit:{}", id), &before, &after, |x| x.id.clone());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/maintenance/plans/add/{api_key}")]
+pub async fn add_maintenance_plan(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<MaintenancePlan>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::MaintenancePlan).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { maintenance, inventory, printers, .. } = &mut *data;
+
+        let result = maintenance.add_plan(body.into_inner(), inventory, printers);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let plan = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "maintenance.add_plan", format!("maintenance_plan:{}", plan.id), &None::<()>, plan);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/maintenance/plans/delete/{id}/{api_key}")]
+pub async fn delete_maintenance_plan(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::MaintenancePlan).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.maintenance.delete_plan(&id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "maintenance.delete_plan", format!("maintenance_plan:{}", id), &result.unwrap(), &None::<()>);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Records maintenance done on a machine or tool, restarting its plan if it had one
+#[post("/api/v1/maintenance/log/add/{api_key}")]
+pub async fn log_maintenance(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<MaintenanceLogRequest>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::MaintenanceLog).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { maintenance, inventory, printers, users, .. } = &mut *data;
+
+        let result = maintenance.log_maintenance(body.into_inner(), actor.get_name(users), inventory, printers);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let entry = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "maintenance.log", entry.target.to_string(), &None::<()>, entry);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Takes a machine or tool out of service, blocking checkouts, the print queue and interlocks
+#[post("/api/v1/maintenance/out_of_service/set/{api_key}")]
+pub async fn set_out_of_service(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<OutOfService>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::MaintenanceLog).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let mut flag = body.into_inner();
+        flag.set_by = actor.get_name(&data.users);
+
+        let target = flag.target.clone();
+        let before = data.maintenance.is_out_of_service(&target);
+
+        data.maintenance.set_out_of_service(flag);
+
+        let after = data.maintenance.is_out_of_service(&target);
+        data.audit_log
+            .record(&actor, "maintenance.set_out_of_service", target.to_string(), &before, &after);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/maintenance/out_of_service/clear/{api_key}")]
+pub async fn set_in_service(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<MaintenanceTarget>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::MaintenanceLog).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let target = body.into_inner();
+        let before = data.maintenance.is_out_of_service(&target);
+
+        data.maintenance.set_in_service(&target);
+
+        data.audit_log
+            .record(&actor, "maintenance.set_in_service", target.to_string(), &before, &None::<()>);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Files an incident report from the kiosk. Reports can only be read back by admins.
+#[post("/api/v1/incidents/report/{api_key}")]
+pub async fn report_incident(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<Incident>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::IncidentsReport).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { incidents, checkout_log, inventory, maintenance, users, suspensions, .. } = &mut *data;
+
+        let result = incidents.report(body.into_inner(), checkout_log, inventory, maintenance, users);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let incident = result.unwrap();
+        let suspension = incidents.apply_strikes(&incident, actor.verified_id().unwrap_or(0), users, suspensions);
+
+        let target = incident
+            .college_id
+            .map(|x| x.to_string())
+            .unwrap_or(format!("incident:{}", incident.id));
+
+        data.audit_log
+            .record(&actor, "incidents.report", target, &None::<()>, &incident);
+
+        let suspended = record_strike_suspension(&mut data, &actor, suspension);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        if let Some((suspension, user)) = suspended {
+            suspension.notify(&user).await;
+        }
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/incidents/update/{id}/{api_key}")]
+pub async fn update_incident(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    body: web::Json<IncidentUpdate>,
+) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::IncidentsManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { incidents, users, suspensions, .. } = &mut *data;
+
+        let before = incidents.get_incident(&id);
+        let result = incidents.update(&id, body.into_inner());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let incident = result.as_ref().unwrap();
+        let suspension = incidents.apply_strikes(incident, actor.verified_id().unwrap_or(0), users, suspensions);
+
+        let target = incident
+            .college_id
+            .map(|x| x.to_string())
+            .unwrap_or(format!("incident:{}", incident.id));
+
+        data.audit_log
+            .record(&actor, "incidents.update", target, &before, incident);
+
+        let suspended = record_strike_suspension(&mut data, &actor, suspension);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        if let Some((suspension, user)) = suspended {
+            suspension.notify(&user).await;
+        }
+
+        Ok(HttpResponse::Ok().json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Audits a suspension from reaching the strike limit, returning it with the user to email
+fn record_strike_suspension(data: &mut Data, actor: &Actor, suspension: Option<Suspension>) -> Option<(Suspension, User)> {
+    let suspension = suspension?;
+    let user = data.users.get_user_by_id(&suspension.college_id)?;
+
+    data.audit_log
+        .record(actor, "users.suspend", suspension.college_id, &None::<()>, &suspension);
+
+    Some((suspension, user))
+}
+
+/// Sets how many strikes get a user suspended. A limit of 0 turns automatic suspensions off.
+#[post("/api/v1/incidents/strike_limit/{limit}/{api_key}")]
+pub async fn set_strike_limit(
+    req: HttpRequest,
+    path: web::Path<(u64, String)>,
+) -> Result<HttpResponse, Error> {
+    let (limit, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::IncidentsManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.incidents.ban_after_strikes;
+
+        data.incidents.ban_after_strikes = Some(limit).filter(|x| *x > 0);
+
+        let after = data.incidents.ban_after_strikes;
+        data.audit_log
+            .record(&actor, "incidents.strike_limit", "incidents", &before, &after);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Adds or replaces the questions for a quiz. Once a quiz has questions it's no longer fetched from the sheets.
+#[post("/api/v1/quizzes/banks/set/{api_key}")]
+pub async fn set_question_bank(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<QuestionBank>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::QuizzesManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        if !data.quiz_registry.exists(&body.quiz) {
+            return Err(ErrorBadRequest(format!("No quiz found with id {}", body.quiz)));
+        }
+
+        let before = data.quiz_banks.get_bank(&body.quiz);
+        let result = data.quiz_banks.set_bank(body.into_inner());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let bank = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "quizzes.set_bank", format!("quiz:{}", bank.quiz), &before, bank);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Starts a quiz attempt from the website, giving back the questions without their answers.
+/// Only for people who are already users, see `start_quiz_attempt_new_user`.
+#[post("/api/v1/quizzes/attempt/start/{quiz_name}/{id_number}")]
+pub async fn start_quiz_attempt(
+    path: web::Path<(QuizName, u64)>,
+    body: web::Json<QuizTaker>,
+) -> Result<HttpResponse, Error> {
+    let (quiz_name, id_number) = path.into_inner();
+
+    let mut data = MEMORY_DATABASE.lock().await;
+    let Data { quiz_banks, users, quiz_registry, .. } = &mut *data;
+
+    let result = quiz_banks.start_attempt(&quiz_name, id_number, body.into_inner(), false, users, quiz_registry);
+
+    if result.is_err() {
+        return Err(ErrorBadRequest(result.unwrap_err()));
+    }
+
+    drop(data);
+
+    let _ = save_database().await;
+
+    Ok(HttpResponse::Ok()
+        .status(http::StatusCode::CREATED)
+        .json(result.unwrap()))
+}
+
+/// Starts a quiz attempt for someone who may not be a user yet, adding them from the name and email in the body
+#[post("/api/v1/quizzes/attempt/start/{quiz_name}/{id_number}/{api_key}")]
+pub async fn start_quiz_attempt_new_user(
+    req: HttpRequest,
+    path: web::Path<(QuizName, u64, String)>,
+    body: web::Json<QuizTaker>,
+) -> Result<HttpResponse, Error> {
+    let (quiz_name, id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersAdd).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { quiz_banks, users, quiz_registry, .. } = &mut *data;
+
+        let existed = users.exists(&id_number);
+        let result = quiz_banks.start_attempt(&quiz_name, id_number, body.into_inner(), true, users, quiz_registry);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        if !existed {
+            let user = data.users.get_user_by_id(&id_number);
+            data.audit_log.record(&actor, "users.add", id_number, &None::<()>, &user);
+        }
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/quizzes/attempt/submit/{id}")]
+pub async fn submit_quiz_attempt(
+    path: web::Path<String>,
+    body: web::Json<AttemptSubmission>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+
+    let mut data = MEMORY_DATABASE.lock().await;
+    let Data { quiz_banks, users, .. } = &mut *data;
+
+    let result = quiz_banks.submit_attempt(&id, body.into_inner(), users);
+
+    if result.is_err() {
+        return Err(ErrorBadRequest(result.unwrap_err()));
+    }
+
+    drop(data);
+
+    let _ = save_database().await;
+
+    Ok(HttpResponse::Ok().json(result.unwrap()))
+}
+
+/// Adds or replaces a quiz in the registry
+#[post("/api/v1/quizzes/registry/set/{api_key}")]
+pub async fn set_quiz_definition(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<QuizDefinition>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::QuizzesManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.quiz_registry.get_quiz(&body.id);
+        let result = data.quiz_registry.set_quiz(body.into_inner());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let quiz = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "quizzes.set_definition", format!("quiz:{}", quiz.id), &before, quiz);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/quizzes/registry/delete/{quiz_name}/{api_key}")]
+pub async fn delete_quiz_definition(
+    req: HttpRequest,
+    path: web::Path<(QuizName, String)>,
+) -> Result<HttpResponse, Error> {
+    let (quiz_name, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::QuizzesManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.quiz_registry.delete_quiz(&quiz_name);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let target = format!("quiz:{}", quiz_name);
+        data.audit_log
+            .record(&actor, "quizzes.delete_definition", target, &result.unwrap(), &None::<()>);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Records a steward signing someone off on a skill, from the management kiosk.
+/// The steward is whoever is signed in with `X-User-Token`.
+#[post("/api/v1/sign_offs/add/{api_key}")]
+pub async fn add_sign_off(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<SignOffRequest>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::SignOffsAdd).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { sign_offs, users, quiz_registry, .. } = &mut *data;
+
+        let result = sign_offs.sign_off(body.into_inner(), actor.verified_id(), users, quiz_registry);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let sign_off = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "sign_offs.add", sign_off.college_id, &None::<()>, sign_off);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/sign_offs/revoke/{id}/{api_key}")]
+pub async fn revoke_sign_off(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::SignOffsManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.sign_offs.revoke(&id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let sign_off = result.as_ref().unwrap();
+        let before = SignOff {
+            revoked: false,
+            ..sign_off.clone()
+        };
+
+        data.audit_log
+            .record(&actor, "sign_offs.revoke", sign_off.college_id, &before, sign_off);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Adds or replaces a skill that needs a steward sign-off
+#[post("/api/v1/sign_offs/skills/set/{api_key}")]
+pub async fn set_skill(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<Skill>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::SignOffsManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { sign_offs, quiz_registry, inventory, .. } = &mut *data;
+
+        let before = sign_offs.get_skill(&body.id);
+        let result = sign_offs.set_skill(body.into_inner(), quiz_registry, inventory);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let skill = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "sign_offs.set_skill", format!("skill:{}", skill.id), &before, skill);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/sign_offs/skills/delete/{skill}/{api_key}")]
+pub async fn delete_skill(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (skill, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::SignOffsManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.sign_offs.delete_skill(&skill);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let target = format!("skill:{}", skill);
+        data.audit_log
+            .record(&actor, "sign_offs.delete_skill", target, &result.unwrap(), &None::<()>);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Merges one user into another, moving their quizzes, checkouts, storage slots and queue entries
+#[post("/api/v1/users/identities/merge/{from_id}/{into_id}/{api_key}")]
+pub async fn merge_identities(
+    req: HttpRequest,
+    path: web::Path<(u64, u64, String)>,
+) -> Result<HttpResponse, Error> {
+    let (from_id, into_id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersManageIdentities).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = vec![data.users.get_user_by_id(&from_id), data.users.get_user_by_id(&into_id)];
+        let result = merge_users(&mut data, from_id, into_id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "users.merge", into_id, &before, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Moves a user and their records to a new college ID
+#[post("/api/v1/users/identities/rekey/{old_id}/{new_id}/{api_key}")]
+pub async fn rekey_identity(
+    req: HttpRequest,
+    path: web::Path<(u64, u64, String)>,
+) -> Result<HttpResponse, Error> {
+    let (old_id, new_id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersManageIdentities).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.users.get_user_by_id(&old_id);
+        let result = rekey_user(&mut data, old_id, new_id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "users.rekey", new_id, &before, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/users/identities/split/{api_key}")]
+pub async fn split_identity(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<SplitRequest>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersManageIdentities).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.users.get_user_by_id(&body.college_id);
+        let target = body.college_id;
+        let result = split_user(&mut data, body.into_inner());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "users.split", target, &before, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/users/identities/email_alias/{email}/{id_number}/{api_key}")]
+pub async fn add_email_alias(
+    req: HttpRequest,
+    path: web::Path<(String, u64, String)>,
+) -> Result<HttpResponse, Error> {
+    let (email, id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersManageIdentities).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { identities, users, .. } = &mut *data;
+
+        let before = identities.email_aliases.get(&email.trim().to_lowercase()).cloned();
+        let result = identities.add_email_alias(&email, id_number, users);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "users.email_alias", id_number, &before, &id_number);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Body is the raw roster CSV, with a header row. Creates or updates a user for each row.
+#[post("/api/v1/users/roster/import_csv/{api_key}")]
+pub async fn import_roster_csv_route(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: String,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersImportRoster).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { users, identities, privacy, .. } = &mut *data;
+
+        let result = import_roster_csv(users, identities, privacy, &body);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "users.import_roster", "roster", &None::<()>, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Body is a list of objects keyed by the same headers as the roster CSV
+#[post("/api/v1/users/roster/import_json/{api_key}")]
+pub async fn import_roster_json_route(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<RosterJson>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersImportRoster).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { users, identities, privacy, .. } = &mut *data;
+
+        let result = import_roster_json(users, identities, privacy, body.into_inner());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "users.import_roster", "roster", &None::<()>, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Asks for a user's data to be deleted. Nothing happens until an admin approves it.
+#[post("/api/v1/privacy/deletion/request/{api_key}")]
+pub async fn request_data_deletion(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<DeletionRequest>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrivacyRequestDeletion).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { privacy, users, .. } = &mut *data;
+
+        let result = privacy.request_deletion(body.into_inner(), users);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let request = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "privacy.request_deletion", request.college_id, &None::<()>, request);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Anonymizes the user in a deletion request, keeping their records for stats
+#[post("/api/v1/privacy/deletion/approve/{id}/{api_key}")]
+pub async fn approve_data_deletion(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrivacyManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = approve_deletion(&mut data, &id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let request = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "privacy.approve_deletion", request.college_id, &None::<()>, request);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/privacy/deletion/reject/{id}/{api_key}")]
+pub async fn reject_data_deletion(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrivacyManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.privacy.reject_deletion(&id);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let request = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "privacy.reject_deletion", request.college_id, &None::<()>, request);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Sets how many years history is kept before it's anonymized, and applies it straight away. 0 turns it off.
+#[post("/api/v1/privacy/retention/{years}/{api_key}")]
+pub async fn set_retention_years(
+    req: HttpRequest,
+    path: web::Path<(u64, String)>,
+) -> Result<HttpResponse, Error> {
+    let (years, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::PrivacyManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.privacy.retention_years;
+
+        data.privacy.set_retention_years(years);
+        let anonymized = apply_retention(&mut data);
+
+        let after = data.privacy.retention_years;
+        data.audit_log
+            .record(&actor, "privacy.set_retention", "privacy", &before, &after);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().json(anonymized))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Adds or replaces a role, eg `{"id": "storage_coordinator", "permissions": ["storage.view", "storage.manage"]}`
+#[post("/api/v1/roles/set/{api_key}")]
+pub async fn set_role(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<Role>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::RolesManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.roles.get_role(&body.id.trim().to_lowercase());
+        let result = data.roles.set_role(body.into_inner());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let role = result.as_ref().unwrap();
+        data.audit_log
+            .record(&actor, "roles.set", format!("role:{}", role.id), &before, role);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Removes a role, or puts a built-in role back to its defaults
+#[post("/api/v1/roles/delete/{role}/{api_key}")]
+pub async fn delete_role(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (role, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::RolesManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let result = data.roles.delete_role(&role);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let after = data.roles.get_role(&role);
+        data.audit_log
+            .record(&actor, "roles.delete", format!("role:{}", role), &result.unwrap(), &after);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/roles/assign/{id_number}/{role}/{api_key}")]
+pub async fn assign_role(
+    req: HttpRequest,
+    path: web::Path<(u64, String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id_number, role, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::RolesManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { roles, users, .. } = &mut *data;
+
+        let before = roles.get_assigned_roles(&id_number);
+        let result = roles.assign_role(id_number, &role, users);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "roles.assign", id_number, &before, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+#[post("/api/v1/roles/unassign/{id_number}/{role}/{api_key}")]
+pub async fn unassign_role(
+    req: HttpRequest,
+    path: web::Path<(u64, String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id_number, role, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::RolesManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.roles.get_assigned_roles(&id_number);
+        let result = data.roles.unassign_role(id_number, &role);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        data.audit_log
+            .record(&actor, "roles.unassign", id_number, &before, result.as_ref().unwrap());
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().json(result.unwrap()))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Issues a user token, which the website and kiosks send in `X-User-Token` to act as that user.
+/// The token is only ever returned here.
+#[post("/api/v1/users/tokens/issue/{id_number}/{api_key}")]
+pub async fn issue_user_token(
+    req: HttpRequest,
+    path: web::Path<(u64, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::RolesManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { roles, users, .. } = &mut *data;
+
+        let result = roles.issue_token(id_number, users);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let issued = result.unwrap();
+
+        // Only the token's ID goes in the log
+        data.audit_log
+            .record(&actor, "users.issue_token", id_number, &None::<()>, &issued.id);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(issued))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Revokes every token a user has
+#[post("/api/v1/users/tokens/revoke/{id_number}/{api_key}")]
+pub async fn revoke_user_tokens(
+    req: HttpRequest,
+    path: web::Path<(u64, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id_number, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::RolesManage).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let revoked = data.roles.revoke_tokens(&id_number);
+
+        data.audit_log
+            .record(&actor, "users.revoke_tokens", id_number, &revoked, &0);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().json(revoked))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Suspends a user and emails them why. The issuing admin is whoever is signed in with `X-User-Token`.
+#[post("/api/v1/suspensions/add/{api_key}")]
+pub async fn suspend_user(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<SuspensionRequest>,
+) -> Result<HttpResponse, Error> {
+    let api_key = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersBan).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+        let Data { suspensions, users, .. } = &mut *data;
+
+        let result = suspensions.suspend(body.into_inner(), actor.verified_id(), users);
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let suspension = result.unwrap();
+        let user = data.users.get_user_by_id(&suspension.college_id).unwrap();
+
+        data.audit_log
+            .record(&actor, "users.suspend", suspension.college_id, &None::<()>, &suspension);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        suspension.notify(&user).await;
+
+        Ok(HttpResponse::Ok()
+            .status(http::StatusCode::CREATED)
+            .json(suspension))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
+}
+
+/// Ends a suspension early, the user is emailed on the next update
+#[post("/api/v1/suspensions/lift/{id}/{api_key}")]
+pub async fn lift_suspension(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (id, api_key) = path.into_inner();
+
+    if has_permission(&req, &api_key, Permission::UsersBan).await {
+        let actor = Actor::from_request(&req, &api_key).await;
+        let mut data = MEMORY_DATABASE.lock().await;
+
+        let before = data.suspensions.get_suspension(&id);
+        let result = data.suspensions.lift(&id, actor.verified_id());
+
+        if result.is_err() {
+            return Err(ErrorBadRequest(result.unwrap_err()));
+        }
+
+        let suspension = result.unwrap();
+        data.audit_log
+            .record(&actor, "users.lift_suspension", suspension.college_id, &before, &suspension);
+
+        drop(data);
+
+        let _ = save_database().await;
+
+        Ok(HttpResponse::Ok().json(suspension))
+    } else {
+        Ok(HttpResponse::Unauthorized().finish())
+    }
 }
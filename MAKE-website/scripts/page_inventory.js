@@ -216,7 +216,7 @@ function generateInventoryDiv(result, kiosk_mode = false) {
         const quantity = document.createElement("div");
         quantity.classList.add("inventory-result-quantity");
 
-        if (item.quantity >= 0) {
+        if (typeof item.quantity === "number") {
             quantity.classList.add("number");
             if (item.checked_quantity > 0) {
                 quantity.innerText = `${item.quantity - item.checked_quantity}/${item.quantity}`;
@@ -224,19 +224,16 @@ function generateInventoryDiv(result, kiosk_mode = false) {
                 quantity.innerText = `${item.quantity}`;
             }
         } else {
-            console.log("Quantity is less than 0:", item.quantity);
-            console.log("type:", typeof item.quantity);
-
             switch (item.quantity) {
-                case -1 || "-1":
+                case "Low":
                     quantity.classList.add("low");
                     quantity.innerText += "Low";
                     break;
-                case -2 || "-2":
+                case "Medium":
                     quantity.classList.add("medium");
                     quantity.innerText += "Medium";
                     break;
-                case -3 || "-3":
+                case "High":
                     quantity.classList.add("high");
                     quantity.innerText += "High";
                     break;
@@ -289,6 +286,20 @@ function generateInventoryDiv(result, kiosk_mode = false) {
         lower_div.appendChild(qr_code);
     }
 
+    if (item.required_quizzes && item.required_quizzes.length > 0) {
+        const required_quizzes = document.createElement("div");
+        required_quizzes.classList.add("inventory-result-lower-detail");
+        required_quizzes.innerText = `Required Quizzes: ${item.required_quizzes.join(", ")}`;
+        lower_div.appendChild(required_quizzes);
+    }
+
+    if (item.min_auth_level) {
+        const min_auth_level = document.createElement("div");
+        min_auth_level.classList.add("inventory-result-lower-detail");
+        min_auth_level.innerText = `Requires: ${item.min_auth_level}`;
+        lower_div.appendChild(min_auth_level);
+    }
+
     const show_lower_div_button = document.createElement("button");
     show_lower_div_button.classList.add("inventory-result-show-lower-div");
     show_lower_div_button.classList.add("grayed-out");